@@ -0,0 +1,60 @@
+use crate::book::{BookSelection, OpeningBook};
+use crate::search::AnalysisOptions;
+use crate::{Game, Move};
+
+/**
+ * The computer player, it plays a move from its opening book if the position
+ * is in the book and otherwise searches for the best move
+ */
+#[derive(Clone, Debug)]
+pub struct Ai {
+    pub options: AnalysisOptions,
+    pub book: Option<OpeningBook>,
+    pub book_selection: BookSelection,
+}
+
+impl Default for Ai {
+    fn default() -> Ai {
+        Ai {
+            options: AnalysisOptions::default(),
+            book: None,
+            book_selection: BookSelection::Weighted,
+        }
+    }
+}
+
+impl Ai {
+
+    /**
+     * Picks the move to play in the position, returns None if there are no legal moves
+     */
+    pub fn choose_move(&self, game: &Game) -> Option<Move> {
+        if let Some(mv) = self.book.as_ref().and_then(|book| book.choose_move(game, self.book_selection)) {
+            return Some(mv);
+        }
+        game.analyse(&self.options).best_move()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ai;
+    use crate::book::{BookSelection, OpeningBook};
+    use crate::search::AnalysisOptions;
+    use crate::Game;
+
+    #[test]
+    fn ai_plays_book_move_before_searching() {
+        let mut game = Game::new();
+        game.init_board();
+        let ai = Ai {
+            book: Some(OpeningBook::from_pgn("1. a3 1-0", 1)),
+            book_selection: BookSelection::BestOnly,
+            options: AnalysisOptions { depth: 2, ..AnalysisOptions::default() },
+        };
+        assert_eq!(ai.choose_move(&game).unwrap().to_uci(), "a2a3");
+
+        game.play(game.parse_move("a2a3").unwrap());
+        assert!(ai.choose_move(&game).is_some());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::result::GameResult;
+use crate::variant::Variant;
+use crate::{Game, Move, ColorTurn, _NONE, _PAWN, _QUEEN, _KING, _WHITE, _BLACK};
+
+/* What each piece left on the board costs the player in the evaluation */
+const PIECE_COST: i32 = 100;
+
+/**
+ * Antichess, also called losing chess. A player who can take a piece must take one,
+ * the king is a piece like the others that can be taken and is never in check, pawns
+ * may also promote to kings, and there is no castling. A player wins by losing all
+ * their pieces or by having no moves
+ */
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Antichess;
+
+impl Variant for Antichess {
+    fn name(&self) -> &str {
+        "Antichess"
+    }
+
+    fn start_fen(&self) -> &str {
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1"
+    }
+
+    fn generate_moves(&self, game: &Game) -> Vec<Move> {
+        let mut moves: Vec<Move> = game.standard_moves().into_iter()
+            .filter(|mv| game.castling_squares(*mv).is_none())
+            .collect();
+        let king_promotions: Vec<Move> = moves.iter()
+            .filter(|mv| mv.promotion == _QUEEN)
+            .map(|mv| Move { promotion: _KING, ..*mv })
+            .collect();
+        moves.extend(king_promotions);
+
+        let is_capture = |mv: &Move| {
+            let (from, to) = (&game.board[mv.from as usize], &game.board[mv.to as usize]);
+            to[0] != _NONE || (from[0] == _PAWN && mv.from % 8 != mv.to % 8)
+        };
+        if moves.iter().any(is_capture) {
+            moves.retain(is_capture);
+        }
+        moves
+    }
+
+    fn is_legal(&self, _game: &Game) -> bool {
+        true
+    }
+
+    fn is_check(&self, _game: &Game) -> bool {
+        false
+    }
+
+    fn outcome(&self, game: &Game) -> Option<GameResult> {
+        [_WHITE, _BLACK].iter().find(|color| game.board.iter().all(|square| square[0] == _NONE || square[1] != **color))
+            .map(|color| GameResult::win_for(if *color == _WHITE { ColorTurn::White } else { ColorTurn::Black }))
+    }
+
+    fn no_moves_result(&self, game: &Game) -> GameResult {
+        GameResult::win_for(game.get_game_turn())
+    }
+
+    /// Fewer pieces are better, whatever they are worth in standard chess.
+    fn evaluate(&self, game: &Game) -> i32 {
+        let own = game.color_to_move();
+        game.board.iter().filter(|square| square[0] != _NONE)
+            .map(|square| if square[1] == own { -PIECE_COST } else { PIECE_COST })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Antichess;
+    use crate::player::Termination;
+    use crate::result::GameResult;
+    use crate::search::AnalysisOptions;
+    use crate::{Ai, Game};
+    use std::sync::Arc;
+
+    fn start(fen: &str) -> Game {
+        let mut game = Game::new();
+        game.set_variant(Arc::new(Antichess));
+        game.load_fen_board(fen.to_string());
+        game
+    }
+
+    #[test]
+    fn captures_are_forced() {
+        let mut game = start("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1");
+        game.play(game.parse_move("e2e4").unwrap());
+        game.play(game.parse_move("d7d5").unwrap());
+        assert_eq!(game.legal_moves().len(), 1);
+        assert!(game.parse_move("d2d4").is_none());
+        assert_eq!(game.move_to_san(game.legal_moves()[0]), "exd5");
+    }
+
+    #[test]
+    fn kings_are_taken_and_promoted_to() {
+        // the king is not in check and may be left where the queen takes it
+        let mut game = start("4k3/P7/8/8/8/8/8/4K2q w - - 0 1");
+        assert!(!game.is_king_check());
+        let promotion = game.parse_san("a8=K").unwrap();
+        assert_eq!(promotion.to_uci(), "a7a8k");
+        assert!(game.play(promotion));
+        game.play(game.parse_san("Qxe1").unwrap());
+        assert_eq!(game.to_fen(), "K3k3/8/8/8/8/8/8/4q3 w - - 0 2");
+    }
+
+    #[test]
+    fn losing_every_piece_wins() {
+        // white must take the last black piece, which wins for black
+        let mut game = start("8/8/8/8/p7/8/8/R7 w - - 0 1");
+        assert_eq!(game.legal_moves().len(), 1);
+        game.play(game.parse_san("Rxa4").unwrap());
+        assert_eq!(game.result(), GameResult::BlackWins);
+        assert_eq!(game.termination(), Some(Termination::Variant));
+
+        // the ai gives its rook away to the pawn
+        let game = start("8/8/8/8/8/1p6/8/R7 w - - 0 1");
+        let ai = Ai { options: AnalysisOptions { depth: 2, ..AnalysisOptions::default() }, ..Ai::default() };
+        assert_eq!(ai.choose_move(&game).unwrap().to_uci(), "a1a2");
+
+        // a player with no moves wins too
+        let game = start("8/8/8/8/8/p7/P7/8 w - - 0 1");
+        assert_eq!(game.result(), GameResult::WhiteWins);
+        assert_eq!(game.termination(), Some(Termination::Stalemate));
+    }
+}
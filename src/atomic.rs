@@ -0,0 +1,198 @@
+use crate::eval;
+use crate::result::GameResult;
+use crate::variant::Variant;
+use crate::{Game, Move, ColorTurn, _NONE, _PAWN, _ROOK, _KING, _WHITE, _BLACK};
+
+/* The bonus for each piece next to the other king the player can take, which would blow up the king */
+const EXPLOSION_BONUS: i32 = 80;
+/* The bonus for the player that is behind when the kings stand together, where neither can be blown up */
+const TOUCHING_KINGS_BONUS: i32 = 60;
+
+/**
+ * Atomic chess, every capture is an explosion that takes away the capturing piece and
+ * every piece but the pawns on the squares around the capture. Kings may not capture,
+ * and a player wins by blowing up the other king. A player may not blow up their own
+ * king, and kings standing next to each other can not check each other since taking
+ * the other king would blow up both
+ */
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Atomic;
+
+impl Variant for Atomic {
+    fn name(&self) -> &str {
+        "Atomic"
+    }
+
+    fn generate_moves(&self, game: &Game) -> Vec<Move> {
+        let mut moves = game.standard_moves();
+        // a Chess960 king taking its own rook is castling, not a capture
+        moves.retain(|mv| {
+            let (from, to) = (&game.board[mv.from as usize], &game.board[mv.to as usize]);
+            from[0] != _KING || to[0] == _NONE || to[1] == from[1]
+        });
+        moves
+    }
+
+    fn make_move(&self, game: &mut Game, mv: Move) {
+        let captured = game.make_standard_move(mv);
+        if captured[0] == _NONE {
+            return;
+        }
+        game.put_piece(mv.to, _NONE, 2);
+        for _pos in Game::king_neighbours(mv.to) {
+            let piece = game.board[_pos as usize][0];
+            if piece != _NONE && piece != _PAWN {
+                game.put_piece(_pos, _NONE, 2);
+            }
+        }
+
+        // a rook that is blown up can not castle any more
+        for i in 0..4 {
+            let owner = if i < 2 { _WHITE } else { _BLACK };
+            if game.board[game.castling_rooks[i] as usize] != [_ROOK, owner] {
+                game.castling[i] = false;
+            }
+        }
+    }
+
+    fn is_legal(&self, game: &Game) -> bool {
+        let mover = Game::opponent(game.color_to_move());
+        if game.find_king(mover).is_none() {
+            return false;
+        }
+        // blowing up the other king wins even if the own king was in check
+        game.find_king(game.color_to_move()).is_none() || game.kings_touch() || !game.is_own_king_left_in_check()
+    }
+
+    fn is_check(&self, game: &Game) -> bool {
+        !game.kings_touch() && game.is_king_attacked()
+    }
+
+    fn outcome(&self, game: &Game) -> Option<GameResult> {
+        if game.find_king(_WHITE).is_none() {
+            Some(GameResult::win_for(ColorTurn::Black))
+        } else if game.find_king(_BLACK).is_none() {
+            Some(GameResult::win_for(ColorTurn::White))
+        } else {
+            None
+        }
+    }
+
+    fn evaluate(&self, game: &Game) -> i32 {
+        let score = eval::standard_evaluation(game);
+        if game.kings_touch() {
+            return if score < 0 { score + TOUCHING_KINGS_BONUS } else { score - TOUCHING_KINGS_BONUS };
+        }
+        let mut threats = 0;
+        for color in [_WHITE, _BLACK] {
+            let king = match game.find_king(Game::opponent(color)) {
+                Some(king) => king,
+                None => continue,
+            };
+            let targets = Game::king_neighbours(king).filter(|_pos| {
+                let square = &game.board[*_pos as usize];
+                square[0] != _NONE && square[1] != color && game.is_square_attacked(*_pos, color)
+            }).count() as i32;
+            threats += if color == game.color_to_move() { targets } else { -targets };
+        }
+        score + threats * EXPLOSION_BONUS
+    }
+}
+
+impl Game {
+
+    /// The squares a king on the square could move to.
+    fn king_neighbours(_pos: u8) -> impl Iterator<Item = u8> {
+        let (file, rank) = ((_pos % 8) as i8, (_pos / 8) as i8);
+        (-1..=1).flat_map(move |x| (-1..=1).map(move |y| (file + x, rank + y)))
+            .filter(move |(f, r)| (0..8).contains(f) && (0..8).contains(r) && (*f, *r) != (file, rank))
+            .map(|(f, r)| (r * 8 + f) as u8)
+    }
+
+    /// Checks if the two kings stand next to each other.
+    fn kings_touch(&self) -> bool {
+        match (self.find_king(_WHITE), self.find_king(_BLACK)) {
+            (Some(white), Some(black)) => Game::king_neighbours(white).any(|_pos| _pos == black),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Atomic, TOUCHING_KINGS_BONUS};
+    use crate::eval;
+    use crate::perft;
+    use crate::player::Termination;
+    use crate::result::GameResult;
+    use crate::search::AnalysisOptions;
+    use crate::variant::Variant;
+    use crate::{Ai, Game};
+    use std::sync::Arc;
+
+    fn start(fen: &str) -> Game {
+        let mut game = Game::new();
+        game.set_variant(Arc::new(Atomic));
+        game.load_fen_board(fen.to_string());
+        game
+    }
+
+    #[test]
+    fn captures_explode() {
+        let mut game = start("4k3/8/8/2nrb3/2pQp3/8/8/4K3 w - - 0 1");
+        game.play(game.parse_san("Qxd5").unwrap());
+        // the pawns stay, everything else around d5 is gone
+        assert_eq!(game.to_fen(), "4k3/8/8/8/2p1p3/8/8/4K3 b - - 0 1");
+        game.undo();
+        assert_eq!(game.to_fen(), "4k3/8/8/2nrb3/2pQp3/8/8/4K3 w - - 0 1");
+    }
+
+    #[test]
+    fn blowing_up_the_king_wins() {
+        // the queen can not take on d2 next to her own king, but blowing up the black king wins
+        let mut game = start("4k3/4p3/8/8/8/8/3qQ3/4K3 w - - 0 1");
+        assert!(game.parse_san("Kxd2").is_none());
+        assert!(game.parse_san("Qxd2").is_none());
+        let ai = Ai { options: AnalysisOptions { depth: 2, ..AnalysisOptions::default() }, ..Ai::default() };
+        assert_eq!(ai.choose_move(&game).unwrap().to_uci(), "e2e7");
+        game.play(game.parse_san("Qxe7").unwrap());
+        assert_eq!(game.result(), GameResult::WhiteWins);
+        assert_eq!(game.termination(), Some(Termination::Variant));
+    }
+
+    #[test]
+    fn touching_kings_give_no_check() {
+        let game = start("8/8/8/8/3kK2r/8/8/8 w - - 0 1");
+        assert!(!game.is_king_check());
+        // the white king may stay on the rook's rank as long as it is next to the black king
+        assert!(game.parse_san("Ke5").is_some());
+        assert!(game.parse_san("Kf4").is_none());
+    }
+
+    #[test]
+    fn counts_moves() {
+        // published atomic counts, standard chess gives 1233 and 46416 here
+        let mut game = start("rn2kb1r/1pp1p2p/p2q1pp1/3P4/2P3b1/4PN2/PP3PPP/R2QKB1R b KQkq - 0 1");
+        assert_eq!(perft(&mut game, 1), 40);
+        assert_eq!(perft(&mut game, 2), 1238);
+        assert_eq!(perft(&mut game, 3), 45237);
+
+        // and 827 and 23836 here
+        let mut game = start("rn1qkb1r/p5pp/2p5/3p4/N3P3/5P2/PPP4P/R1BQK3 w Qkq - 0 1");
+        assert_eq!(perft(&mut game, 1), 28);
+        assert_eq!(perft(&mut game, 2), 833);
+        assert_eq!(perft(&mut game, 3), 23353);
+    }
+
+    #[test]
+    fn the_ai_aims_at_the_pieces_around_the_king() {
+        // the rook goes to the seventh rank, where taking any of the pawns blows up the king
+        let game = start("4k3/3ppp2/8/8/8/8/8/R3K3 w - - 0 1");
+        let ai = Ai { options: AnalysisOptions { depth: 1, ..AnalysisOptions::default() }, ..Ai::default() };
+        assert_eq!(ai.choose_move(&game).unwrap().to_uci(), "a1a7");
+
+        // white is a queen down and holds on with the kings together
+        let game = start("8/8/8/3Kk3/8/8/8/q7 w - - 0 1");
+        assert_eq!(Atomic.evaluate(&game), eval::standard_evaluation(&game) + TOUCHING_KINGS_BONUS);
+    }
+}
@@ -0,0 +1,63 @@
+use ogronman_chess::live::LiveServer;
+use ogronman_chess::server::GameServer;
+use ogronman_chess::{set_tablebase_path, Ai, OpeningBook};
+use std::env;
+use std::process;
+
+/* Where the servers listen if --addr and --ws-addr are not given */
+const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+const DEFAULT_WS_ADDR: &str = "127.0.0.1:8081";
+
+/**
+ * The HTTP game server, with live games over WebSocket next to it
+ *
+ * usage: server [--addr host:port] [--ws-addr host:port] [--book book.bin] [--syzygy dir]
+ */
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let value = |flag: &str| -> Option<&String> {
+        let index = args.iter().position(|arg| arg == flag)?;
+        Some(args.get(index + 1).unwrap_or_else(|| {
+            eprintln!("{} needs a value", flag);
+            process::exit(1);
+        }))
+    };
+
+    let mut ai = Ai::default();
+    if let Some(path) = value("--book") {
+        match OpeningBook::open(path) {
+            Ok(book) => ai.book = Some(book),
+            Err(err) => {
+                eprintln!("could not read {}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(dir) = value("--syzygy") {
+        if let Err(err) = set_tablebase_path(Some(dir)) {
+            eprintln!("could not read {}: {}", dir, err);
+            process::exit(1);
+        }
+    }
+
+    let ws_addr = value("--ws-addr").map_or(DEFAULT_WS_ADDR, String::as_str);
+    match LiveServer::default().listen(ws_addr) {
+        Ok((addr, _)) => println!("Live games on ws://{}/live/{{game}}", addr),
+        Err(err) => {
+            eprintln!("could not listen on {}: {}", ws_addr, err);
+            process::exit(1);
+        }
+    }
+
+    let addr = value("--addr").map_or(DEFAULT_ADDR, String::as_str);
+    match GameServer::new(ai).listen(addr) {
+        Ok((addr, handle)) => {
+            println!("Listening on http://{}", addr);
+            let _ = handle.join();
+        }
+        Err(err) => {
+            eprintln!("could not listen on {}: {}", addr, err);
+            process::exit(1);
+        }
+    }
+}
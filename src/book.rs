@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rand::Rng;
+
+use crate::pgn::parse_pgn;
+use crate::{Game, Move, ColorTurn, _NONE, _BISHOP, _KNIGHT, _ROOK, _QUEEN, _KING};
+
+/* Every entry in a Polyglot book file is 16 bytes */
+const ENTRY_SIZE: usize = 16;
+
+/**
+ * One entry of a Polyglot book, a move that can be played in the position with the given hash.
+ *
+ * The move is stored the Polyglot way, with the to square in bits 0-5, the from square in
+ * bits 6-11 and the promotion piece in bits 12-14. Castling is written as the king taking its own rook
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+/// How a move is picked when there are several book moves for a position.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BookSelection {
+    /// A random move, where moves with a higher weight are picked more often.
+    Weighted,
+    /// Always the move with the highest weight.
+    BestOnly,
+}
+
+/**
+ * An opening book in the Polyglot format, with the entries sorted by hash so
+ * the moves for a position can be found with a binary search
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OpeningBook {
+    entries: Vec<BookEntry>,
+}
+
+impl OpeningBook {
+
+    /// Reads a Polyglot .bin book from disk.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<OpeningBook> {
+        Ok(OpeningBook::from_bytes(&fs::read(path)?))
+    }
+
+    /**
+     * Reads the entries of a Polyglot book, all numbers are big endian.
+     * A broken entry at the end of the data is ignored
+     */
+    pub fn from_bytes(bytes: &[u8]) -> OpeningBook {
+        let mut entries: Vec<BookEntry> = bytes.chunks_exact(ENTRY_SIZE).map(|chunk| BookEntry {
+            key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(chunk[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(chunk[12..16].try_into().unwrap()),
+        }).collect();
+        // books should already be sorted, but the lookup depends on it
+        entries.sort_by_key(|entry| entry.key);
+        OpeningBook { entries }
+    }
+
+    /// Writes the book in the Polyglot format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.entries.len() * ENTRY_SIZE);
+        for entry in &self.entries {
+            bytes.extend_from_slice(&entry.key.to_be_bytes());
+            bytes.extend_from_slice(&entry.raw_move.to_be_bytes());
+            bytes.extend_from_slice(&entry.weight.to_be_bytes());
+            bytes.extend_from_slice(&entry.learn.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Saves the book as a Polyglot .bin file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    /// The number of entries in the book.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks if the book has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /**
+     * Finds the book moves for the position, with the highest weight first.
+     * Entries that are not legal moves in the position are left out
+     */
+    pub fn lookup(&self, game: &Game) -> Vec<(Move, u16)> {
+        // Polyglot books only have positions on the standard board
+        if game.uses_piece_definitions() {
+            return Vec::new();
+        }
+        let key = game.polyglot_hash();
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let legal_moves = game.legal_moves();
+
+        let mut moves: Vec<(Move, u16)> = Vec::new();
+        for entry in self.entries[start..].iter().take_while(|entry| entry.key == key) {
+            let mv = OpeningBook::decode_move(game, entry.raw_move);
+            if legal_moves.contains(&mv) && !moves.iter().any(|(other, _)| *other == mv) {
+                moves.push((mv, entry.weight));
+            }
+        }
+        moves.sort_by_key(|(_, weight)| std::cmp::Reverse(*weight));
+        moves
+    }
+
+    /**
+     * Picks a book move for the position, returns None if the position is not
+     * in the book or all of its moves have weight 0
+     */
+    pub fn choose_move(&self, game: &Game, selection: BookSelection) -> Option<Move> {
+        let moves = self.lookup(game);
+        let total: u32 = moves.iter().map(|(_, weight)| *weight as u32).sum();
+        if total == 0 {
+            return None;
+        }
+        match selection {
+            BookSelection::BestOnly => moves.first().map(|(mv, _)| *mv),
+            BookSelection::Weighted => {
+                let mut pick = rand::thread_rng().gen_range(0..total);
+                for (mv, weight) in &moves {
+                    if pick < *weight as u32 {
+                        return Some(*mv);
+                    }
+                    pick -= *weight as u32;
+                }
+                None
+            }
+        }
+    }
+
+    /**
+     * Changes a Polyglot move to a move on the board, castling is stored as the
+     * king taking its own rook so it is changed to the king moving two squares
+     * unless the game writes castling the Chess960 way
+     */
+    fn decode_move(game: &Game, raw_move: u16) -> Move {
+        let to = (raw_move & 0x3f) as u8;
+        let from = ((raw_move >> 6) & 0x3f) as u8;
+        let promotion = match (raw_move >> 12) & 0x7 {
+            1 => _KNIGHT,
+            2 => _BISHOP,
+            3 => _ROOK,
+            4 => _QUEEN,
+            _ => _NONE,
+        };
+
+        let board = &game.board;
+        if !game.chess960 && board[from as usize][0] == _KING && board[to as usize] == [_ROOK, board[from as usize][1]] {
+            let to = if to > from { from + 2 } else { from - 2 };
+            return Move::new(from, to);
+        }
+        Move { from, to, promotion }
+    }
+
+    /// Changes a legal move in the position to the Polyglot way of storing it.
+    fn encode_move(game: &Game, mv: Move) -> u16 {
+        let mut to = mv.to;
+        if let Some((_, rook, _)) = game.castling_squares(mv) {
+            to = rook;
+        }
+        let promotion: u16 = match mv.promotion {
+            _KNIGHT => 1,
+            _BISHOP => 2,
+            _ROOK => 3,
+            _QUEEN => 4,
+            _ => 0,
+        };
+        to as u16 | (mv.from as u16) << 6 | promotion << 12
+    }
+
+    /**
+     * Builds a book from the games in a PGN text, using the first max_plies moves of every game
+     *
+     * Every time a move is played it scores 2 if the player who made it won the game,
+     * 1 for a draw or an unfinished game and 0 for a loss, and the weight of the move is
+     * its total score. Moves that only lost are left out. A game stops being read at the
+     * first move that can not be played
+     */
+    pub fn from_pgn(text: &str, max_plies: usize) -> OpeningBook {
+        let mut scores: HashMap<(u64, u16), u32> = HashMap::new();
+
+        for pgn_game in parse_pgn(text) {
+            let mut game = Game::new();
+            match pgn_game.tag("FEN") {
+                Some(fen) => game.load_fen_board(fen.to_string()),
+                None => game.init_board(),
+            }
+
+            for san in pgn_game.moves.iter().take(max_plies) {
+                let mv = match game.parse_san(san) {
+                    Some(mv) => mv,
+                    None => break,
+                };
+                let score = match (pgn_game.result.as_str(), game.get_game_turn()) {
+                    ("1-0", ColorTurn::White) | ("0-1", ColorTurn::Black) => 2,
+                    ("1-0", ColorTurn::Black) | ("0-1", ColorTurn::White) => 0,
+                    _ => 1,
+                };
+                *scores.entry((game.polyglot_hash(), OpeningBook::encode_move(&game, mv))).or_insert(0) += score;
+                game.play(mv);
+            }
+        }
+
+        // weights have to fit in 16 bits, so scale them down if any is too big
+        let max_score = scores.values().copied().max().unwrap_or(0);
+        let scale = (max_score / u16::MAX as u32) + 1;
+
+        let mut entries: Vec<BookEntry> = scores.into_iter()
+            .filter(|(_, score)| *score > 0)
+            .map(|((key, raw_move), score)| BookEntry {
+                key,
+                raw_move,
+                // a weight of 0 means the move is never played, so scaling must not round down to it
+                weight: (score / scale).max(1) as u16,
+                learn: 0,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)).then(a.raw_move.cmp(&b.raw_move)));
+        OpeningBook { entries }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BookSelection, OpeningBook};
+    use crate::Game;
+
+    const GAMES: &str = "[Result \"1-0\"]\n1. e4 e5 2. Nf3 Nc6 3. Bb5 1-0\n\n[Result \"0-1\"]\n1. e4 c5 2. Nf3 0-1\n\n[Result \"1/2-1/2\"]\n1. d4 d5 1/2-1/2\n";
+
+    #[test]
+    fn book_from_pgn_is_weighted_by_results() {
+        let book = OpeningBook::from_pgn(GAMES, 10);
+        let mut game = Game::new();
+        game.init_board();
+
+        let moves = book.lookup(&game);
+        // e4 scored 2 + 0 and d4 scored 1
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].0.to_uci(), "e2e4");
+        assert_eq!(moves[0].1, 2);
+        assert_eq!(book.choose_move(&game, BookSelection::BestOnly).unwrap().to_uci(), "e2e4");
+    }
+
+    #[test]
+    fn book_survives_writing_and_reading() {
+        let book = OpeningBook::from_pgn("1. e4 e5 2. Nf3 Nf6 3. Be2 Be7 4. O-O 1-0", 20);
+        let copy = OpeningBook::from_bytes(&book.to_bytes());
+        assert_eq!(book, copy);
+
+        let mut game = Game::new();
+        game.load_fen_board("rnbqk2r/ppppbppp/5n2/4p3/4P3/5N2/PPPPBPPP/RNBQK2R w KQkq - 4 4".to_string());
+        assert_eq!(copy.lookup(&game)[0].0.to_uci(), "e1g1");
+    }
+}
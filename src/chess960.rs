@@ -0,0 +1,183 @@
+use rand::Rng;
+
+use crate::Game;
+
+/* The number of Chess960 starting positions */
+pub const CHESS960_POSITIONS: u16 = 960;
+/* The two squares of the knights among the five left after the bishops and the queen */
+const KNIGHTS: [(usize, usize); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+
+/**
+ * Writes the white back rank of Chess960 starting position number index, for example
+ * "RNBQKBNR" for 518. The positions are numbered from 0 to 959 the way Scharnagl does:
+ * the index gives the light square bishop, the dark square bishop, the queen and the
+ * knights in that order, and the king goes between the two rooks on the squares left.
+ * Returns None for an index of 960 or more
+ */
+pub fn chess960_back_rank(index: u16) -> Option<String> {
+    if index >= CHESS960_POSITIONS {
+        return None;
+    }
+    let mut rank = [' '; 8];
+    let mut n = index as usize;
+    rank[n % 4 * 2 + 1] = 'B';
+    n /= 4;
+    rank[n % 4 * 2] = 'B';
+    n /= 4;
+
+    let empty = |rank: &[char; 8]| -> Vec<usize> { (0..8).filter(|file| rank[*file] == ' ').collect() };
+    rank[empty(&rank)[n % 6]] = 'Q';
+    n /= 6;
+    let (first, second) = KNIGHTS[n];
+    let squares = empty(&rank);
+    rank[squares[first]] = 'N';
+    rank[squares[second]] = 'N';
+
+    for (file, piece) in empty(&rank).into_iter().zip(['R', 'K', 'R']) {
+        rank[file] = piece;
+    }
+    Some(rank.iter().collect())
+}
+
+/**
+ * Writes Chess960 starting position number index as a full fen string with the castling
+ * rights as X-FEN, "KQkq". Returns None for an index of 960 or more
+ */
+pub fn chess960_fen(index: u16) -> Option<String> {
+    let white = chess960_back_rank(index)?;
+    Some(format!("{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1", white.to_lowercase(), white))
+}
+
+impl Game {
+
+    /**
+     * Writes castling as the king taking its own rook, for example "e1h1", as Chess960 does
+     * and UCI asks for in Chess960 games. Otherwise castling is the king moving two squares,
+     * which can only be used when the kings and rooks start where they do in standard chess,
+     * so a position loaded with load_fen_board where they do not is written this way anyway
+     */
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
+    pub fn is_chess960(&self) -> bool {
+        self.chess960 || self.chess960_castling
+    }
+
+    /**
+     * Sets up Chess960 starting position number index, see chess960_back_rank.
+     * Returns false and leaves the board as it was for an index of 960 or more
+     */
+    pub fn init_chess960(&mut self, index: u16) -> bool {
+        let fen = match chess960_fen(index) {
+            Some(fen) => fen,
+            None => return false,
+        };
+        self.chess960 = true;
+        self.load_fen_board(fen);
+        true
+    }
+
+    /// Sets up a random Chess960 starting position and returns its number.
+    pub fn init_chess960_random(&mut self) -> u16 {
+        let index = rand::thread_rng().gen_range(0..CHESS960_POSITIONS);
+        self.init_chess960(index);
+        index
+    }
+
+    /**
+     * The number of the Chess960 starting position on the board, None if the pieces are not
+     * in a starting position. The standard position is number 518
+     */
+    pub fn chess960_index(&self) -> Option<u16> {
+        let placement = self.to_fen();
+        let placement = placement.split(' ').next()?;
+        (0..CHESS960_POSITIONS).find(|index| {
+            chess960_fen(*index).is_some_and(|fen| fen.split(' ').next() == Some(placement))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chess960_back_rank, chess960_fen};
+    use crate::Game;
+    use crate::perft;
+
+    #[test]
+    fn numbers_the_starting_positions() {
+        assert_eq!(chess960_back_rank(518).unwrap(), "RNBQKBNR");
+        assert_eq!(chess960_back_rank(0).unwrap(), "BBQNNRKR");
+        assert_eq!(chess960_back_rank(959).unwrap(), "RKRNNQBB");
+        assert!(chess960_back_rank(960).is_none());
+
+        let mut game = Game::new();
+        let index = game.init_chess960_random();
+        assert_eq!(game.chess960_index(), Some(index));
+        assert!(game.is_chess960());
+    }
+
+    #[test]
+    fn castles_onto_the_standard_squares() {
+        // king on b1 with rooks on a1 and h1, the queen side rook is next to the king
+        let mut game = Game::new();
+        game.load_fen_board("3rk2r/8/8/8/8/8/8/RK5R w AHh - 0 1".to_string());
+        assert!(game.is_chess960());
+        assert_eq!(game.to_fen(), "3rk2r/8/8/8/8/8/8/RK5R w KQk - 0 1");
+
+        // the rook may end on d1 where the rook on d8 attacks, but the king may not pass it
+        let queen_side = game.parse_move("b1a1").unwrap();
+        assert_eq!(game.move_to_san(queen_side), "O-O-O");
+        assert!(game.parse_san("O-O").is_none());
+        game.play(queen_side);
+        assert_eq!(game.to_fen(), "3rk2r/8/8/8/8/8/8/2KR3R b k - 1 1");
+        assert!(game.parse_move("e8h8").is_some());
+        game.undo();
+        assert_eq!(game.to_fen(), "3rk2r/8/8/8/8/8/8/RK5R w KQk - 0 1");
+
+        game.load_fen_board("4k3/8/8/8/8/8/8/RK5R w AH - 0 1".to_string());
+        let king_side = game.parse_san("O-O").unwrap();
+        assert_eq!(king_side.to_uci(), "b1h1");
+        game.play(king_side);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/R4RK1 b - - 1 1");
+    }
+
+    #[test]
+    fn a_standard_position_after_a_chess960_one_castles_as_usual() {
+        let mut game = Game::new();
+        game.load_fen_board("1k6/8/8/8/8/8/8/RK5R w AH - 0 1".to_string());
+        assert!(game.is_chess960());
+        game.load_fen_board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1".to_string());
+        assert!(!game.is_chess960());
+        assert!(game.parse_move("e1g1").is_some());
+        assert_eq!(game.parse_san("O-O-O").unwrap().to_uci(), "e1c1");
+
+        // asking for Chess960 castling keeps it for every position
+        game.set_chess960(true);
+        game.load_fen_board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1".to_string());
+        assert_eq!(game.parse_san("O-O").unwrap().to_uci(), "e1h1");
+    }
+
+    #[test]
+    fn writes_inner_rooks_with_their_file() {
+        let mut game = Game::new();
+        game.load_fen_board("4k3/8/8/8/8/8/8/1R1K2RR w BG - 0 1".to_string());
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/1R1K2RR w GQ - 0 1");
+    }
+
+    #[test]
+    fn counts_moves_in_chess960_positions() {
+        let mut game = Game::new();
+        game.init_chess960(0);
+        assert_eq!(chess960_fen(0).unwrap(), game.to_fen());
+        assert_eq!(perft(&mut game, 2), 400);
+
+        game.load_fen_board("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9".to_string());
+        assert_eq!(perft(&mut game, 1), 21);
+        assert_eq!(perft(&mut game, 2), 528);
+        assert_eq!(perft(&mut game, 3), 12189);
+
+        game.load_fen_board("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9".to_string());
+        assert_eq!(perft(&mut game, 3), 18002);
+    }
+}
@@ -0,0 +1,357 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::ColorTurn;
+
+/// The time a player gets back for a move.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Bonus {
+    /// No time is given back, sudden death.
+    None,
+    /// Fischer increment, the time is added after every move.
+    Increment(Duration),
+    /// Bronstein delay, the time used for the move is given back, at most the delay.
+    Bronstein(Duration),
+    /// Simple delay, the clock waits this long before it starts counting down.
+    Delay(Duration),
+}
+
+/**
+ * A part of a time control, time for the given number of moves, or for the rest of the game
+ * if moves is None. A last period with a number of moves is repeated
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimePeriod {
+    pub moves: Option<u32>,
+    pub time: Duration,
+    pub bonus: Bonus,
+}
+
+impl TimePeriod {
+
+    /// Time for the rest of the game, without anything given back.
+    pub fn sudden_death(time: Duration) -> TimePeriod {
+        TimePeriod { moves: None, time, bonus: Bonus::None }
+    }
+
+    /**
+     * Reads a period the way the PGN TimeControl tag writes them, in seconds:
+     * "300" sudden death, "300+2" with increment and "40/5400" for 40 moves, and
+     * "300d5" and "300b5" for a simple or a Bronstein delay
+     */
+    fn parse(text: &str) -> Option<TimePeriod> {
+        let (moves, rest) = match text.split_once('/') {
+            Some((moves, rest)) => (Some(moves.parse::<u32>().ok().filter(|moves| *moves > 0)?), rest),
+            None => (None, text),
+        };
+        let seconds = |text: &str| text.parse::<f64>().ok().filter(|s| *s >= 0.0 && s.is_finite()).map(Duration::from_secs_f64);
+        let (time, bonus) = if let Some((time, increment)) = rest.split_once('+') {
+            (time, Bonus::Increment(seconds(increment)?))
+        } else if let Some((time, delay)) = rest.split_once('d') {
+            (time, Bonus::Delay(seconds(delay)?))
+        } else if let Some((time, delay)) = rest.split_once('b') {
+            (time, Bonus::Bronstein(seconds(delay)?))
+        } else {
+            (rest, Bonus::None)
+        };
+        Some(TimePeriod { moves, time: seconds(time)?, bonus })
+    }
+}
+
+impl fmt::Display for TimePeriod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(moves) = self.moves {
+            write!(f, "{}/", moves)?;
+        }
+        write!(f, "{}", self.time.as_secs_f64())?;
+        match self.bonus {
+            Bonus::None => Ok(()),
+            Bonus::Increment(time) => write!(f, "+{}", time.as_secs_f64()),
+            Bonus::Delay(time) => write!(f, "d{}", time.as_secs_f64()),
+            Bonus::Bronstein(time) => write!(f, "b{}", time.as_secs_f64()),
+        }
+    }
+}
+
+fn side(color: ColorTurn) -> usize {
+    if color == ColorTurn::White { 0 } else { 1 }
+}
+
+/**
+ * A chess clock for both players with a time control of one or more periods.
+ *
+ * The clock of the player to move runs from start until the player presses it after the
+ * move. A player whose time runs out has lost on time, the flag has fallen, and the clock
+ * stops. The time control is written like the PGN TimeControl tag with periods separated
+ * by ":", for example "40/5400+30:1800+30" for 90 minutes for 40 moves and then
+ * 30 minutes for the rest of the game, with 30 seconds added after every move
+ */
+#[derive(Clone, Debug)]
+pub struct Clock {
+    periods: Vec<TimePeriod>,
+    remaining: [Duration; 2],
+    moves: [u32; 2],
+    /* the period each player is in and the move it ends after */
+    period: [usize; 2],
+    period_end: [Option<u32>; 2],
+    running: Option<(ColorTurn, Instant)>,
+    flagged: Option<ColorTurn>,
+}
+
+impl Clock {
+
+    /// A clock with the periods in order, with no periods there is no time at all.
+    pub fn new(periods: Vec<TimePeriod>) -> Clock {
+        let first = periods.first().copied().unwrap_or(TimePeriod::sudden_death(Duration::ZERO));
+        Clock {
+            periods,
+            remaining: [first.time; 2],
+            moves: [0; 2],
+            period: [0; 2],
+            period_end: [first.moves; 2],
+            running: None,
+            flagged: None,
+        }
+    }
+
+    /// Reads a time control like "300+2" or "40/5400:1800", see Clock.
+    pub fn parse(text: &str) -> Option<Clock> {
+        let periods = text.split(':').map(TimePeriod::parse).collect::<Option<Vec<TimePeriod>>>()?;
+        if periods.is_empty() {
+            return None;
+        }
+        Some(Clock::new(periods))
+    }
+
+    pub fn periods(&self) -> &[TimePeriod] {
+        &self.periods
+    }
+
+    /// The period the player is in now, with no periods a sudden death without time.
+    pub fn period(&self, color: ColorTurn) -> TimePeriod {
+        let index = self.period[side(color)].min(self.periods.len().saturating_sub(1));
+        self.periods.get(index).copied().unwrap_or(TimePeriod::sudden_death(Duration::ZERO))
+    }
+
+    /// The player whose clock is running, if any.
+    pub fn running(&self) -> Option<ColorTurn> {
+        self.running.map(|(color, _)| color)
+    }
+
+    /// Starts the clock of the player, a running clock is stopped without a move.
+    pub fn start(&mut self, color: ColorTurn) {
+        self.stop();
+        if self.flagged.is_none() {
+            self.running = Some((color, Instant::now()));
+        }
+    }
+
+    /// Stops the running clock without a move, the time used is taken from the player.
+    pub fn stop(&mut self) {
+        if let Some((color, started)) = self.running.take() {
+            let used = self.counted(color, started.elapsed());
+            self.take(color, used);
+        }
+    }
+
+    /**
+     * The player whose clock is running has moved, its time is counted and the clock of
+     * the other player starts. Returns false if the flag fell before the move
+     */
+    pub fn press(&mut self) -> bool {
+        match self.running.take() {
+            Some((color, started)) => {
+                let moved = self.record_move(color, started.elapsed());
+                if moved {
+                    self.running = Some((if color == ColorTurn::White { ColorTurn::Black } else { ColorTurn::White }, Instant::now()));
+                }
+                moved
+            }
+            None => self.flagged.is_none(),
+        }
+    }
+
+    /**
+     * Puts back the clock as it was before a move that is taken back. The clock of the
+     * player that made the move starts again from now
+     */
+    pub(crate) fn take_back(&mut self, before: Clock) {
+        *self = before;
+        if let Some((color, _)) = self.running {
+            self.running = Some((color, Instant::now()));
+        }
+    }
+
+    /// The time the clock counts down of the elapsed time, the delay is left out.
+    fn counted(&self, color: ColorTurn, elapsed: Duration) -> Duration {
+        match self.period(color).bonus {
+            Bonus::Delay(delay) => elapsed.saturating_sub(delay),
+            _ => elapsed,
+        }
+    }
+
+    fn take(&mut self, color: ColorTurn, used: Duration) {
+        let remaining = &mut self.remaining[side(color)];
+        if used >= *remaining && self.flagged.is_none() {
+            self.flagged = Some(color);
+        }
+        *remaining = remaining.saturating_sub(used);
+    }
+
+    /**
+     * Counts a move the player made in the elapsed time without the running clock, and gives
+     * back time and starts the next period as the time control says. Returns false if the
+     * flag fell before the move
+     */
+    pub fn record_move(&mut self, color: ColorTurn, elapsed: Duration) -> bool {
+        if self.flagged.is_some() {
+            return false;
+        }
+        let period = self.period(color);
+        self.take(color, self.counted(color, elapsed));
+        if self.flagged.is_some() {
+            return false;
+        }
+
+        let me = side(color);
+        match period.bonus {
+            Bonus::Increment(increment) => self.remaining[me] += increment,
+            Bonus::Bronstein(delay) => self.remaining[me] += elapsed.min(delay),
+            Bonus::None | Bonus::Delay(_) => {}
+        }
+        self.moves[me] += 1;
+        if self.period_end[me] == Some(self.moves[me]) {
+            // the last period is repeated when it is for a number of moves
+            self.period[me] = (self.period[me] + 1).min(self.periods.len() - 1);
+            let next = self.periods[self.period[me]];
+            self.remaining[me] += next.time;
+            self.period_end[me] = next.moves.map(|moves| self.moves[me] + moves);
+        }
+        true
+    }
+
+    /// The time the player has left, with the time of a running clock taken off.
+    pub fn remaining(&self, color: ColorTurn) -> Duration {
+        let remaining = self.remaining[side(color)];
+        match self.running {
+            Some((running, started)) if running == color => remaining.saturating_sub(self.counted(color, started.elapsed())),
+            _ => remaining,
+        }
+    }
+
+    /// The player whose flag has fallen, also while the clock is running.
+    pub fn flagged(&self) -> Option<ColorTurn> {
+        if self.flagged.is_some() {
+            return self.flagged;
+        }
+        match self.running {
+            Some((color, _)) if self.remaining(color).is_zero() => Some(color),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let periods: Vec<String> = self.periods.iter().map(|period| period.to_string()).collect();
+        write!(f, "{}", periods.join(":"))
+    }
+}
+
+/**
+ * Writes a time the way clocks show it, "1:05:09" or "4:59", with tenths of
+ * a second under 10 seconds, "0:09.4"
+ */
+pub fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else if time < Duration::from_secs(10) {
+        format!("0:{:02}.{}", seconds, time.subsec_millis() / 100)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_time, Bonus, Clock, TimePeriod};
+    use crate::{ColorTurn, Game};
+    use std::time::Duration;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn parses_time_controls() {
+        let clock = Clock::parse("40/5400+30:1800+30").unwrap();
+        assert_eq!(clock.periods()[0], TimePeriod { moves: Some(40), time: secs(5400), bonus: Bonus::Increment(secs(30)) });
+        assert_eq!(clock.periods()[1].moves, None);
+        assert_eq!(clock.to_string(), "40/5400+30:1800+30");
+        assert_eq!(Clock::parse("300d5").unwrap().periods()[0].bonus, Bonus::Delay(secs(5)));
+        assert_eq!(Clock::parse("300b5").unwrap().periods()[0].bonus, Bonus::Bronstein(secs(5)));
+        assert!(Clock::parse("5 minutes").is_none());
+    }
+
+    #[test]
+    fn increments_and_delays_give_time_back() {
+        let mut fischer = Clock::parse("60+2").unwrap();
+        assert!(fischer.record_move(ColorTurn::White, secs(5)));
+        assert_eq!(fischer.remaining(ColorTurn::White), secs(57));
+
+        let mut bronstein = Clock::parse("60b2").unwrap();
+        bronstein.record_move(ColorTurn::White, secs(5));
+        bronstein.record_move(ColorTurn::Black, secs(1));
+        assert_eq!(bronstein.remaining(ColorTurn::White), secs(57));
+        assert_eq!(bronstein.remaining(ColorTurn::Black), secs(60));
+
+        let mut delay = Clock::parse("60d2").unwrap();
+        delay.record_move(ColorTurn::White, secs(5));
+        delay.record_move(ColorTurn::Black, secs(1));
+        assert_eq!(delay.remaining(ColorTurn::White), secs(57));
+        assert_eq!(delay.remaining(ColorTurn::Black), secs(60));
+    }
+
+    #[test]
+    fn periods_add_time_and_flags_fall() {
+        let mut clock = Clock::parse("2/60:30").unwrap();
+        clock.record_move(ColorTurn::White, secs(20));
+        clock.record_move(ColorTurn::White, secs(20));
+        assert_eq!(clock.remaining(ColorTurn::White), secs(50));
+        assert_eq!(clock.period(ColorTurn::White).moves, None);
+
+        assert!(!clock.record_move(ColorTurn::White, secs(50)));
+        assert_eq!(clock.flagged(), Some(ColorTurn::White));
+        assert!(!clock.record_move(ColorTurn::Black, secs(1)));
+
+        assert_eq!(format_time(secs(3909)), "1:05:09");
+        assert_eq!(format_time(secs(299)), "4:59");
+        assert_eq!(format_time(Duration::from_millis(9400)), "0:09.4");
+    }
+
+    #[test]
+    fn a_clock_without_periods_has_no_time() {
+        let mut clock = Clock::new(Vec::new());
+        assert!(!clock.record_move(ColorTurn::White, secs(0)));
+        assert_eq!(clock.flagged(), Some(ColorTurn::White));
+        assert_eq!(clock.remaining(ColorTurn::Black), secs(0));
+    }
+
+    #[test]
+    fn undo_puts_back_the_time_of_the_move() {
+        let mut game = Game::new();
+        game.init_board();
+        game.set_clock(Clock::parse("60+2"));
+        game.play(game.parse_move("e2e4").unwrap());
+        assert!(game.clock().unwrap().remaining(ColorTurn::White) > secs(61));
+
+        game.undo();
+        let clock = game.clock().unwrap();
+        assert!(clock.remaining(ColorTurn::White) <= secs(60));
+        assert_eq!(clock.running(), Some(ColorTurn::White));
+        game.play(game.parse_move("d2d4").unwrap());
+        assert_eq!(game.clock().unwrap().running(), Some(ColorTurn::Black));
+    }
+}
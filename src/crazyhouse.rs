@@ -0,0 +1,183 @@
+use crate::eval::{self, PIECE_VALUES};
+use crate::variant::Variant;
+use crate::{Game, Move, ColorTurn, _NONE, _PAWN, _BISHOP, _KNIGHT, _ROOK, _QUEEN, _WHITE};
+
+/* The pieces that can be in hand, in the order the fen string writes them */
+const POCKET_PIECES: [u8; 5] = [_QUEEN, _ROOK, _BISHOP, _KNIGHT, _PAWN];
+
+/**
+ * Crazyhouse, a piece that is taken changes color and goes into the hand of the player
+ * that took it. Instead of moving, a player may drop a piece from the hand on any empty
+ * square, except pawns on the first and last rank. A promoted piece turns back into a
+ * pawn when it is taken. The fen string writes the pieces in hand in brackets after the
+ * placement, "[Qp]", and a promoted piece with a "~" after it
+ */
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Crazyhouse;
+
+impl Variant for Crazyhouse {
+    fn name(&self) -> &str {
+        "Crazyhouse"
+    }
+
+    fn start_fen(&self) -> &str {
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1"
+    }
+
+    fn has_pockets(&self) -> bool {
+        true
+    }
+
+    fn generate_moves(&self, game: &Game) -> Vec<Move> {
+        let mut moves = game.standard_moves();
+        moves.append(&mut game.drop_moves());
+        moves
+    }
+
+    fn make_move(&self, game: &mut Game, mv: Move) {
+        let side = if game.color_to_move() == _WHITE { 0 } else { 1 };
+        if mv.is_drop() {
+            game.put_piece(mv.to, mv.promotion, game.color_to_move());
+            game.pockets[side][mv.promotion as usize] -= 1;
+            game.last_moved_pawn = vec![_NONE, 2];
+            game.halfmove_clock += 1;
+            return;
+        }
+
+        let (from_bit, to_bit) = (1u64 << mv.from, 1u64 << mv.to);
+        let moved_promoted = game.promoted & from_bit != 0;
+        let taken_promoted = game.promoted & to_bit != 0;
+        let captured = game.make_standard_move(mv);
+        game.promoted &= !(from_bit | to_bit);
+        if moved_promoted || mv.promotion != _NONE {
+            game.promoted |= to_bit;
+        }
+        if captured[0] != _NONE {
+            let piece = if taken_promoted { _PAWN } else { captured[0] };
+            game.pockets[side][piece as usize] += 1;
+        }
+    }
+
+    fn evaluate(&self, game: &Game) -> i32 {
+        let (own, other) = if game.color_to_move() == _WHITE { (0, 1) } else { (1, 0) };
+        let hand = |side: usize| -> i32 {
+            POCKET_PIECES.iter().map(|piece| PIECE_VALUES[*piece as usize] * game.pockets[side][*piece as usize] as i32).sum()
+        };
+        eval::standard_evaluation(game) + hand(own) - hand(other)
+    }
+}
+
+impl Game {
+
+    /// The drops of the pieces the player to move has in hand.
+    fn drop_moves(&self) -> Vec<Move> {
+        let side = if self.color_to_move() == _WHITE { 0 } else { 1 };
+        let mut moves = Vec::new();
+        for piece in POCKET_PIECES.iter().filter(|piece| self.pockets[side][**piece as usize] > 0) {
+            for _pos in 0..64u8 {
+                if self.board[_pos as usize][0] == _NONE && (*piece != _PAWN || (8..56).contains(&_pos)) {
+                    moves.push(Move::drop_piece(*piece, _pos));
+                }
+            }
+        }
+        moves
+    }
+
+    /**
+     * The pieces the player has in hand as letters, for example "QNPP". The hands are
+     * only filled in variants such as Crazyhouse
+     */
+    pub fn pocket(&self, color: ColorTurn) -> String {
+        let side = if color == ColorTurn::White { 0 } else { 1 };
+        POCKET_PIECES.iter()
+            .flat_map(|piece| std::iter::repeat_n(Game::fen_symbol(*piece, _WHITE), self.pockets[side][*piece as usize] as usize))
+            .collect()
+    }
+
+    /// Checks if the piece on the square came from a promotion, which Crazyhouse keeps track of.
+    pub fn is_promoted(&self, _pos: u8) -> bool {
+        self.promoted & (1u64 << _pos) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Crazyhouse;
+    use crate::ai::Ai;
+    use crate::player::NO_MOVE;
+    use crate::search::AnalysisOptions;
+    use crate::{ColorTurn, Game, Move};
+    use std::sync::Arc;
+
+    fn start() -> Game {
+        let mut game = Game::new();
+        game.set_variant(Arc::new(Crazyhouse));
+        game.init_board();
+        game
+    }
+
+    fn play(game: &mut Game, moves: &str) {
+        for text in moves.split_whitespace() {
+            let mv = game.parse_move(text).or_else(|| game.parse_san(text)).unwrap();
+            assert!(game.play(mv), "{} is not legal", text);
+        }
+    }
+
+    #[test]
+    fn taken_pieces_can_be_dropped() {
+        let mut game = start();
+        play(&mut game, "e4 d5 exd5 Qxd5 Nc3 Qa5");
+        assert_eq!(game.pocket(ColorTurn::White), "P");
+        assert_eq!(game.pocket(ColorTurn::Black), "P");
+        assert_eq!(game.to_fen(), "rnb1kbnr/ppp1pppp/8/q7/8/2N5/PPPP1PPP/R1BQKBNR[Pp] w KQkq - 2 4");
+
+        let drop = game.parse_move("P@d5").unwrap();
+        assert_eq!(drop.to_uci(), "P@d5");
+        assert_eq!(game.move_to_san(drop), "P@d5");
+        play(&mut game, "@d5");
+        assert_eq!(game.pocket(ColorTurn::White), "");
+        game.undo();
+        assert_eq!(game.pocket(ColorTurn::White), "P");
+        assert_eq!(game.to_fen(), "rnb1kbnr/ppp1pppp/8/q7/8/2N5/PPPP1PPP/R1BQKBNR[Pp] w KQkq - 2 4");
+    }
+
+    #[test]
+    fn pawns_are_not_dropped_on_the_first_or_last_rank() {
+        let mut game = start();
+        game.load_fen_board("4k3/8/8/8/8/8/8/4K3[PN] w - - 0 1".to_string());
+        assert!(game.parse_move("P@a8").is_none());
+        assert!(game.parse_move("P@a1").is_none());
+        assert!(game.parse_move("P@a2").is_some());
+        assert!(game.parse_san("N@a8").is_some());
+        assert!(game.parse_move("Q@a2").is_none());
+    }
+
+    #[test]
+    fn promoted_pieces_are_taken_as_pawns() {
+        let mut game = start();
+        game.load_fen_board("1n2k3/P7/8/8/8/8/8/4K3[] w - - 0 1".to_string());
+        play(&mut game, "a7b8=Q");
+        assert!(game.is_promoted(57));
+        assert_eq!(game.to_fen(), "1Q~2k3/8/8/8/8/8/8/4K3[N] b - - 0 1");
+
+        // the pocket may also be written as a ninth rank
+        game.load_fen_board("1Q~2k3/8/8/8/8/8/7K/1q6/r b - - 0 1".to_string());
+        assert_eq!(game.pocket(ColorTurn::Black), "R");
+        play(&mut game, "Qxb8");
+        assert_eq!(game.pocket(ColorTurn::Black), "RP");
+        assert!(!game.is_promoted(57));
+    }
+
+    #[test]
+    fn only_drops_are_drops() {
+        assert!(!NO_MOVE.is_drop());
+        assert!(!Move::new(12, 12).is_drop());
+
+        // the search orders and plays the drops with the other moves
+        let mut game = start();
+        game.load_fen_board("r1bqkbnr/pppp1ppp/2n5/8/8/8/PPPP1PPP/RNBQKBNR[Nn] w KQkq - 0 1".to_string());
+        let ai = Ai { options: AnalysisOptions { depth: 2, ..AnalysisOptions::default() }, ..Ai::default() };
+        let mv = ai.choose_move(&game).unwrap();
+        assert!(game.play(mv));
+    }
+}
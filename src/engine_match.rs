@@ -0,0 +1,663 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::ai::Ai;
+use crate::pgn::{parse_pgn, PgnGame};
+use crate::player::{Outcome, Termination};
+use crate::result::GameResult;
+use crate::search::Score;
+use crate::sprt::{Pentanomial, Sprt};
+use crate::uci;
+use crate::{Game, ColorTurn};
+
+/* How long an engine may take to start, answer isready or send its last move after stop */
+const ENGINE_TIMEOUT: Duration = Duration::from_secs(10);
+/* Mate scores count as this many centipawns when adjudicating */
+const MATE_CENTIPAWNS: i32 = 100000;
+
+/**
+ * A chess engine that speaks UCI, usually a program started as a subprocess.
+ * The engine is told to quit when it is dropped
+ */
+pub struct UciEngine {
+    name: String,
+    input: Box<dyn Write + Send>,
+    lines: Receiver<String>,
+    child: Option<Child>,
+    /* sent with every go after the clocks, like "depth 4" */
+    go_limits: String,
+}
+
+/* What an engine answered to go */
+enum Reply {
+    Move(String, Option<Score>),
+    Timeout,
+    Gone,
+}
+
+impl UciEngine {
+
+    /**
+     * Starts the program with the arguments and waits for it to finish the UCI handshake
+     */
+    pub fn start(command: &str, args: &[String]) -> io::Result<UciEngine> {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let output = BufReader::new(child.stdout.take().ok_or_else(|| io::Error::other("no stdout"))?);
+        let input = child.stdin.take().ok_or_else(|| io::Error::other("no stdin"))?;
+        let mut engine = UciEngine::from_streams(output, input)?;
+        engine.child = Some(child);
+        Ok(engine)
+    }
+
+    /**
+     * Talks UCI with an engine that reads from input and writes to output, for example
+     * one running on a thread, and waits for it to finish the handshake
+     */
+    pub fn from_streams<R, W>(output: R, input: W) -> io::Result<UciEngine>
+        where R: BufRead + Send + 'static, W: Write + Send + 'static {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in output.lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            return;
+                        }
+                    }
+                    Err(_) => return,
+                }
+            }
+        });
+
+        let mut engine = UciEngine { name: String::from("engine"), input: Box::new(input), lines, child: None, go_limits: String::new() };
+        engine.send("uci")?;
+        let deadline = Instant::now() + ENGINE_TIMEOUT;
+        loop {
+            let line = engine.next_line(deadline)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            }
+            if line.trim() == "uciok" {
+                break;
+            }
+        }
+        engine.wait_ready()?;
+        Ok(engine)
+    }
+
+    /**
+     * The ai of this crate as a UCI engine, running on its own thread. It searches no
+     * deeper than the depth of its options, and no more than their nodes
+     */
+    pub fn from_ai(ai: Ai) -> io::Result<UciEngine> {
+        let mut go_limits = format!("depth {}", ai.options.depth);
+        if let Some(nodes) = ai.options.nodes {
+            go_limits.push_str(&format!(" nodes {}", nodes));
+        }
+        let (engine_in, to_engine) = io::pipe()?;
+        let (from_engine, engine_out) = io::pipe()?;
+        thread::spawn(move || uci::run(ai, BufReader::new(engine_in), engine_out));
+        let mut engine = UciEngine::from_streams(BufReader::new(from_engine), to_engine)?;
+        engine.go_limits = go_limits;
+        Ok(engine)
+    }
+
+    /// The name the engine gave with "id name", or the one given with set_name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Changes the name written in the games, for engines that give the same name.
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> io::Result<()> {
+        self.send(&format!("setoption name {} value {}", name, value))?;
+        self.wait_ready()
+    }
+
+    /// Tells the engine that the next position is from another game.
+    pub fn new_game(&mut self) -> io::Result<()> {
+        self.send("ucinewgame")?;
+        self.wait_ready()
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.input, "{}", line)?;
+        self.input.flush()
+    }
+
+    fn next_line(&mut self, deadline: Instant) -> io::Result<String> {
+        let wait = deadline.saturating_duration_since(Instant::now());
+        self.lines.recv_timeout(wait).map_err(|err| match err {
+            RecvTimeoutError::Timeout => io::Error::new(io::ErrorKind::TimedOut, "the engine did not answer"),
+            RecvTimeoutError::Disconnected => io::Error::new(io::ErrorKind::UnexpectedEof, "the engine has stopped"),
+        })
+    }
+
+    /// Sends isready and skips everything until readyok, like a move from an earlier search.
+    fn wait_ready(&mut self) -> io::Result<()> {
+        self.send("isready")?;
+        let deadline = Instant::now() + ENGINE_TIMEOUT;
+        while self.next_line(deadline)?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    /**
+     * Lets the engine search the position, which is "startpos" or "fen ..." followed by
+     * "moves ...", and waits at most time for its move. The engine is stopped when the time runs out
+     */
+    fn go(&mut self, position: &str, clock_args: &str, time: Duration) -> Reply {
+        if self.send(&format!("position {}", position)).is_err() || self.send(format!("go {} {}", clock_args, self.go_limits).trim_end()).is_err() {
+            return Reply::Gone;
+        }
+        let deadline = Instant::now() + time;
+        let mut score = None;
+        loop {
+            match self.next_line(deadline) {
+                Ok(line) => {
+                    let words: Vec<&str> = line.split_whitespace().collect();
+                    match words.first() {
+                        Some(&"bestmove") => return Reply::Move(words.get(1).unwrap_or(&"").to_string(), score),
+                        Some(&"info") => {
+                            if let Some(index) = words.iter().position(|word| *word == "score") {
+                                let value = words.get(index + 2).and_then(|value| value.parse::<i32>().ok());
+                                score = match (words.get(index + 1), value) {
+                                    (Some(&"cp"), Some(cp)) => Some(Score::Centipawns(cp)),
+                                    (Some(&"mate"), Some(moves)) => Some(Score::Mate(moves)),
+                                    _ => score,
+                                };
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::TimedOut => {
+                    let _ = self.send("stop");
+                    return Reply::Timeout;
+                }
+                Err(_) => return Reply::Gone,
+            }
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        if let Some(child) = self.child.as_mut() {
+            let deadline = Instant::now() + Duration::from_secs(1);
+            while Instant::now() < deadline {
+                if let Ok(Some(_)) = child.try_wait() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/**
+ * The time every engine has for the game and the time added after each of its moves
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl TimeControl {
+
+    /// Reads "seconds+increment", for example "10+0.1", or only "seconds".
+    pub fn parse(text: &str) -> Option<TimeControl> {
+        let (base, increment) = text.split_once('+').unwrap_or((text, "0"));
+        let seconds = |text: &str| text.trim().parse::<f64>().ok().filter(|s| *s >= 0.0 && s.is_finite()).map(Duration::from_secs_f64);
+        Some(TimeControl { base: seconds(base)?, increment: seconds(increment)? })
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}+{}", self.base.as_secs_f64(), self.increment.as_secs_f64())
+    }
+}
+
+/**
+ * When a game is stopped before it is over. A side resigns when its engine has seen a score of
+ * at most -resign_score for resign_moves of its moves in a row and the other engine has agreed.
+ * The game is a draw when both engines have seen a score within draw_score for draw_moves
+ * moves each, from move draw_start on, or when it reaches max_moves
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Adjudication {
+    pub resign_score: Option<i32>,
+    pub resign_moves: u32,
+    pub draw_score: Option<i32>,
+    pub draw_moves: u32,
+    pub draw_start: u32,
+    pub max_moves: Option<u32>,
+}
+
+impl Default for Adjudication {
+    fn default() -> Adjudication {
+        Adjudication {
+            resign_score: None,
+            resign_moves: 3,
+            draw_score: None,
+            draw_moves: 8,
+            draw_start: 40,
+            max_moves: None,
+        }
+    }
+}
+
+/**
+ * Where a game of a match starts, a fen or the start position and the moves played from it in UCI form
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Opening {
+    pub fen: Option<String>,
+    pub moves: Vec<String>,
+}
+
+impl Opening {
+
+    /// Sets up the position of the opening, moves after the first one that is not legal are left out.
+    fn game(&self) -> Game {
+        let mut game = Game::new();
+        match &self.fen {
+            Some(fen) => game.load_fen_board(fen.clone()),
+            None => game.init_board(),
+        }
+        for text in &self.moves {
+            match game.parse_move(text) {
+                Some(mv) => {
+                    game.play(mv);
+                }
+                None => break,
+            }
+        }
+        game
+    }
+}
+
+/**
+ * Reads an opening suite, an EPD file with one position on each line or a PGN file
+ * whose games are played from the start or from their FEN tag
+ */
+pub fn read_openings<P: AsRef<Path>>(path: P) -> io::Result<Vec<Opening>> {
+    let text = fs::read_to_string(&path)?;
+    let is_epd = path.as_ref().extension().is_some_and(|extension| extension.eq_ignore_ascii_case("epd"));
+    Ok(if is_epd { openings_from_epd(&text) } else { openings_from_pgn(&text) })
+}
+
+/// The positions of an EPD text, the operations after the first four fields are left out.
+pub fn openings_from_epd(text: &str) -> Vec<Opening> {
+    text.lines()
+        .map(|line| line.split_whitespace().take(4).collect::<Vec<&str>>())
+        .filter(|fields| fields.len() == 4)
+        .map(|fields| Opening { fen: Some(format!("{} 0 1", fields.join(" "))), moves: Vec::new() })
+        .collect()
+}
+
+/// The games of a PGN text as openings, moves after the first one that is not legal are left out.
+pub fn openings_from_pgn(text: &str) -> Vec<Opening> {
+    parse_pgn(text).iter().map(|pgn| {
+        let fen = pgn.tag("FEN").map(str::to_string);
+        let mut game = Opening { fen: fen.clone(), moves: Vec::new() }.game();
+        let mut moves = Vec::new();
+        for san in &pgn.moves {
+            match game.parse_san(san) {
+                Some(mv) => {
+                    moves.push(mv.to_uci());
+                    game.play(mv);
+                }
+                None => break,
+            }
+        }
+        Opening { fen, moves }
+    }).collect()
+}
+
+/// A score in centipawns from the point of view of the engine, mates are a lot.
+fn centipawns(score: Score) -> i32 {
+    match score {
+        Score::Centipawns(cp) => cp,
+        Score::Mate(moves) if moves > 0 => MATE_CENTIPAWNS - moves,
+        Score::Mate(moves) => -MATE_CENTIPAWNS - moves,
+    }
+}
+
+/// Today's date the PGN way, "2024.05.17".
+fn pgn_date() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs() / 86400) as i64;
+    // days since 1970-01-01 to year, month and day in the proleptic Gregorian calendar
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/**
+ * A game played by play_engine_game, the winner, why the game ended and the game as PGN
+ */
+#[derive(Clone, Debug)]
+pub struct EngineGame {
+    pub outcome: Outcome,
+    pub reason: String,
+    pub pgn: PgnGame,
+}
+
+/**
+ * Plays one game between two engines from the opening with the time control, until the game
+ * is over, it is adjudicated, or an engine loses on time, plays an illegal move or stops
+ */
+pub fn play_engine_game(white: &mut UciEngine, black: &mut UciEngine, opening: &Opening,
+                        time_control: TimeControl, adjudication: &Adjudication) -> EngineGame {
+    let mut game = opening.game();
+    let start_fen = game.to_fen();
+    let mut uci_moves: Vec<String> = Vec::new();
+    let mut san_moves: Vec<String> = Vec::new();
+    let mut clocks = [time_control.base, time_control.base];
+    let mut winning = [0, 0];
+    let mut losing = [0, 0];
+    let mut drawish = 0;
+    let side = |color: ColorTurn| if color == ColorTurn::White { 0 } else { 1 };
+    let _ = white.new_game();
+    let _ = black.new_game();
+
+    let (outcome, reason, termination) = loop {
+        let turn = game.get_game_turn();
+        let winner_if_lost = if turn == ColorTurn::White { Outcome::BlackWins } else { Outcome::WhiteWins };
+
+        if let Some((result, termination)) = game.board_result() {
+            let outcome = match result {
+                GameResult::WhiteWins => Outcome::WhiteWins,
+                GameResult::BlackWins => Outcome::BlackWins,
+                _ => Outcome::Draw,
+            };
+            let reason = match termination {
+                Termination::Checkmate => "checkmate",
+                Termination::Stalemate => "stalemate",
+                Termination::FiftyMoves => "fifty moves",
+                Termination::Repetition => "threefold repetition",
+                Termination::InsufficientMaterial => "insufficient material",
+                _ => "variant rule",
+            };
+            break (outcome, reason, "normal");
+        }
+        if adjudication.max_moves.is_some_and(|max| game.fullmove_number > max) {
+            break (Outcome::Draw, "adjudication, too many moves", "adjudication");
+        }
+
+        let position = if opening.fen.is_none() {
+            format!("startpos moves {}", opening.moves.iter().chain(uci_moves.iter()).cloned().collect::<Vec<_>>().join(" "))
+        } else {
+            format!("fen {} moves {}", start_fen, uci_moves.join(" "))
+        };
+        let clock_args = format!(
+            "wtime {} btime {} winc {} binc {}",
+            clocks[0].as_millis(), clocks[1].as_millis(), time_control.increment.as_millis(), time_control.increment.as_millis()
+        );
+        let engine = if turn == ColorTurn::White { &mut *white } else { &mut *black };
+        let started = Instant::now();
+        let reply = engine.go(position.trim_end(), &clock_args, clocks[side(turn)]);
+        let used = started.elapsed();
+
+        let (text, score) = match reply {
+            Reply::Move(text, score) => (text, score),
+            Reply::Timeout => break (winner_if_lost, "time forfeit", "time forfeit"),
+            Reply::Gone => break (winner_if_lost, "the engine stopped", "abandoned"),
+        };
+        if used > clocks[side(turn)] {
+            break (winner_if_lost, "time forfeit", "time forfeit");
+        }
+        clocks[side(turn)] = clocks[side(turn)] - used + time_control.increment;
+        let mv = match game.parse_move(&text) {
+            Some(mv) => mv,
+            None => break (winner_if_lost, "illegal move", "rules infraction"),
+        };
+        san_moves.push(game.move_to_san(mv));
+        uci_moves.push(text);
+        game.play(mv);
+
+        // both engines have to agree before a game is adjudicated
+        let score = score.map_or(0, centipawns);
+        let me = side(turn);
+        if let Some(threshold) = adjudication.resign_score {
+            losing[me] = if score <= -threshold { losing[me] + 1 } else { 0 };
+            winning[me] = if score >= threshold { winning[me] + 1 } else { 0 };
+            if losing[me] >= adjudication.resign_moves && winning[1 - me] >= adjudication.resign_moves {
+                break (winner_if_lost, "adjudication, resigned", "adjudication");
+            }
+            let winner = if turn == ColorTurn::White { Outcome::WhiteWins } else { Outcome::BlackWins };
+            if winning[me] >= adjudication.resign_moves && losing[1 - me] >= adjudication.resign_moves {
+                break (winner, "adjudication, resigned", "adjudication");
+            }
+        }
+        if let Some(threshold) = adjudication.draw_score {
+            drawish = if score.abs() <= threshold { drawish + 1 } else { 0 };
+            if game.fullmove_number >= adjudication.draw_start && drawish >= 2 * adjudication.draw_moves {
+                break (Outcome::Draw, "adjudication, draw", "adjudication");
+            }
+        }
+    };
+
+    let result = match outcome {
+        Outcome::WhiteWins => "1-0",
+        Outcome::BlackWins => "0-1",
+        Outcome::Draw => "1/2-1/2",
+    };
+    let mut tags: Vec<(String, String)> = vec![
+        ("Event".to_string(), "Engine match".to_string()),
+        ("Site".to_string(), "?".to_string()),
+        ("Date".to_string(), pgn_date()),
+        ("Round".to_string(), "?".to_string()),
+        ("White".to_string(), white.name().to_string()),
+        ("Black".to_string(), black.name().to_string()),
+        ("Result".to_string(), result.to_string()),
+        ("TimeControl".to_string(), time_control.to_string()),
+        ("Termination".to_string(), termination.to_string()),
+    ];
+    if opening.fen.is_some() {
+        tags.push(("SetUp".to_string(), "1".to_string()));
+        tags.push(("FEN".to_string(), opening.fen.clone().unwrap_or_default()));
+    }
+    // the moves of the opening are written as well, from the start of the opening
+    let mut position = Opening { fen: opening.fen.clone(), moves: Vec::new() }.game();
+    let mut moves = Vec::new();
+    for text in opening.moves.iter() {
+        match position.parse_move(text) {
+            Some(mv) => {
+                moves.push(position.move_to_san(mv));
+                position.play(mv);
+            }
+            None => break,
+        }
+    }
+    moves.extend(san_moves);
+
+    EngineGame {
+        outcome,
+        reason: reason.to_string(),
+        pgn: PgnGame { tags, moves, result: result.to_string() },
+    }
+}
+
+/**
+ * Wins, draws and losses of the first engine of a match, and the scores of the finished
+ * game pairs played from the same opening
+ */
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub pairs: Pentanomial,
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The share of the points the first engine got, from 0 to 1.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+
+    /**
+     * The Elo difference of the first engine over the second and the margin of the 95%
+     * confidence interval. None if there are no games or one engine got all the points
+     */
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let games = self.games() as f64;
+        let score = self.score();
+        if self.games() == 0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2)) / games;
+        let margin = 1.959964 * (variance / games).sqrt();
+        // adding 0 turns the -0 of an even score into 0
+        let elo = |score: f64| -400.0 * (1.0 / score.clamp(1e-6, 1.0 - 1e-6) - 1.0).log10() + 0.0;
+        Some((elo(score), (elo(score + margin) - elo(score - margin)) / 2.0))
+    }
+}
+
+/**
+ * The games of a match, how they are played and from which openings. With an SPRT the
+ * match ends when the test has accepted a hypothesis, or after games at the most
+ */
+#[derive(Clone, Debug)]
+pub struct MatchConfig {
+    pub games: u32,
+    pub time_control: TimeControl,
+    pub openings: Vec<Opening>,
+    pub adjudication: Adjudication,
+    pub sprt: Option<Sprt>,
+}
+
+/**
+ * Plays the games of a match between two engines. Every opening is played twice with
+ * the colors switched, the first engine is white in the first game of each pair.
+ * on_game is called after every game with the game and the score so far
+ */
+pub fn run_match<F>(first: &mut UciEngine, second: &mut UciEngine, config: &MatchConfig, mut on_game: F) -> MatchScore
+    where F: FnMut(&EngineGame, &MatchScore) {
+    let mut score = MatchScore::default();
+    let start = vec![Opening::default()];
+    let openings = if config.openings.is_empty() { &start } else { &config.openings };
+    let mut pair_half_points = 0;
+
+    for round in 0..config.games {
+        let opening = &openings[(round as usize / 2) % openings.len()];
+        let first_is_white = round % 2 == 0;
+        let mut game = if first_is_white {
+            play_engine_game(first, second, opening, config.time_control, &config.adjudication)
+        } else {
+            play_engine_game(second, first, opening, config.time_control, &config.adjudication)
+        };
+        game.pgn.set_tag("Round", &(round + 1).to_string());
+
+        match (game.outcome, first_is_white) {
+            (Outcome::Draw, _) => {
+                score.draws += 1;
+                pair_half_points += 1;
+            }
+            (Outcome::WhiteWins, true) | (Outcome::BlackWins, false) => {
+                score.wins += 1;
+                pair_half_points += 2;
+            }
+            _ => score.losses += 1,
+        }
+        if !first_is_white {
+            score.pairs.add(pair_half_points);
+            pair_half_points = 0;
+        }
+        on_game(&game, &score);
+
+        // the test only looks at whole pairs
+        if !first_is_white && config.sprt.is_some_and(|sprt| sprt.result(&score.pairs).is_some()) {
+            break;
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{openings_from_epd, openings_from_pgn, run_match, Adjudication, MatchConfig, MatchScore, TimeControl, UciEngine};
+    use crate::Ai;
+    use std::time::Duration;
+
+    fn engine() -> UciEngine {
+        UciEngine::from_ai(Ai::default()).unwrap()
+    }
+
+    #[test]
+    fn reads_openings_and_time_controls() {
+        let epd = openings_from_epd("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 id \"e4\";\n\n");
+        assert_eq!(epd.len(), 1);
+        assert_eq!(epd[0].fen.as_deref(), Some("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"));
+
+        let pgn = openings_from_pgn("1. e4 e5 2. Nf3 *\n\n1. d4 Zz9 *\n");
+        assert_eq!(pgn[0].moves, vec!["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(pgn[1].moves, vec!["d2d4"]);
+
+        assert_eq!(TimeControl::parse("10+0.1"), Some(TimeControl { base: Duration::from_secs(10), increment: Duration::from_millis(100) }));
+        assert_eq!(TimeControl::parse("fast"), None);
+    }
+
+    #[test]
+    fn elo_follows_the_score() {
+        assert_eq!(MatchScore { wins: 5, draws: 0, losses: 5, ..MatchScore::default() }.elo().unwrap().0, 0.0);
+        let (elo, margin) = MatchScore { wins: 60, draws: 20, losses: 20, ..MatchScore::default() }.elo().unwrap();
+        assert!((elo - 147.2).abs() < 0.5);
+        assert!(margin > 0.0);
+        assert!(MatchScore { wins: 3, draws: 0, losses: 0, ..MatchScore::default() }.elo().is_none());
+    }
+
+    #[test]
+    fn plays_a_match_between_engines() {
+        let mut first = engine();
+        let mut second = engine();
+        let config = MatchConfig {
+            games: 2,
+            time_control: TimeControl { base: Duration::from_secs(1), increment: Duration::ZERO },
+            openings: openings_from_pgn("1. e4 e5 *"),
+            adjudication: Adjudication { max_moves: Some(6), ..Adjudication::default() },
+            sprt: None,
+        };
+        let mut games = Vec::new();
+        let score = run_match(&mut first, &mut second, &config, |game, _| games.push(game.pgn.clone()));
+
+        assert_eq!(score.games(), 2);
+        assert_eq!(games[0].tag("White"), games[1].tag("Black"));
+        assert_eq!(games[1].tag("Round"), Some("2"));
+        assert_eq!(&games[0].moves[..2], ["e4", "e5"]);
+        assert_eq!(score.pairs.pairs(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{Game, _NONE, _PAWN, _BISHOP, _KNIGHT, _ROOK, _QUEEN, _KING, _WHITE};
+
+/**
+ * The value of each piece in centipawns, indexed by the piece value (_PAWN = 1 and so on).
+ * After the king come the fairy pieces, the Archbishop, the Chancellor, the Amazon and the
+ * Camel, and 8 is no piece
+ */
+pub const PIECE_VALUES: [i32; 12] = [0, 100, 330, 320, 500, 900, 0, 825, 0, 875, 1250, 250];
+
+/* Piece square tables seen from white, with rank 8 on the first row */
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+const KNIGHT_TABLE: [i32; 64] = [
+   -50,-40,-30,-30,-30,-30,-40,-50,
+   -40,-20,  0,  0,  0,  0,-20,-40,
+   -30,  0, 10, 15, 15, 10,  0,-30,
+   -30,  5, 15, 20, 20, 15,  5,-30,
+   -30,  0, 15, 20, 20, 15,  0,-30,
+   -30,  5, 10, 15, 15, 10,  5,-30,
+   -40,-20,  0,  5,  5,  0,-20,-40,
+   -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+const BISHOP_TABLE: [i32; 64] = [
+   -20,-10,-10,-10,-10,-10,-10,-20,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -10,  0,  5, 10, 10,  5,  0,-10,
+   -10,  5,  5, 10, 10,  5,  5,-10,
+   -10,  0, 10, 10, 10, 10,  0,-10,
+   -10, 10, 10, 10, 10, 10, 10,-10,
+   -10,  5,  0,  0,  0,  0,  5,-10,
+   -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+const QUEEN_TABLE: [i32; 64] = [
+   -20,-10,-10, -5, -5,-10,-10,-20,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+    -5,  0,  5,  5,  5,  5,  0, -5,
+     0,  0,  5,  5,  5,  5,  0, -5,
+   -10,  5,  5,  5,  5,  5,  0,-10,
+   -10,  0,  5,  0,  0,  0,  0,-10,
+   -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+const KING_MIDDLEGAME_TABLE: [i32; 64] = [
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -20,-30,-30,-40,-40,-30,-30,-20,
+   -10,-20,-20,-20,-20,-20,-20,-10,
+    20, 20,  0,  0,  0,  0, 20, 20,
+    20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+const KING_ENDGAME_TABLE: [i32; 64] = [
+   -50,-40,-30,-20,-20,-30,-40,-50,
+   -30,-20,-10,  0,  0,-10,-20,-30,
+   -30,-10, 20, 30, 30, 20,-10,-30,
+   -30,-10, 30, 40, 40, 30,-10,-30,
+   -30,-10, 30, 40, 40, 30,-10,-30,
+   -30,-10, 20, 30, 30, 20,-10,-30,
+   -30,-30,  0,  0,  0,  0,-30,-30,
+   -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+/* How much each piece counts towards the game being in the middlegame, a full board has 24 */
+const PHASE_VALUES: [i32; 7] = [0, 0, 1, 1, 2, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+/**
+ * Evaluates the position on the board in centipawns, from the point of view
+ * of the player whose turn it is. A positive value means that player is better.
+ * The variant the game is played by evaluates it, see standard_evaluation
+ */
+pub fn evaluate(game: &Game) -> i32 {
+    game.variant().evaluate(game)
+}
+
+/**
+ * Evaluates the position by the rules of standard chess, from the point of view of the
+ * player whose turn it is.
+ *
+ * The evaluation is the material on the board plus a bonus from the piece square tables,
+ * the king table is blended between the middlegame and the endgame one depending on
+ * how much material is left
+ */
+pub fn standard_evaluation(game: &Game) -> i32 {
+    let mut score = 0;
+    let mut king_middlegame = 0;
+    let mut king_endgame = 0;
+    let mut phase = 0;
+
+    for (_pos, square) in game.board.iter().enumerate() {
+        let (piece, color) = (square[0], square[1]);
+        if piece == _NONE {
+            continue;
+        }
+        // the tables have rank 8 first, so white pieces are flipped and black pieces are not
+        let _index = if color == _WHITE { (7 - _pos / 8) * 8 + _pos % 8 } else { _pos };
+        let sign = if color == _WHITE { 1 } else { -1 };
+
+        phase += PHASE_VALUES[piece as usize];
+        score += sign * PIECE_VALUES[piece as usize];
+        score += sign * match piece {
+            _PAWN => PAWN_TABLE[_index],
+            _KNIGHT => KNIGHT_TABLE[_index],
+            _BISHOP => BISHOP_TABLE[_index],
+            _ROOK => ROOK_TABLE[_index],
+            _QUEEN => QUEEN_TABLE[_index],
+            _ => 0,
+        };
+        if piece == _KING {
+            king_middlegame += sign * KING_MIDDLEGAME_TABLE[_index];
+            king_endgame += sign * KING_ENDGAME_TABLE[_index];
+        }
+    }
+
+    let phase = phase.min(MAX_PHASE);
+    score += (king_middlegame * phase + king_endgame * (MAX_PHASE - phase)) / MAX_PHASE;
+
+    if game.color_to_move() == _WHITE { score } else { -score }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate;
+    use crate::Game;
+
+    #[test]
+    fn start_position_is_equal() {
+        let mut game = Game::new();
+        game.init_board();
+        assert_eq!(evaluate(&game), 0);
+    }
+
+    #[test]
+    fn extra_queen_is_better() {
+        let mut game = Game::new();
+        game.init_board();
+        game.load_fen_board("4k3/8/8/8/8/8/8/3QK3 b - - 0 1".to_string());
+        assert!(evaluate(&game) < -800);
+    }
+}
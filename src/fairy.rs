@@ -0,0 +1,558 @@
+use crate::eval::PIECE_VALUES;
+use crate::variant::Variant;
+use crate::{Game, Move, _NONE, _PAWN, _BISHOP, _KNIGHT, _ROOK, _QUEEN, _KING, _ARCHBISHOP, _CHANCELLOR, _WHITE};
+
+/* The most files and ranks a board can have, the squares must fit in a u8 and the files run to p */
+const MAX_SIDE: u8 = 16;
+/* The most squares a board can have, square 255 is where drops come from */
+const MAX_SQUARES: usize = 255;
+/* The leaps of the Betza atoms as files and ranks moved, every other direction is found by symmetry */
+const ATOMS: [(char, i8, i8); 9] = [
+    ('W', 1, 0), ('F', 1, 1), ('D', 2, 0), ('N', 2, 1), ('A', 2, 2),
+    ('H', 3, 0), ('C', 3, 1), ('Z', 3, 2), ('G', 3, 3),
+];
+/* The Betza shorthands for pieces that are made of other atoms */
+const SHORTHANDS: [(char, &[&str]); 4] = [('R', &["WW"]), ('B', &["FF"]), ('Q', &["WW", "FF"]), ('K', &["W", "F"])];
+/* The pieces piece_definition knows, with their Betza notation */
+const PIECES: [(&str, &str); 10] = [
+    ("Pawn", "fmWfcF"),
+    ("Knight", "N"),
+    ("Bishop", "B"),
+    ("Rook", "R"),
+    ("Queen", "Q"),
+    ("King", "K"),
+    ("Archbishop", "BN"),
+    ("Chancellor", "RN"),
+    ("Amazon", "QN"),
+    ("Camel", "C"),
+];
+/* The bonus for each half square a piece stands closer to the middle of the board than its edge */
+const CENTRE_BONUS: i32 = 5;
+
+/**
+ * The size of a board, with the files from a and the ranks from 1. The squares are
+ * numbered the way Game numbers them, a1 is 0 and the first rank is counted first,
+ * so a board of any size is a Vec of [piece, color] squares just like Game's board
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Geometry {
+    pub files: u8,
+    pub ranks: u8,
+}
+
+impl Geometry {
+    pub const STANDARD: Geometry = Geometry { files: 8, ranks: 8 };
+    pub const CAPABLANCA: Geometry = Geometry { files: 10, ranks: 8 };
+    pub const LOS_ALAMOS: Geometry = Geometry { files: 6, ranks: 6 };
+
+    /**
+     * A board with the files and ranks, None if a side is 0 or more than 16 or the
+     * board is 16 by 16, which has one square too many
+     */
+    pub fn new(files: u8, ranks: u8) -> Option<Geometry> {
+        if (1..=MAX_SIDE).contains(&files) && (1..=MAX_SIDE).contains(&ranks) && files as usize * ranks as usize <= MAX_SQUARES {
+            Some(Geometry { files, ranks })
+        } else {
+            None
+        }
+    }
+
+    pub fn squares(&self) -> usize {
+        self.files as usize * self.ranks as usize
+    }
+
+    /// The square on the file and rank counted from 0, None if it is off the board.
+    pub fn square(&self, file: i8, rank: i8) -> Option<u8> {
+        if (0..self.files as i8).contains(&file) && (0..self.ranks as i8).contains(&rank) {
+            Some(rank as u8 * self.files + file as u8)
+        } else {
+            None
+        }
+    }
+
+    /// The name of the square, for example "j1" on a Capablanca board.
+    pub fn square_name(&self, _pos: u8) -> String {
+        format!("{}{}", (b'a' + _pos % self.files) as char, _pos / self.files + 1)
+    }
+
+    /// Changes a square name such as "j8" to a square, returns None if it is not on the board.
+    pub fn parse_square(&self, name: &str) -> Option<u8> {
+        let mut chars = name.chars();
+        let file = chars.next()?;
+        if !file.is_ascii_lowercase() {
+            return None;
+        }
+        let rank: u8 = chars.as_str().parse().ok()?;
+        if rank == 0 {
+            return None;
+        }
+        self.square((file as u8 - b'a') as i8, rank as i8 - 1)
+    }
+
+    /// A board with every square empty.
+    pub fn empty_board(&self) -> Vec<Vec<u8>> {
+        vec![vec![_NONE, 2]; self.squares()]
+    }
+}
+
+/// One direction a piece moves in, as far as range allows.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Step {
+    file: i8,
+    rank: i8,
+    /* how many times the step may be repeated, 0 is as far as the board goes */
+    range: u8,
+    moves: bool,
+    captures: bool,
+}
+
+/**
+ * How a piece moves, read from Betza notation. The atoms W, F, D, N, A, H, C, Z and G leap
+ * once, a doubled atom rides as far as it can, so WW is a rook, and a number after the atom
+ * limits how far it rides. R, B, Q and K are short for WW, FF, WWFF and WF. The letters
+ * before an atom limit it: m only moves, c only captures, f goes forward, b backward,
+ * s sideways and v up and down the file. Forward is up the board for white and down for black
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct PieceDefinition {
+    name: String,
+    betza: String,
+    steps: Vec<Step>,
+}
+
+impl PieceDefinition {
+
+    /// Reads the Betza notation of the piece, None if it is not notation this understands.
+    pub fn from_betza(name: &str, betza: &str) -> Option<PieceDefinition> {
+        // the letters before a shorthand limit every atom it is made of
+        let mut expanded = String::new();
+        let mut pending = String::new();
+        for c in betza.chars() {
+            match SHORTHANDS.iter().find(|(short, _)| *short == c) {
+                Some((_, parts)) => {
+                    expanded.push_str(&parts.join(&pending));
+                    pending.clear();
+                }
+                None if "mcfbsv".contains(c) => pending.push(c),
+                None => pending.clear(),
+            }
+            if !SHORTHANDS.iter().any(|(short, _)| *short == c) {
+                expanded.push(c);
+            }
+        }
+
+        let mut steps = Vec::new();
+        let mut modifiers = String::new();
+        let mut chars = expanded.chars().peekable();
+        while let Some(c) = chars.next() {
+            if "mcfbsv".contains(c) {
+                modifiers.push(c);
+                continue;
+            }
+            let (_, file, rank) = ATOMS.iter().find(|(atom, _, _)| *atom == c)?;
+            let mut range = 1;
+            if chars.peek() == Some(&c) {
+                chars.next();
+                range = 0;
+            } else if let Some(digit) = chars.peek().and_then(|d| d.to_digit(10)) {
+                chars.next();
+                range = digit as u8;
+            }
+            PieceDefinition::add_steps(&mut steps, *file, *rank, range, &modifiers);
+            modifiers.clear();
+        }
+        if steps.is_empty() || !modifiers.is_empty() {
+            return None;
+        }
+        Some(PieceDefinition { name: name.to_string(), betza: betza.to_string(), steps })
+    }
+
+    /// Adds every direction of the leap that the modifiers allow.
+    fn add_steps(steps: &mut Vec<Step>, file: i8, rank: i8, range: u8, modifiers: &str) {
+        let moves = !modifiers.contains('c') || modifiers.contains('m');
+        let captures = !modifiers.contains('m') || modifiers.contains('c');
+        let directions = modifiers.chars().filter(|c| "fbsv".contains(*c)).collect::<String>();
+        for (x, y) in [(file, rank), (rank, file)] {
+            for (sx, sy) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                let (file, rank) = (x * sx, y * sy);
+                let allowed = directions.is_empty()
+                    || (directions.contains('f') && rank > 0)
+                    || (directions.contains('b') && rank < 0)
+                    || (directions.contains('s') && rank == 0)
+                    || (directions.contains('v') && file == 0);
+                let step = Step { file, rank, range, moves, captures };
+                if allowed && !steps.contains(&step) {
+                    steps.push(step);
+                }
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn betza(&self) -> &str {
+        &self.betza
+    }
+
+    /**
+     * The squares the piece on the square can move to on the board, which has
+     * geometry.squares() squares of [piece, color]. Whether a move leaves the king
+     * in check is not looked at
+     */
+    pub fn targets(&self, geometry: Geometry, board: &[Vec<u8>], _from: u8) -> Vec<u8> {
+        let color = board[_from as usize][1];
+        let forward = if color == _WHITE { 1 } else { -1 };
+        let (file, rank) = ((_from % geometry.files) as i8, (_from / geometry.files) as i8);
+
+        let mut targets = Vec::new();
+        for step in &self.steps {
+            let mut n = 1;
+            while let Some(_to) = geometry.square(file + step.file * n, rank + step.rank * forward * n) {
+                let square = &board[_to as usize];
+                if square[0] == _NONE {
+                    if step.moves && !targets.contains(&_to) {
+                        targets.push(_to);
+                    }
+                } else {
+                    if step.captures && square[1] != color && !targets.contains(&_to) {
+                        targets.push(_to);
+                    }
+                    break;
+                }
+                if step.range != 0 && n >= step.range as i8 {
+                    break;
+                }
+                n += 1;
+            }
+        }
+        targets
+    }
+}
+
+/**
+ * The piece with the name, in any case: the standard pieces and the Archbishop (BN),
+ * Chancellor (RN), Amazon (QN) and Camel (C)
+ */
+pub fn piece_definition(name: &str) -> Option<PieceDefinition> {
+    PIECES.iter()
+        .find(|(piece, _)| piece.eq_ignore_ascii_case(name))
+        .and_then(|(piece, betza)| PieceDefinition::from_betza(piece, betza))
+}
+
+/**
+ * A variant where the pieces move by their piece definitions, on a board of any size. The
+ * pawns move as in standard chess, with the double step if double_step is set, and promote
+ * on the last rank to the promotions. The king also castles where the castling rights allow
+ * it, ending on the same squares counted from the edges as in standard chess
+ */
+#[derive(Clone, Debug)]
+pub struct Fairy {
+    pub name: String,
+    /// The starting position as a full fen string, the empty squares of a rank may be more than nine.
+    pub start_fen: String,
+    pub geometry: Geometry,
+    /// How every piece but the pawn moves, by the piece value (_KNIGHT = 3 and so on).
+    pub pieces: Vec<(u8, PieceDefinition)>,
+    pub promotions: Vec<u8>,
+    pub double_step: bool,
+}
+
+impl Fairy {
+
+    /**
+     * Capablanca chess on a board ten files wide, with an Archbishop (A) that moves as a
+     * bishop and a knight and a Chancellor (C) that moves as a rook and a knight
+     */
+    pub fn capablanca() -> Fairy {
+        Fairy {
+            name: "Capablanca".to_string(),
+            start_fen: "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1".to_string(),
+            geometry: Geometry::CAPABLANCA,
+            pieces: Fairy::definitions(&[(_KNIGHT, "Knight"), (_BISHOP, "Bishop"), (_ROOK, "Rook"), (_QUEEN, "Queen"),
+                (_KING, "King"), (_ARCHBISHOP, "Archbishop"), (_CHANCELLOR, "Chancellor")]),
+            promotions: vec![_QUEEN, _ROOK, _BISHOP, _KNIGHT, _ARCHBISHOP, _CHANCELLOR],
+            double_step: true,
+        }
+    }
+
+    /// Los Alamos chess on a six by six board without bishops, pawns only move one square and nobody castles.
+    pub fn los_alamos() -> Fairy {
+        Fairy {
+            name: "Los Alamos".to_string(),
+            start_fen: "rnqknr/pppppp/6/6/PPPPPP/RNQKNR w - - 0 1".to_string(),
+            geometry: Geometry::LOS_ALAMOS,
+            pieces: Fairy::definitions(&[(_KNIGHT, "Knight"), (_ROOK, "Rook"), (_QUEEN, "Queen"), (_KING, "King")]),
+            promotions: vec![_QUEEN, _ROOK, _KNIGHT],
+            double_step: false,
+        }
+    }
+
+    /// The definitions piece_definition has for the pieces.
+    fn definitions(pieces: &[(u8, &str)]) -> Vec<(u8, PieceDefinition)> {
+        pieces.iter().filter_map(|(piece, name)| piece_definition(name).map(|definition| (*piece, definition))).collect()
+    }
+
+    /// Adds the moves of the pawn on _from, one or two squares forward, diagonal captures and en passante.
+    fn pawn_moves(&self, game: &Game, _from: u8, moves: &mut Vec<Move>) {
+        let geometry = self.geometry;
+        let color = game.board[_from as usize][1];
+        let (forward, start_rank, last_rank) = if color == _WHITE { (1, 1, geometry.ranks - 1) } else { (-1, geometry.ranks as i8 - 2, 0) };
+        let (file, rank) = ((_from % geometry.files) as i8, (_from / geometry.files) as i8);
+
+        let mut targets = Vec::new();
+        if let Some(one) = geometry.square(file, rank + forward).filter(|one| game.board[*one as usize][0] == _NONE) {
+            targets.push(one);
+            if let Some(two) = geometry.square(file, rank + 2 * forward) {
+                if self.double_step && rank == start_rank && game.board[two as usize][0] == _NONE {
+                    targets.push(two);
+                }
+            }
+        }
+        for side in [-1, 1] {
+            let _to = match geometry.square(file + side, rank + forward) {
+                Some(_to) => _to,
+                None => continue,
+            };
+            let square = &game.board[_to as usize];
+            // the pawn that just moved two squares next to this one can be taken en passante
+            let en_passant = game.last_moved_pawn[1] != 2 && game.last_moved_pawn[1] != color
+                && geometry.square(file + side, rank) == Some(game.last_moved_pawn[0]);
+            if (square[0] != _NONE && square[1] != color) || (square[0] == _NONE && en_passant) {
+                targets.push(_to);
+            }
+        }
+
+        for _to in targets {
+            if _to / geometry.files == last_rank {
+                moves.extend(self.promotions.iter().map(|piece| Move { promotion: *piece, ..Move::new(_from, _to) }));
+            } else {
+                moves.push(Move::new(_from, _to));
+            }
+        }
+    }
+}
+
+impl Variant for Fairy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn start_fen(&self) -> &str {
+        &self.start_fen
+    }
+
+    fn geometry(&self) -> Geometry {
+        self.geometry
+    }
+
+    fn piece_definition(&self, piece: u8) -> Option<&PieceDefinition> {
+        self.pieces.iter().find(|(code, _)| *code == piece).map(|(_, definition)| definition)
+    }
+
+    fn generate_moves(&self, game: &Game) -> Vec<Move> {
+        let color = game.color_to_move();
+        let mut moves = Vec::new();
+        for _from in 0..game.board.len() as u8 {
+            let piece = game.board[_from as usize][0];
+            if piece == _NONE || game.board[_from as usize][1] != color {
+                continue;
+            }
+            if piece == _PAWN {
+                self.pawn_moves(game, _from, &mut moves);
+                continue;
+            }
+            if let Some(definition) = self.piece_definition(piece) {
+                moves.extend(definition.targets(self.geometry, &game.board, _from).into_iter().map(|_to| Move::new(_from, _to)));
+            }
+            if piece == _KING {
+                moves.append(&mut game.castling_moves(_from));
+            }
+        }
+        moves
+    }
+
+    /**
+     * The material on the board and a bonus for pieces near the middle, the piece square
+     * tables of the standard evaluation only fit the standard board
+     */
+    fn evaluate(&self, game: &Game) -> i32 {
+        let (files, ranks) = (self.geometry.files as i32, self.geometry.ranks as i32);
+        let mut score = 0;
+        for (_pos, square) in game.board.iter().enumerate().filter(|(_, square)| square[0] != _NONE) {
+            let (file, rank) = (_pos as i32 % files, _pos as i32 / files);
+            // the distance from the middle in half squares, counted the way a king walks
+            let distance = (2 * file + 1 - files).abs().max((2 * rank + 1 - ranks).abs());
+            let centre = if square[0] == _KING { 0 } else { CENTRE_BONUS * (files.max(ranks) - distance) };
+            let value = PIECE_VALUES.get(square[0] as usize).copied().unwrap_or(0) + centre;
+            score += if square[1] == game.color_to_move() { value } else { -value };
+        }
+        score
+    }
+}
+
+impl Game {
+
+    /**
+     * Checks if the variant moves the pieces by piece definitions instead of the moves
+     * Game generates itself, which only know the standard board. A variant that does
+     * has a definition for the king
+     */
+    pub(crate) fn uses_piece_definitions(&self) -> bool {
+        self.geometry != Geometry::STANDARD || self.variant.piece_definition(_KING).is_some()
+    }
+
+    /**
+     * Checks if a square is attacked by any piece of the given color, with the pieces
+     * moving by the piece definitions of the variant and the pawns taking diagonally forward
+     */
+    pub(crate) fn is_square_attacked_by_definitions(&self, _pos: u8, by_color: u8) -> bool {
+        let geometry = self.geometry;
+        let forward = if by_color == _WHITE { 1 } else { -1 };
+        let (file, rank) = ((_pos % geometry.files) as i8, (_pos / geometry.files) as i8);
+
+        // pawns attack diagonally forward, so look diagonally backwards from the square
+        let pawn = [-1, 1].iter().filter_map(|side| geometry.square(file + side, rank - forward));
+        if pawn.into_iter().any(|_from| self.board[_from as usize] == [_PAWN, by_color]) {
+            return true;
+        }
+        (0..self.board.len() as u8).any(|_from| {
+            let square = &self.board[_from as usize];
+            square[1] == by_color && square[0] != _PAWN && self.variant.piece_definition(square[0])
+                .is_some_and(|definition| definition.targets(geometry, &self.board, _from).contains(&_pos))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{piece_definition, Fairy, Geometry, PieceDefinition};
+    use crate::perft;
+    use crate::{Game, _BLACK, _BISHOP, _KNIGHT, _PAWN, _WHITE};
+    use std::sync::Arc;
+
+    fn start(variant: Fairy) -> Game {
+        let mut game = Game::new();
+        game.set_variant(Arc::new(variant));
+        game.init_board();
+        game
+    }
+
+    fn play(game: &mut Game, moves: &str) {
+        for text in moves.split_whitespace() {
+            let mv = game.parse_move(text).or_else(|| game.parse_san(text)).unwrap();
+            assert!(game.play(mv), "{} is not legal", text);
+        }
+    }
+
+    #[test]
+    fn names_squares_on_any_board() {
+        let capablanca = Geometry::CAPABLANCA;
+        assert_eq!(capablanca.square_name(9), "j1");
+        assert_eq!(capablanca.parse_square("j8"), Some(79));
+        assert_eq!(capablanca.parse_square("k1"), None);
+        assert_eq!(Geometry::LOS_ALAMOS.squares(), 36);
+        assert!(Geometry::new(17, 8).is_none());
+        assert!(Geometry::new(16, 16).is_none());
+    }
+
+    #[test]
+    fn knights_move_as_in_the_game() {
+        let mut game = Game::new();
+        game.init_board();
+        let knight = piece_definition("knight").unwrap();
+        let board = game.get_board_vec();
+        for _from in (0..64u8).filter(|_pos| board[*_pos as usize][0] == _KNIGHT && board[*_pos as usize][1] == _WHITE) {
+            let mut targets = knight.targets(Geometry::STANDARD, &board, _from);
+            let mut moves: Vec<u8> = game.legal_moves().iter().filter(|mv| mv.from == _from).map(|mv| mv.to).collect();
+            targets.sort();
+            moves.sort();
+            assert_eq!(targets, moves);
+        }
+    }
+
+    #[test]
+    fn fairy_pieces_move_by_their_betza_notation() {
+        let geometry = Geometry::CAPABLANCA;
+        let mut board = geometry.empty_board();
+        board[0] = vec![_BISHOP, _WHITE];
+        // the bishop part goes from a1 to h8, the knight part to b3 and c2
+        assert_eq!(piece_definition("Archbishop").unwrap().targets(geometry, &board, 0).len(), 9);
+
+        let geometry = Geometry::STANDARD;
+        let mut board = geometry.empty_board();
+        board[0] = vec![_BISHOP, _WHITE];
+        assert_eq!(piece_definition("Camel").unwrap().targets(geometry, &board, 0), vec![11, 25]);
+        // the letters before a shorthand hold for all of it, so this goes up the file and the diagonal
+        let forward_queen = PieceDefinition::from_betza("Forward queen", "fQ").unwrap();
+        assert_eq!(forward_queen.targets(geometry, &board, 0).len(), 14);
+
+        // a rook that goes two squares at most
+        let geometry = Geometry::LOS_ALAMOS;
+        let mut board = geometry.empty_board();
+        board[14] = vec![_BISHOP, _BLACK];
+        let short_rook = PieceDefinition::from_betza("Short rook", "W2").unwrap();
+        assert_eq!(short_rook.targets(geometry, &board, 14).len(), 8);
+
+        assert!(PieceDefinition::from_betza("Nothing", "X").is_none());
+        assert!(PieceDefinition::from_betza("Nothing", "m").is_none());
+    }
+
+    #[test]
+    fn pawns_move_forward_and_capture_diagonally() {
+        let geometry = Geometry::STANDARD;
+        let pawn = piece_definition("pawn").unwrap();
+        let mut board = geometry.empty_board();
+        board[12] = vec![_PAWN, _WHITE];
+        board[19] = vec![_PAWN, _BLACK];
+        board[21] = vec![_PAWN, _WHITE];
+        let mut targets = pawn.targets(geometry, &board, 12);
+        targets.sort();
+        assert_eq!(targets, vec![19, 20]);
+
+        board[52] = vec![_PAWN, _BLACK];
+        assert_eq!(pawn.targets(geometry, &board, 52), vec![44]);
+    }
+
+    #[test]
+    fn counts_capablanca_moves_from_the_start() {
+        let mut game = start(Fairy::capablanca());
+        assert_eq!(game.to_fen(), "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1");
+        assert_eq!(perft(&mut game, 1), 28);
+        assert_eq!(perft(&mut game, 2), 784);
+        assert_eq!(perft(&mut game, 3), 25228);
+    }
+
+    #[test]
+    fn counts_los_alamos_moves_from_the_start() {
+        let mut game = start(Fairy::los_alamos());
+        assert_eq!(perft(&mut game, 1), 10);
+        assert_eq!(perft(&mut game, 2), 100);
+        // the pawns only step one square
+        assert!(game.parse_move("a2a4").is_none());
+    }
+
+    #[test]
+    fn plays_capablanca_on_ten_files() {
+        let mut game = start(Fairy::capablanca());
+        play(&mut game, "h3 a6 Nj3 b6 Bh2 a5 Ci3 b5");
+        // the king castles to i1 and the rook goes to h1, next to it
+        assert!(game.parse_move("f1h1").is_none());
+        let castle = game.parse_san("O-O").unwrap();
+        assert_eq!(game.move_to_uci(castle), "f1i1");
+        play(&mut game, "f1i1");
+        assert_eq!(game.to_fen(), "rnabqkbcnr/2pppppppp/10/pp8/10/7PCN/PPPPPPPBPP/RNABQ2RK1 b kq - 1 5");
+        assert_eq!(game.undo().map(|mv| game.move_to_san(mv)), Some("O-O".to_string()));
+    }
+
+    #[test]
+    fn pawns_promote_on_the_sixth_rank_in_los_alamos() {
+        let mut game = start(Fairy::los_alamos());
+        game.load_fen_board("3k2/P5/6/6/6/3K2 w - - 0 1".to_string());
+        let promotions: Vec<String> = game.legal_moves().iter().filter(|mv| mv.from == 24).map(|mv| game.move_to_san(*mv)).collect();
+        assert_eq!(promotions, vec!["a6=Q+", "a6=R+", "a6=N"]);
+        play(&mut game, "a6=N");
+        assert_eq!(game.to_fen(), "N2k2/6/6/6/6/3K2 b - - 0 1");
+    }
+}
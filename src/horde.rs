@@ -0,0 +1,112 @@
+use crate::result::GameResult;
+use crate::variant::Variant;
+use crate::{Game, Move, ColorTurn, _NONE, _PAWN, _WHITE};
+
+/**
+ * Horde, white has 36 pawns and no king against the black army. White wins by
+ * checkmating the black king and black wins by taking every white piece. Pawns on
+ * the first rank may move one or two squares, the same as pawns on the second rank,
+ * but can not be taken en passante after moving two
+ */
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Horde;
+
+impl Variant for Horde {
+    fn name(&self) -> &str {
+        "Horde"
+    }
+
+    fn start_fen(&self) -> &str {
+        "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1"
+    }
+
+    fn generate_moves(&self, game: &Game) -> Vec<Move> {
+        let mut moves = game.standard_moves();
+        let color = game.color_to_move();
+        let (first_rank, dir): (u8, i8) = if color == _WHITE { (0, 8) } else { (7, -8) };
+        for _pos in first_rank * 8..first_rank * 8 + 8 {
+            let one = (_pos as i8 + dir) as u8;
+            let two = (one as i8 + dir) as u8;
+            if game.board[_pos as usize] == [_PAWN, color]
+                && game.board[one as usize][0] == _NONE && game.board[two as usize][0] == _NONE {
+                moves.push(Move::new(_pos, two));
+            }
+        }
+        moves
+    }
+
+    fn make_move(&self, game: &mut Game, mv: Move) {
+        let first_rank = if game.color_to_move() == _WHITE { 0 } else { 7 };
+        let from_first_rank = game.board[mv.from as usize][0] == _PAWN && mv.from / 8 == first_rank;
+        game.make_standard_move(mv);
+        if from_first_rank {
+            game.last_moved_pawn = vec![_NONE, 2];
+        }
+    }
+
+    fn outcome(&self, game: &Game) -> Option<GameResult> {
+        if game.board.iter().all(|square| square[0] == _NONE || square[1] != _WHITE) {
+            Some(GameResult::win_for(ColorTurn::Black))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Horde;
+    use crate::perft;
+    use crate::player::Termination;
+    use crate::result::GameResult;
+    use crate::Game;
+    use std::sync::Arc;
+
+    fn start() -> Game {
+        let mut game = Game::new();
+        game.set_variant(Arc::new(Horde));
+        game.init_board();
+        game
+    }
+
+    #[test]
+    fn counts_moves_from_the_start() {
+        let mut game = start();
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1");
+        assert_eq!(perft(&mut game, 1), 8);
+        assert_eq!(perft(&mut game, 2), 128);
+        assert_eq!(perft(&mut game, 3), 1274);
+    }
+
+    #[test]
+    fn first_rank_pawns_move_two_squares() {
+        let mut game = start();
+        game.load_fen_board("4k3/8/8/8/8/8/8/P7 w - - 0 1".to_string());
+        assert!(game.parse_move("a1a3").is_some());
+        assert!(game.parse_move("a1a2").is_some());
+
+        // the pawn passes a2 but can not be taken there
+        game.load_fen_board("4k3/8/8/8/8/1p6/8/P7 w - - 0 1".to_string());
+        game.play(game.parse_move("a1a3").unwrap());
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/Pp6/8/8 b - - 0 1");
+        assert!(game.parse_move("b3a2").is_none());
+        // from the second rank it can
+        game.load_fen_board("4k3/8/8/8/1p6/8/P7/8 w - - 0 1".to_string());
+        game.play(game.parse_move("a2a4").unwrap());
+        assert!(game.parse_move("b4a3").is_some());
+    }
+
+    #[test]
+    fn taking_every_white_piece_wins() {
+        let mut game = start();
+        game.load_fen_board("4k3/8/8/8/8/8/1q6/P7 b - - 0 1".to_string());
+        game.play(game.parse_san("Qxa1").unwrap());
+        assert_eq!(game.result(), GameResult::BlackWins);
+        assert_eq!(game.termination(), Some(Termination::Variant));
+
+        // white still wins by checkmate
+        game.load_fen_board("7k/5PP1/6PP/8/8/8/8/8 b - - 0 1".to_string());
+        assert_eq!(game.result(), GameResult::WhiteWins);
+        assert_eq!(game.termination(), Some(Termination::Checkmate));
+    }
+}
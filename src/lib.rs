@@ -1,9 +1,59 @@
 use std::fmt;
 use std::cmp;
-use std::io;
-use std::io::prelude::*;    
+use std::sync::Arc;
 use rand::Rng;
 
+pub mod ai;
+pub mod antichess;
+pub mod atomic;
+pub mod book;
+pub mod chess960;
+pub mod clock;
+pub mod crazyhouse;
+pub mod engine_match;
+pub mod eval;
+pub mod fairy;
+pub mod horde;
+#[cfg(feature = "server")]
+pub mod live;
+pub mod network;
+pub mod pgn;
+pub mod player;
+pub mod racing_kings;
+pub mod render;
+pub mod result;
+pub mod san;
+pub mod search;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod sprt;
+pub mod svg;
+pub mod tablebase;
+pub mod tournament;
+pub mod uci;
+pub mod variant;
+pub mod xboard;
+pub mod zobrist;
+
+pub use ai::Ai;
+pub use antichess::Antichess;
+pub use atomic::Atomic;
+pub use book::{BookSelection, OpeningBook};
+pub use chess960::{chess960_back_rank, chess960_fen};
+pub use clock::{format_time, Bonus, Clock, TimePeriod};
+pub use crazyhouse::Crazyhouse;
+pub use fairy::{piece_definition, Fairy, Geometry, PieceDefinition};
+pub use horde::Horde;
+pub use network::{RemoteError, RemotePlayer};
+pub use player::{play_game, FinishedGame, HumanPlayer, Outcome, Player, RandomPlayer, ScriptedPlayer, Termination};
+pub use racing_kings::RacingKings;
+pub use render::RenderOptions;
+pub use result::{GameEvent, GameResult};
+pub use search::{Analysis, AnalysisOptions, PvLine, Score};
+pub use svg::SvgOptions;
+pub use tablebase::{set_tablebase_path, Tablebase, TablebaseProbe, Wdl};
+pub use variant::{variant_from_name, KingOfTheHill, Standard, ThreeCheck, Variant};
+
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum GameState {
@@ -18,25 +68,148 @@ pub enum ColorTurn{
     Black
 }
 
-/** 
-     * 
-     *     
-     * 
+/**
+ * A single move on the board, from one square index to another.
+ *
+ * The squares are indexes into the board vector (a1 = 0, h8 = 63) and
+ * promotion is the piece a pawn turns into, or _NONE if the move is not a promotion
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: u8,
+    pub to: u8,
+    pub promotion: u8,
+}
+
+impl Move {
+    /// Creates a move that is not a promotion.
+    pub fn new(from: u8, to: u8) -> Move {
+        Move { from, to, promotion: _NONE }
+    }
+
+    /**
+     * Creates a move that drops a piece from the pocket on the square, as in Crazyhouse.
+     * A drop comes from DROP_SQUARE, which is on no board, and has the piece as promotion
+     */
+    pub(crate) fn drop_piece(piece: u8, to: u8) -> Move {
+        Move { from: DROP_SQUARE, to, promotion: piece }
+    }
+
+    /// Checks if the move drops a piece from the pocket.
+    pub fn is_drop(&self) -> bool {
+        self.from == DROP_SQUARE
+    }
+
+    /**
+     * Returns the move in long algebraic notation, for example "e2e4" or "e7e8q",
+     * and drops like "N@f3"
+     */
+    pub fn to_uci(&self) -> String {
+        if self.is_drop() {
+            return format!("{}@{}", Game::fen_symbol(self.promotion, _WHITE), square_name(self.to));
+        }
+        let mut uci = square_name(self.from);
+        uci.push_str(&square_name(self.to));
+        match self.promotion {
+            _KNIGHT => uci.push('n'),
+            _BISHOP => uci.push('b'),
+            _ROOK => uci.push('r'),
+            _QUEEN => uci.push('q'),
+            _KING => uci.push('k'),
+            _ => {}
+        }
+        uci
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_uci())
+    }
+}
+
+/**
+ * Changes a board index to the name of the square, for example 0 becomes "a1"
+ */
+pub fn square_name(_pos: u8) -> String {
+    let mut name = String::new();
+    name.push((b'a' + _pos % 8) as char);
+    name.push((b'1' + _pos / 8) as char);
+    name
+}
+
+/**
+ * Splits a square name off the front of the text, a file letter and the rank, which has
+ * two digits on boards with more than nine ranks. Returns the square and the rest
+ */
+fn split_square(text: &str) -> (&str, &str) {
+    let end = text.char_indices().skip(1).find(|(_, c)| !c.is_ascii_digit()).map_or(text.len(), |(at, _)| at);
+    text.split_at(end)
+}
+
+/**
+ * Everything that is needed to take back a move that has been made on the board
+ */
+#[derive(Clone, Debug)]
+struct UndoInfo {
+    mv: Move,
+    /* the squares the move changed with what was on them before, in the order they were changed */
+    squares: Vec<(u8, Vec<u8>)>,
+    last_moved_pawn: Vec<u8>,
+    castling: Vec<bool>,
+    halfmove_clock: u32,
+    checks: [u32; 2],
+    pockets: [[u8; 7]; 2],
+    promoted: u64,
+    state: GameState,
+    /* the clock before the move, only saved by play, boxed to keep the search's undo records small */
+    clock: Option<Box<Clock>>,
+}
+
+/*
+     *
+     *
+     *
     */
 
 /* IMPORTANT:
  * - Document well!
  * - Write well structured and clean code!
  */
+#[derive(Clone)]
 pub struct Game {
     /* save board, active colour, ... */
     board: Vec<Vec<u8>>,
+    /* the files and ranks of the board, which the variant decides */
+    geometry: Geometry,
     move_offset: Vec<i8>,
     move_offset_knight: Vec<i8>,
     move_to_edge: Vec<Vec<u8>>,
     state: GameState,
     turn: ColorTurn,
     last_moved_pawn: Vec<u8>,
+    /* castling rights in the order white king side, white queen side, black king side, black queen side */
+    castling: Vec<bool>,
+    /* the square of the rook each castling right castles with, in the same order */
+    castling_rooks: Vec<u8>,
+    /* castling is written as the king taking its own rook, as Chess960 needs */
+    chess960: bool,
+    /* the position loaded last has castling that can only be written that way */
+    chess960_castling: bool,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    /* the checks white and black have given, counted if the variant counts them */
+    checks: [u32; 2],
+    /* the pieces white and black have in hand by kind, and the squares with promoted pieces, for Crazyhouse */
+    pockets: [[u8; 7]; 2],
+    promoted: u64,
+    history: Vec<UndoInfo>,
+    /* the clock of the game if it is played with time, pressed by play */
+    clock: Option<Clock>,
+    /* resignations, draw offers and results set by the arbiter, with the number of moves played before */
+    events: Vec<(usize, GameEvent)>,
+    /* the rules the game is played by */
+    variant: Arc<dyn Variant>,
 
 }
 
@@ -47,12 +220,24 @@ const _KNIGHT:u8 = 3;
 const _ROOK:u8 = 4;
 const _QUEEN:u8 = 5;
 const _KING:u8 = 6;
+/* The fairy pieces, only variants with piece definitions have them. 8 is skipped, it is _WHITE */
+const _ARCHBISHOP:u8 = 7;
+const _CHANCELLOR:u8 = 9;
+const _AMAZON:u8 = 10;
+const _CAMEL:u8 = 11;
 const _WHITE:u8 = 8;
 const _BLACK:u8 = 16;
+/* The square a drop comes from, no board has a square this far so no other move comes from it */
+const DROP_SQUARE:u8 = 255;
 
 //const _MOVEOFFSET:Vec<i8> = vec![8, -8, -1, 1, 7 -7, 9, -9];
 
-const _STARTFEN:&str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR";
+
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
+}
 
 impl Game {
 
@@ -60,149 +245,179 @@ impl Game {
     /// Initialises a new board with pieces.
     pub fn new() -> Game {
         Game {
-            
+
             /* initialise board, set active colour to white, and sets up all of the move offsets */
             state: GameState::InProgress,
             turn: ColorTurn::White,
             board: Vec::with_capacity(64),
+            geometry: Geometry::STANDARD,
             move_offset: vec![8, -8, -1, 1, 7, -7, 9, -9],
             move_offset_knight: vec![-15, -17, -6, -10, 10, 6, 17, 15],
             move_to_edge: Vec::with_capacity(64),
             last_moved_pawn: Vec::with_capacity(2),
+            castling: vec![false; 4],
+            castling_rooks: vec![7, 0, 63, 56],
+            chess960: false,
+            chess960_castling: false,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            checks: [0, 0],
+            pockets: [[0; 7]; 2],
+            promoted: 0,
+            history: Vec::new(),
+            clock: None,
+            events: Vec::new(),
+            variant: Arc::new(Standard),
         }
 
     }
 
 
     /**
-     * A function that takes in a start value (the posistion the piece has before the move)
-     * if the piece can not move any further (since it is a pawn), the set_promotion function is called
+     * Pushes the move from _start to _target to possible_moves, if the pawn reaches
+     * the last rank one move for every promotion piece is pushed instead
      */
-    fn should_promote (&mut self, _start: u8) -> () {
-        if self.board[_start as usize][0] == _PAWN && self.board[_start as usize][1] == _WHITE {
-            if _start+8*2 > 64 {
-                self.set_promotion(_start);
-            }
-        }else if self.board[_start as usize][0] == _PAWN && self.board[_start as usize][1] == _BLACK{
-            if _start-8*2 < 0 {
-                self.set_promotion(_start);
+    fn push_pawn_move(possible_moves: &mut Vec<Move>, _start: u8, _target: u8) {
+        if _target / 8 == 0 || _target / 8 == 7 {
+            for promotion in [_QUEEN, _ROOK, _BISHOP, _KNIGHT] {
+                possible_moves.push(Move { from: _start, to: _target, promotion });
             }
+        } else {
+            possible_moves.push(Move::new(_start, _target));
         }
     }
 
     /**
      * A function that generates all of the possible moves that the pawns can move
-     * 
-     * Returns a vector with all of the possible moves with the structure [start, end]
+     *
+     * Returns a vector with all of the possible moves
      */
-    fn generate_short_moves(&self, _start: u8) -> Vec<Vec<u8>> {
-        let start_index = 0;
-        let end_index = 8;
+    fn generate_short_moves(&self, _start: u8) -> Vec<Move> {
+        let mut possible_moves:Vec<Move> = Vec::new();
+
+        let color = self.board[_start as usize][1];
+        let (dir, start_rank): (i8, u8) = if color == _WHITE { (8, 1) } else { (-8, 6) };
+        let one = _start as i8 + dir;
+        if !(0..64).contains(&one) {
+            return possible_moves;
+        }
+        let one = one as u8;
 
-        let mut possible_moves:Vec<Vec<u8>> = Vec::new(); 
+        if self.board[one as usize][0] == _NONE {
+            Game::push_pawn_move(&mut possible_moves, _start, one);
 
-        if self.board[_start as usize][0] == _PAWN && self.board[_start as usize][1] == _WHITE {
-            if _start+8 < 64 && self.board[(_start+8) as usize][0] == _NONE{
-                possible_moves.push(vec![_start, _start+8]);
-            }
-            if self.board[(_start+7) as usize][1] != self.board[_start as usize][1] && self.board[(_start+7) as usize][0] != _NONE {
-                possible_moves.push(vec![_start, _start+7]);
-            }
-            if self.board[(_start+9) as usize][1] != self.board[_start as usize][1] && self.board[(_start+9) as usize][0] != _NONE {
-                possible_moves.push(vec![_start, _start+9]);
-            }
-        }else if self.board[_start as usize][0] == _PAWN && self.board[_start as usize][1] == _BLACK {
-            if _start-8 > 0 && self.board[(_start-8) as usize][0] == _NONE{
-                possible_moves.push(vec![_start, _start-8]);
-            }
-            if self.board[(_start-7) as usize][1] != self.board[_start as usize][1] && self.board[(_start-7) as usize][0] != _NONE {
-                possible_moves.push(vec![_start, _start-7]);
-            }
-            if self.board[(_start-9) as usize][1] != self.board[_start as usize][1] && self.board[(_start-9) as usize][0] != _NONE {
-                possible_moves.push(vec![_start, _start-9]);
+            // if pawn is in startposistion it can move two spaces forward
+            let two = (one as i8 + dir) as u8;
+            if _start / 8 == start_rank && self.board[two as usize][0] == _NONE {
+                possible_moves.push(Move::new(_start, two));
             }
         }
 
-        // if pawn is in startposistion it can move two spaces forward
-        if self.board[_start as usize][1] == _WHITE && _start >= 8 && _start < 16 && self.board[(_start+2*8) as usize][0] == _NONE{
-            possible_moves.push(vec![_start, _start+2*8]);
-        }else if self.board[_start as usize][1] == _BLACK && _start >= 46 && _start < 55 && self.board[(_start-2*8) as usize][0] == _NONE{
-            possible_moves.push(vec![_start, _start-2*8]);
+        let file = (_start % 8) as i8;
+        for side in [-1, 1] {
+            if !(0..8).contains(&(file + side)) {
+                continue;
+            }
+            let _target = (one as i8 + side) as u8;
+            if self.board[_target as usize][0] != _NONE && self.board[_target as usize][1] != color {
+                Game::push_pawn_move(&mut possible_moves, _start, _target);
+            }
         }
 
         // add en passante moves
-        if self.last_moved_pawn[1] != 2 {
-            if self.last_moved_pawn[1] != self.board[_start as usize][1] {
-                if self.board[_start as usize][1] == _WHITE {
-                    possible_moves.push(vec![_start, self.last_moved_pawn[0]+8]);
-                }else{
-                    possible_moves.push(vec![_start, self.last_moved_pawn[0]-8]);
-                }
+        if self.last_moved_pawn[1] != 2 && self.last_moved_pawn[1] != color {
+            let pawn = self.last_moved_pawn[0];
+            if pawn / 8 == _start / 8 && ((pawn % 8) as i8 - file).abs() == 1 {
+                possible_moves.push(Move::new(_start, (pawn as i8 + dir) as u8));
             }
         }
-        return possible_moves;
+        possible_moves
     }
 
     /**
      * Generate all of the moves that a knight at the start posistion can move
-     * 
-     * Returns a vector with all of the possible moves with the structure [start, end]
+     *
+     * Returns a vector with all of the possible moves
      */
-    fn generate_knight_moves(&self, _start: u8) -> Vec<Vec<u8>> {
-        let mut possible_moves:Vec<Vec<u8>> = Vec::new(); 
-
-        for i in 0..8 {
-            if _start as i8 + self.move_offset_knight[i] >= 0 && _start as i8 + self.move_offset_knight[i] <= 64 {
-                let _target:u8 = (_start as i8 + self.move_offset_knight[i]) as u8;
+    fn generate_knight_moves(&self, _start: u8) -> Vec<Move> {
+        let mut possible_moves:Vec<Move> = Vec::new();
+
+        for offset in &self.move_offset_knight {
+            let _target = _start as i8 + offset;
+            // a knight never moves more than two files, anything else wrapped around the edge
+            if (0..64).contains(&_target) && ((_target % 8) - (_start % 8) as i8).abs() <= 2 {
+                let _target = _target as u8;
                 if self.board[_target as usize][1] != self.board[_start as usize][1] {
-                    possible_moves.push(vec![_start, (_target)]);
+                    possible_moves.push(Move::new(_start, _target));
                 }
             }
         }
-        return possible_moves;
+        possible_moves
 
     }
     /**
-     * Generates all the moves a king at a given posistion (_start) can move
-     * 
-     * Returns a vector with all of the possible moves with the structure [start, end]
+     * Generates all the moves a king at a given posistion (_start) can move,
+     * including castling
+     *
+     * Returns a vector with all of the possible moves
      */
-    fn generate_king_moves(&self, _start: u8) -> Vec<Vec<u8>> {
+    fn generate_king_moves(&self, _start: u8) -> Vec<Move> {
 
-        let mut possible_moves:Vec<Vec<u8>> = Vec::new(); 
+        let mut possible_moves:Vec<Move> = Vec::new();
 
         for _dir_index in 0..8{
-            for mut _n in 0..self.move_to_edge[_start as usize][_dir_index]{
-                let _target:u8 = (_start as i8 + (self.move_offset[_dir_index] * (_n+1) as i8)) as u8;
-                
-                if _target > 0 && _target < 65 {
-                    //If blocked by friendly
-                    if self.board[_target as usize][0] != _NONE && self.board[_target as usize][1] == self.board[_start as usize][1] {
-                        break;
-                    }
-
-                    possible_moves.push(vec![_start, _target]);
-
-                    if self.board[_target as usize][1] != self.board[_start as usize][1] {
-                        _n = self.move_to_edge[_start as usize][_dir_index];
-                    }
-                }else{
-                    _n = self.move_to_edge[_start as usize][_dir_index];
+            if self.move_to_edge[_start as usize][_dir_index] > 0 {
+                let _target:u8 = (_start as i8 + self.move_offset[_dir_index]) as u8;
+                //If not blocked by friendly
+                if self.board[_target as usize][1] != self.board[_start as usize][1] {
+                    possible_moves.push(Move::new(_start, _target));
                 }
-                break;
             }
         }
 
-        return possible_moves;
+        possible_moves.append(&mut self.castling_moves(_start));
+        possible_moves
 
     }
-    
+
+    /**
+     * Generates the castling moves of the king at _start. The king and the rook end on
+     * the same squares as in standard chess wherever they start, counted from the edges
+     * of the board, so on a board ten files wide the king castles to i1 or c1
+     */
+    pub(crate) fn castling_moves(&self, _start: u8) -> Vec<Move> {
+        let mut possible_moves:Vec<Move> = Vec::new();
+        let color = self.board[_start as usize][1];
+        let files = self.geometry.files;
+        let back = self.back_rank(color);
+        let rights = if color == _WHITE { 0 } else { 2 };
+        let opponent = Game::opponent(color);
+        if _start / files == back / files && !self.is_square_attacked(_start, opponent) {
+            for right in [rights, rights+1] {
+                let rook = self.castling_rooks[right];
+                let king_side = right == rights;
+                if !self.castling[right] || self.board[rook as usize] != [_ROOK, color] || (rook > _start) != king_side {
+                    continue;
+                }
+                let (king_to, rook_to) = if king_side { (back+files-2, back+files-3) } else { (back+2, back+3) };
+                let low = _start.min(king_to).min(rook).min(rook_to);
+                let high = _start.max(king_to).max(rook).max(rook_to);
+                let between: Vec<u8> = (low..=high).filter(|_pos| *_pos != _start && *_pos != rook).collect();
+                let attacked = (_start.min(king_to)..=_start.max(king_to)).any(|_pos| _pos != _start && self.is_square_attacked(_pos, opponent));
+                if self.is_empty(&between) && !attacked {
+                    possible_moves.push(Move::new(_start, if self.is_chess960() { rook } else { king_to }));
+                }
+            }
+        }
+        possible_moves
+    }
+
     /**
      * Generates all of the moves a sliding piece can move from a given posistion
-     * 
-     * Returns a vector with all of the possible moves with the structure [start, end]
+     *
+     * Returns a vector with all of the possible moves
      */
-    fn generate_long_moves(&self, _start: u8) -> Vec<Vec<u8>>{
+    fn generate_long_moves(&self, _start: u8) -> Vec<Move>{
         let mut start_index = 0;
         let mut end_index = 8;
         if self.board[_start as usize][0] == _BISHOP {
@@ -211,64 +426,779 @@ impl Game {
             end_index = 4;
         }
 
-        let mut possible_moves:Vec<Vec<u8>> = Vec::new(); 
+        let mut possible_moves:Vec<Move> = Vec::new();
         for _dir_index in start_index..end_index{
-            for mut _n in 0..self.move_to_edge[_start as usize][_dir_index]{
+            for _n in 0..self.move_to_edge[_start as usize][_dir_index]{
                 let _target:u8 = (_start as i8 + (self.move_offset[_dir_index] * (_n+1) as i8)) as u8;
-                if _target > 0 && _target < 65 {
-                    //If blocked by friendly
-                    if self.board[_target as usize][0] != _NONE && self.board[_target as usize][1] == self.board[_start as usize][1] {
-                        break;
-                    }
+                //If blocked by friendly
+                if self.board[_target as usize][1] == self.board[_start as usize][1] {
+                    break;
+                }
+
+                possible_moves.push(Move::new(_start, _target));
+
+                //If it is an enemy piece the piece can not move any further
+                if self.board[_target as usize][0] != _NONE {
+                    break;
+                }
+            }
+        }
+        possible_moves
+    }
+
+    /**
+     * Generates all of the moves the piece at _start can make, without checking
+     * if the move leaves the own king in check
+     */
+    fn generate_piece_moves(&self, _start: u8) -> Vec<Move> {
+        match self.board[_start as usize][0] {
+            _BISHOP | _ROOK | _QUEEN => self.generate_long_moves(_start),
+            _PAWN => self.generate_short_moves(_start),
+            _KING => self.generate_king_moves(_start),
+            _KNIGHT => self.generate_knight_moves(_start),
+            _ => Vec::new(),
+        }
+    }
+
+    /**
+     * Generates all of the moves for the player whose turn it is by the rules of the
+     * variant, the moves may break a rule such as leaving the own king in check
+     */
+    pub(crate) fn generate_moves(&self) -> Vec<Move> {
+        self.variant.generate_moves(self)
+    }
+
+    /**
+     * Generates all of the moves for the player whose turn it is by the rules of standard
+     * chess, the moves may leave the own king in check
+     */
+    pub fn standard_moves(&self) -> Vec<Move> {
+        let color = self.color_to_move();
+        let mut all_moves:Vec<Move> = Vec::new();
+        for _in in 0..self.board.len() as u8 {
+            if self.board[_in as usize][1] == color {
+                all_moves.append(&mut self.generate_piece_moves(_in));
+            }
+        }
+        all_moves
+    }
+
+    /**
+     * Generates all of the legal moves for the player whose turn it is,
+     * by making every move and taking back the ones the variant does not allow,
+     * in standard chess the ones that leave the king in check
+     */
+    pub(crate) fn generate_legal_moves(&mut self) -> Vec<Move> {
+        let mut legal_moves:Vec<Move> = Vec::new();
+        for mv in self.generate_moves() {
+            self.do_move(mv);
+            if self.is_position_legal() {
+                legal_moves.push(mv);
+            }
+            self.undo_move();
+        }
+        legal_moves
+    }
+
+    /// Returns all of the legal moves for the player whose turn it is.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.clone().generate_legal_moves()
+    }
 
-                    possible_moves.push(vec![_start, _target]);
+    /**
+     * Checks if a square is attacked by any piece of the given color
+     */
+    pub(crate) fn is_square_attacked(&self, _pos: u8, by_color: u8) -> bool {
+        if self.uses_piece_definitions() {
+            return self.is_square_attacked_by_definitions(_pos, by_color);
+        }
+        let file = (_pos % 8) as i8;
+
+        // pawns attack diagonally forward, so look diagonally backwards from the square
+        let pawn_dir: i8 = if by_color == _WHITE { -8 } else { 8 };
+        for side in [-1, 1] {
+            let _from = _pos as i8 + pawn_dir + side;
+            if (0..8).contains(&(file + side)) && (0..64).contains(&_from)
+                && self.board[_from as usize] == [_PAWN, by_color] {
+                return true;
+            }
+        }
 
-                    if self.board[_target as usize][1] != self.board[_start as usize][1] {
-                        _n = self.move_to_edge[_start as usize][_dir_index];
+        for offset in &self.move_offset_knight {
+            let _from = _pos as i8 + offset;
+            if (0..64).contains(&_from) && ((_from % 8) - file).abs() <= 2
+                && self.board[_from as usize] == [_KNIGHT, by_color] {
+                return true;
+            }
+        }
+
+        for _dir_index in 0..8 {
+            for _n in 0..self.move_to_edge[_pos as usize][_dir_index] {
+                let _from = (_pos as i8 + self.move_offset[_dir_index] * (_n+1) as i8) as usize;
+                let piece = self.board[_from][0];
+                if piece == _NONE {
+                    continue;
+                }
+                if self.board[_from][1] == by_color {
+                    let slider = if _dir_index < 4 { _ROOK } else { _BISHOP };
+                    if piece == _QUEEN || piece == slider || (piece == _KING && _n == 0) {
+                        return true;
                     }
-                }else{
-                    _n = self.move_to_edge[_start as usize][_dir_index];
                 }
+                break;
+            }
+        }
+        false
+    }
+
+    /// Puts the given piece on a square, _NONE with color 2 empties the square.
+    fn set_square(&mut self, _pos: usize, piece: u8, color: u8) {
+        self.board[_pos][0] = piece;
+        self.board[_pos][1] = color;
+    }
+
+    /// Checks if all of the given squares are empty.
+    fn is_empty(&self, squares: &[u8]) -> bool {
+        squares.iter().all(|_pos| self.board[*_pos as usize][0] == _NONE)
+    }
+
+    /// Returns the color of the other player.
+    fn opponent(color: u8) -> u8 {
+        if color == _WHITE { _BLACK } else { _WHITE }
+    }
+
+    /// Returns the piece color of the player whose turn it is.
+    pub(crate) fn color_to_move(&self) -> u8 {
+        if self.turn == ColorTurn::White { _WHITE } else { _BLACK }
+    }
+
+    /// Finds the king of the given color, if there is one on the board.
+    fn find_king(&self, color: u8) -> Option<u8> {
+        (0..self.board.len() as u8).find(|_pos| self.board[*_pos as usize] == [_KING, color])
+    }
+
+    /// The board the game is played on.
+    pub fn geometry(&self) -> Geometry {
+        self.geometry
+    }
+
+    /// The first square of the rank the pieces of the color start on, a1 or a8 on a standard board.
+    fn back_rank(&self, color: u8) -> u8 {
+        if color == _WHITE { 0 } else { (self.geometry.ranks - 1) * self.geometry.files }
+    }
+
+    /// The square the king starts on outside Chess960, the e file on a standard board.
+    fn king_square(&self, color: u8) -> u8 {
+        self.back_rank(color) + self.geometry.files / 2
+    }
+
+    /// The corners of the board in the order of the castling rights, where the rooks start.
+    fn corner_squares(&self) -> Vec<u8> {
+        let (white, black, files) = (self.back_rank(_WHITE), self.back_rank(_BLACK), self.geometry.files);
+        vec![white + files - 1, white, black + files - 1, black]
+    }
+
+    /**
+     * Checks if the last move made with do_move was allowed by the variant, in standard
+     * chess the player who moved may not leave their own king in check
+     */
+    pub(crate) fn is_position_legal(&self) -> bool {
+        self.variant.is_legal(self)
+    }
+
+    /**
+     * Checks if the player who just moved left their own king in check,
+     * which means that the last move was not legal in standard chess
+     */
+    pub fn is_own_king_left_in_check(&self) -> bool {
+        let color = Game::opponent(self.color_to_move());
+        match self.find_king(color) {
+            Some(king) => self.is_square_attacked(king, self.color_to_move()),
+            None => false,
+        }
+    }
+
+    /**
+     * Finds the squares of a castling move before it is made, where the king ends and where
+     * the rook starts and ends. Castling is either the king moving two squares or the king
+     * taking its own rook. Returns None if the move is not castling
+     */
+    pub(crate) fn castling_squares(&self, mv: Move) -> Option<(u8, u8, u8)> {
+        if mv.is_drop() {
+            return None;
+        }
+        let king = &self.board[mv.from as usize];
+        if king[0] != _KING {
+            return None;
+        }
+        let takes_rook = self.board[mv.to as usize] == [_ROOK, king[1]];
+        let king_side = mv.to > mv.from;
+        let files = self.geometry.files;
+        let back = mv.from / files * files;
+        let (king_to, rook_to) = if king_side { (back+files-2, back+files-3) } else { (back+2, back+3) };
+        // without taking the rook the king moves two squares or more along the rank, straight to where it ends
+        if !takes_rook && (mv.to != king_to || (mv.to as i16 - mv.from as i16).abs() < 2) {
+            return None;
+        }
+        let right = if king[1] == _WHITE { 0 } else { 2 } + if king_side { 0 } else { 1 };
+        let rook_from = if takes_rook { mv.to } else { self.castling_rooks[right] };
+        Some((king_to, rook_from, rook_to))
+    }
+
+    /**
+     * Makes a move on the board without checking if it is legal, and saves
+     * everything needed to take it back with undo_move. The variant makes the move
+     * and every square it changes is saved, so undo_move can put back what was there
+     */
+    pub(crate) fn do_move(&mut self, mv: Move) {
+        let color = self.color_to_move();
+        self.history.push(UndoInfo {
+            mv,
+            squares: Vec::new(),
+            last_moved_pawn: self.last_moved_pawn.clone(),
+            castling: self.castling.clone(),
+            halfmove_clock: self.halfmove_clock,
+            checks: self.checks,
+            pockets: self.pockets,
+            promoted: self.promoted,
+            state: self.state,
+            clock: None,
+        });
+
+        let variant = Arc::clone(&self.variant);
+        variant.make_move(self, mv);
+
+        if color == _BLACK {
+            self.fullmove_number += 1;
+        }
+        self.change_turn();
+        if variant.counts_checks() && self.is_king_check() {
+            self.checks[if color == _WHITE { 0 } else { 1 }] += 1;
+        }
+    }
+
+    /**
+     * Puts the piece on the square while a move is made, what was there before is saved
+     * so undo_move can put it back. The piece _NONE with the color 2 empties the square
+     */
+    pub(crate) fn put_piece(&mut self, _pos: u8, piece: u8, color: u8) {
+        let before = self.board[_pos as usize].clone();
+        if let Some(undo) = self.history.last_mut() {
+            undo.squares.push((_pos, before));
+        }
+        self.set_square(_pos as usize, piece, color);
+    }
+
+    /**
+     * Makes the move on the board by the rules of standard chess, with castling, en passante
+     * and promotions, and updates the castling rights, the en passante square and the
+     * halfmove clock. Returns the piece that was taken as [piece, color], [_NONE, 2] if none
+     */
+    pub(crate) fn make_standard_move(&mut self, mv: Move) -> Vec<u8> {
+        let _from = mv.from as usize;
+        let _to = mv.to as usize;
+        let piece = self.board[_from][0];
+        let color = self.board[_from][1];
+
+        // a pawn moving diagonally to an empty square is capturing en passante
+        let files = self.geometry.files;
+        let mut captured_pos = mv.to;
+        if piece == _PAWN && _from % files as usize != _to % files as usize && self.board[_to][0] == _NONE {
+            captured_pos = if color == _WHITE { mv.to - files } else { mv.to + files };
+        }
+
+        let mut captured = vec![_NONE, 2];
+        if let Some((king_to, rook_from, rook_to)) = self.castling_squares(mv) {
+            // castling also moves the rook, the king may start where the rook ends or the other way around
+            self.put_piece(mv.from, _NONE, 2);
+            self.put_piece(rook_from, _NONE, 2);
+            self.put_piece(king_to, _KING, color);
+            self.put_piece(rook_to, _ROOK, color);
+        } else {
+            captured = self.board[captured_pos as usize].clone();
+            self.put_piece(captured_pos, _NONE, 2);
+            self.put_piece(mv.from, _NONE, 2);
+            self.put_piece(mv.to, if mv.promotion != _NONE { mv.promotion } else { piece }, color);
+        }
+
+        for i in 0..4 {
+            let rook = self.castling_rooks[i] as usize;
+            let owner = if i < 2 { _WHITE } else { _BLACK };
+            if _from == rook || _to == rook || (piece == _KING && color == owner) {
+                self.castling[i] = false;
+            }
+        }
+
+        self.last_moved_pawn = vec![_NONE, 2];
+        if piece == _PAWN && _to.abs_diff(_from) == 2 * files as usize {
+            self.last_moved_pawn = vec![mv.to, color];
+        }
+
+        if piece == _PAWN || captured[0] != _NONE {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        captured
+    }
+
+    /**
+     * Takes back the last move made with do_move
+     *
+     * Returns false if there is no move to take back
+     */
+    pub(crate) fn undo_move(&mut self) -> bool {
+        let undo = match self.history.pop() {
+            Some(undo) => undo,
+            None => return false,
+        };
+        for (_pos, before) in undo.squares.iter().rev() {
+            self.set_square(*_pos as usize, before[0], before[1]);
+        }
+
+        self.turn = if self.turn == ColorTurn::White { ColorTurn::Black } else { ColorTurn::White };
+        if self.turn == ColorTurn::Black {
+            self.fullmove_number -= 1;
+        }
+        self.last_moved_pawn = undo.last_moved_pawn;
+        self.castling = undo.castling;
+        self.checks = undo.checks;
+        self.pockets = undo.pockets;
+        self.promoted = undo.promoted;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.state = undo.state;
+        true
+    }
+
+    /**
+     * Sets the game state from the position on the board, the game is over if the
+     * player whose turn it is has no legal moves or a rule of the variant ended it
+     */
+    fn update_state(&mut self) {
+        if self.variant.outcome(self).is_some() || self.generate_legal_moves().is_empty() {
+            self.state = GameState::GameOver;
+        } else if self.is_king_check() {
+            self.state = GameState::Check;
+        } else {
+            self.state = GameState::InProgress;
+        }
+    }
+
+    /**
+     * Makes the given move if it is legal and updates the game state
+     *
+     * Returns true if the move was made
+     */
+    pub fn play(&mut self, mv: Move) -> bool {
+        if self.state == GameState::GameOver || !self.generate_legal_moves().contains(&mv) {
+            return false;
+        }
+        // a move made after the flag fell does not count, the game is over
+        let before = self.clock.clone().map(Box::new);
+        if let Some(clock) = self.clock.as_mut() {
+            if !clock.press() {
+                self.state = GameState::GameOver;
+                return false;
+            }
+        }
+        self.do_move(mv);
+        if let Some(undo) = self.history.last_mut() {
+            undo.clock = before;
+        }
+        self.update_state();
+        true
+    }
 
+    /**
+     * Plays the game with the clock, or without time if it is None. The clock of the player
+     * to move starts now and play presses it after every move
+     */
+    pub fn set_clock(&mut self, clock: Option<Clock>) {
+        self.clock = clock;
+        let turn = self.turn;
+        if let Some(clock) = self.clock.as_mut() {
+            clock.start(turn);
+        }
+    }
+
+    pub fn clock(&self) -> Option<&Clock> {
+        self.clock.as_ref()
+    }
+
+    /// The player that has lost on time, if the game has a clock.
+    pub fn flagged(&self) -> Option<ColorTurn> {
+        self.clock.as_ref().and_then(Clock::flagged)
+    }
+
+    /**
+     * Checks if the player could still mate by some series of legal moves, so that running
+     * out of time against it loses. A lone king never can. A king with one knight can only
+     * when the other side has a piece to block its own king, and bishops that all stand on
+     * one color only when the other side has a piece that is not a bishop of that color.
+     * With pockets any piece left on the board or in hand could be taken and dropped, so
+     * only bare kings can not mate
+     */
+    pub fn can_mate(&self, color: ColorTurn) -> bool {
+        if self.variant.has_pockets() {
+            return self.pockets.iter().flatten().any(|count| *count > 0)
+                || self.board.iter().any(|square| square[0] != _NONE && square[0] != _KING);
+        }
+        let color = if color == ColorTurn::White { _WHITE } else { _BLACK };
+        let files = self.geometry.files as usize;
+        let mut knights = 0;
+        let mut bishop_squares = [0, 0];
+        let mut other_bishop_squares = [0, 0];
+        let mut other_pieces = 0;
+        for (_pos, square) in self.board.iter().enumerate() {
+            let shade = (_pos % files + _pos / files) % 2;
+            match (square[0], square[1] == color) {
+                (_NONE, _) | (_KING, _) => {}
+                (_KNIGHT, true) => knights += 1,
+                (_BISHOP, true) => bishop_squares[shade] += 1,
+                (_, true) => return true,
+                (_BISHOP, false) => other_bishop_squares[shade] += 1,
+                (_, false) => other_pieces += 1,
             }
         }
-        return possible_moves;
+        match (knights, bishop_squares) {
+            (0, [0, 0]) => false,
+            (1, [0, 0]) => other_pieces + other_bishop_squares[0] + other_bishop_squares[1] > 0,
+            (0, [_, 0]) => other_pieces + other_bishop_squares[1] > 0,
+            (0, [0, _]) => other_pieces + other_bishop_squares[0] > 0,
+            _ => true,
+        }
+    }
+
+    /**
+     * Takes back the last move made with play, returns the move or None if there
+     * are no moves to take back since the game started or the fen was loaded. The clock
+     * gets back the time of the move and runs for the player that made it again
+     */
+    pub fn undo(&mut self) -> Option<Move> {
+        let mv = self.history.last()?.mv;
+        let before = self.history.last_mut()?.clock.take();
+        if let (Some(clock), Some(before)) = (self.clock.as_mut(), before) {
+            clock.take_back(*before);
+        }
+        self.undo_move();
+        let played = self.history.len();
+        self.events.retain(|(ply, _)| *ply <= played);
+        Some(mv)
+    }
+
+    /**
+     * Finds the legal move written in long algebraic notation, for example "e2e4" or "e7e8q"
+     *
+     * A pawn move to the last rank without a promotion piece promotes to a queen
+     */
+    pub fn parse_move(&self, _in: &str) -> Option<Move> {
+        let _in = _in.trim();
+        if !_in.is_ascii() {
+            return None;
+        }
+        if let Some((piece, square)) = _in.split_once('@') {
+            let piece = match piece {
+                "" => _PAWN,
+                piece if piece.len() == 1 => Game::piece_from_symbol(piece.chars().next()?.to_ascii_lowercase()),
+                _ => return None,
+            };
+            let drop = Move::drop_piece(piece, self.geometry.parse_square(square)?);
+            return self.legal_moves().into_iter().find(|mv| *mv == drop);
+        }
+        let (from, rest) = split_square(_in);
+        let (to, promotion) = split_square(rest);
+        let _from = self.geometry.parse_square(from)?;
+        let _to = self.geometry.parse_square(to)?;
+        let promotion = match promotion.chars().next() {
+            Some(c) => match Game::piece_from_symbol(c.to_ascii_lowercase()) {
+                _NONE | _PAWN => return None,
+                piece => piece,
+            },
+            None => _NONE,
+        };
+        let legal_moves = self.legal_moves();
+        legal_moves.iter().copied()
+            .find(|mv| mv.from == _from && mv.to == _to && (mv.promotion == promotion || (promotion == _NONE && mv.promotion == _QUEEN)))
+    }
+
+    /**
+     * Writes the move in long algebraic notation like Move::to_uci, with the names the
+     * squares have on the board of the game, "j1" is a square on a board ten files wide
+     */
+    pub fn move_to_uci(&self, mv: Move) -> String {
+        if self.geometry == Geometry::STANDARD {
+            return mv.to_uci();
+        }
+        if mv.is_drop() {
+            return format!("{}@{}", Game::fen_symbol(mv.promotion, _WHITE), self.geometry.square_name(mv.to));
+        }
+        let mut uci = format!("{}{}", self.geometry.square_name(mv.from), self.geometry.square_name(mv.to));
+        if mv.promotion != _NONE {
+            uci.push(Game::fen_symbol(mv.promotion, _BLACK));
+        }
+        uci
     }
 
 
     /**
      * Takes a fen string (a specific way to structure a chess board)
      * and creates / initialises the board from the fen string
-     * 
-     * input
+     *
+     * input is either only the piece placement or a full fen string, the fields after
+     * the piece placement (turn, castling, en passante and the move counters) are optional.
+     * Without a castling field every king and rook still on their starting squares may castle
      */
-    pub fn load_fen_board(&mut self,fen_string: String ) -> (){
-        
+    pub fn load_fen_board(&mut self,fen_string: String ) {
+        if self.move_to_edge.is_empty() {
+            self.get_edge();
+        }
+        self.board = self.geometry.empty_board();
+        let files = self.geometry.files as usize;
+
+        let mut fields = fen_string.split_whitespace();
         let mut file:usize = 0;
-        let mut rank:usize = 7;
+        let mut rank:usize = self.geometry.ranks as usize - 1;
+
+        // Crazyhouse writes the pockets in brackets after the placement, "RNBQKBNR[Qp]", or as
+        // a ninth rank, "RNBQKBNR/Qp", and a promoted piece with a "~" after it
+        let placement = fields.next().unwrap_or("");
+        let (placement, pockets) = match placement.split_once('[') {
+            Some((placement, pockets)) => (placement, pockets.trim_end_matches(']')),
+            None => match placement.match_indices('/').nth(rank) {
+                Some((at, _)) => (&placement[..at], &placement[at+1..]),
+                None => (placement, ""),
+            },
+        };
+        self.pockets = [[0; 7]; 2];
+        for c in pockets.chars() {
+            let piece = Game::piece_from_symbol(c.to_ascii_lowercase());
+            if (_PAWN.._KING).contains(&piece) {
+                self.pockets[if c.is_uppercase() { 0 } else { 1 }][piece as usize] += 1;
+            }
+        }
+        self.promoted = 0;
 
-        for c in fen_string.chars(){
+        let mut chars = placement.chars().peekable();
+        while let Some(c) = chars.next() {
             if c == '/'{
                 file = 0;
-                rank -= 1;
-            }else{
-                if c.is_numeric() {
-                    file += c as usize;
-                }else{
-                    let mut piece_color = _BLACK;
-                    if c.is_uppercase() {
-                        piece_color = _WHITE; 
-                    }
+                rank = rank.saturating_sub(1);
+            }else if c == '~' {
+                if file > 0 && rank*files+file-1 < 64 {
+                    self.promoted |= 1 << (rank*files+file-1);
+                }
+            }else if let Some(mut empty) = c.to_digit(10) {
+                // boards wider than nine files have runs of ten or more empty squares
+                while let Some(digit) = chars.peek().and_then(|d| d.to_digit(10)) {
+                    empty = empty * 10 + digit;
+                    chars.next();
+                }
+                file += empty as usize;
+            }else if file < files {
+                let mut piece_color = _BLACK;
+                if c.is_uppercase() {
+                    piece_color = _WHITE;
+                }
+
+                let piece_type = self.piece_from_fen(c.to_ascii_lowercase());
+                if piece_type != _NONE {
+                    self.board[rank*files+file] = vec![piece_type, piece_color];
+                }
+                file += 1;
+            }
+        }
 
-                    let piece_type = Game::piece_from_symbol(c.to_ascii_lowercase());
-                    self.board[rank*8+file] = vec![piece_type, piece_color];
-                    file += 1;
+        self.turn = match fields.next() {
+            Some("b") => ColorTurn::Black,
+            _ => ColorTurn::White,
+        };
+
+        self.castling_rooks = self.corner_squares();
+        match fields.next() {
+            Some(castling) => {
+                self.castling = vec![false; 4];
+                for c in castling.chars() {
+                    self.add_castling_right(c);
                 }
             }
+            None => self.castling = (0..4).map(|i| {
+                let color = if i < 2 { _WHITE } else { _BLACK };
+                self.board[self.king_square(color) as usize] == [_KING, color]
+                    && self.board[self.castling_rooks[i] as usize] == [_ROOK, color]
+            }).collect(),
+        }
+        // castling from other squares than in standard chess can only be written the Chess960 way
+        self.chess960_castling = false;
+        for (i, corner) in self.corner_squares().into_iter().enumerate() {
+            let color = if i < 2 { _WHITE } else { _BLACK };
+            let back = self.back_rank(color) as usize;
+            let king = self.king_square(color) as usize;
+            let moved_king = (back..back+files).any(|_pos| _pos != king && self.board[_pos] == [_KING, color]);
+            if self.castling[i] && (self.castling_rooks[i] != corner || moved_king) {
+                self.chess960_castling = true;
+            }
+        }
+
+        // the en passante square is behind the pawn that just moved two squares
+        self.last_moved_pawn = vec![_NONE, 2];
+        let geometry = self.geometry;
+        if let Some(_pos) = fields.next().and_then(|field| geometry.parse_square(field)) {
+            if _pos / geometry.files == 2 {
+                self.last_moved_pawn = vec![_pos + geometry.files, _WHITE];
+            } else if _pos / geometry.files == geometry.ranks - 3 {
+                self.last_moved_pawn = vec![_pos - geometry.files, _BLACK];
+            }
         }
 
+        // the checks of Three-check are written "+2+1" for the checks given after the move
+        // counters, or "1+2" for the checks left before them
+        let (checks, mut counters): (Vec<&str>, Vec<&str>) = fields.partition(|field| field.contains('+'));
+        self.checks = [0, 0];
+        if let Some(field) = checks.first() {
+            let numbers: Vec<u32> = field.trim_start_matches('+').split('+').filter_map(|n| n.parse().ok()).collect();
+            if let [white, black] = numbers[..] {
+                self.checks = if field.starts_with('+') { [white, black] } else { [3u32.saturating_sub(white), 3u32.saturating_sub(black)] };
+            }
+        }
+        counters.reverse();
+        self.halfmove_clock = counters.pop().and_then(|s| s.parse().ok()).unwrap_or(0);
+        self.fullmove_number = counters.pop().and_then(|s| s.parse().ok()).unwrap_or(1);
+        self.history.clear();
+        self.events.clear();
+        self.update_state();
+
+    }
+
+    /**
+     * Reads one letter of the castling field of a fen string. "K" and "Q" castle with the
+     * outermost rook on that side of the king, as in X-FEN, and the files "A" to "H" name
+     * the rook, as in Shredder-FEN. Lower case letters are for black
+     */
+    fn add_castling_right(&mut self, c: char) {
+        let (color, rights): (u8, usize) = if c.is_ascii_uppercase() { (_WHITE, 0) } else { (_BLACK, 2) };
+        let (back, files) = (self.back_rank(color), self.geometry.files);
+        let king = match (back..back+files).find(|_pos| self.board[*_pos as usize] == [_KING, color]) {
+            Some(king) => king,
+            None => self.king_square(color),
+        };
+        let is_rook = |_pos: &u8| self.board[*_pos as usize] == [_ROOK, color];
+        let rook = match c.to_ascii_lowercase() {
+            'k' => (king+1..back+files).rev().find(is_rook).unwrap_or(back + files - 1),
+            'q' => (back..king).find(is_rook).unwrap_or(back),
+            file if file.is_ascii_lowercase() && (file as u8 - b'a') < files => back + (file as u8 - b'a'),
+            _ => return,
+        };
+        let right = if rook > king { rights } else { rights + 1 };
+        self.castling[right] = true;
+        self.castling_rooks[right] = rook;
+    }
+
+    /**
+     * Writes the castling field of a fen string, as X-FEN. A right is written with the file
+     * of the rook instead of "K" or "Q" if another rook is further out on the same side
+     */
+    fn castling_field(&self) -> String {
+        let mut field = String::new();
+        for i in 0..4 {
+            if !self.castling[i] {
+                continue;
+            }
+            let rook = self.castling_rooks[i];
+            let color = if i < 2 { _WHITE } else { _BLACK };
+            let back = self.back_rank(color);
+            let outer = if i % 2 == 0 { rook+1..back+self.geometry.files } else { back..rook };
+            let letter = if outer.into_iter().any(|_pos| self.board[_pos as usize] == [_ROOK, color]) {
+                (b'a' + rook % self.geometry.files) as char
+            } else if i % 2 == 0 { 'k' } else { 'q' };
+            field.push(if color == _WHITE { letter.to_ascii_uppercase() } else { letter });
+        }
+        if field.is_empty() { "-".to_string() } else { field }
+    }
+
+    /// The square the pawn that just moved two squares passed, where it can be taken en passante.
+    fn en_passant_square(&self) -> Option<u8> {
+        match self.last_moved_pawn.get(1) {
+            Some(&_WHITE) => Some(self.last_moved_pawn[0] - self.geometry.files),
+            Some(&_BLACK) => Some(self.last_moved_pawn[0] + self.geometry.files),
+            _ => None,
+        }
+    }
+
+    /**
+     * Writes the position as a full fen string, the opposite of load_fen_board
+     */
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        let files = self.geometry.files as usize;
+        for rank in (0..self.geometry.ranks as usize).rev() {
+            let mut empty = 0;
+            for file in 0..files {
+                let _pos = rank * files + file;
+                let square = &self.board[_pos];
+                if square[0] == _NONE {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    fen.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                fen.push(Game::fen_symbol(square[0], square[1]));
+                if _pos < 64 && self.promoted & 1 << _pos != 0 {
+                    fen.push('~');
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        if self.variant.has_pockets() {
+            fen.push('[');
+            for (side, color) in [_WHITE, _BLACK].iter().enumerate() {
+                for piece in [_QUEEN, _ROOK, _BISHOP, _KNIGHT, _PAWN] {
+                    for _ in 0..self.pockets[side][piece as usize] {
+                        fen.push(Game::fen_symbol(piece, *color));
+                    }
+                }
+            }
+            fen.push(']');
+        }
+        fen.push_str(if self.turn == ColorTurn::White { " w " } else { " b " });
+        fen.push_str(&self.castling_field());
+
+        fen.push(' ');
+        match self.en_passant_square() {
+            Some(_pos) => fen.push_str(&self.geometry.square_name(_pos)),
+            None => fen.push('-'),
+        }
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+        if self.variant.counts_checks() {
+            fen.push_str(&format!(" +{}+{}", self.checks[0], self.checks[1]));
+        }
+        fen
+    }
+
+
+    /// The letter of the piece in a fen string, upper case for white.
+    fn fen_symbol(piece: u8, color: u8) -> char {
+        let symbol = match piece {
+            _PAWN => 'p',
+            _KNIGHT => 'n',
+            _BISHOP => 'b',
+            _ROOK => 'r',
+            _QUEEN => 'q',
+            _ARCHBISHOP => 'a',
+            _CHANCELLOR => 'c',
+            _AMAZON => 'm',
+            _CAMEL => 'l',
+            _ => 'k',
+        };
+        if color == _WHITE { symbol.to_ascii_uppercase() } else { symbol }
     }
-    
 
     /**
      * takes a char (in this case p,n,b,r,q,k) representing each kind of piece
@@ -276,109 +1206,60 @@ impl Game {
      */
     fn piece_from_symbol(c:char) -> u8 {
         let mut _s = c.to_string();
-        _s = _s.chars().map(|_s| match _s {      
+        _s = _s.chars().map(|_s| match _s {
             'p' => _PAWN.to_string(),  //Game::_PAWN
-            'n' => _KNIGHT.to_string(), 
+            'n' => _KNIGHT.to_string(),
             'b' => _BISHOP.to_string(),
             'r' => _ROOK.to_string(),
             'q' => _QUEEN.to_string(),
             'k' => _KING.to_string(),
+            'a' => _ARCHBISHOP.to_string(),
+            'c' => _CHANCELLOR.to_string(),
+            'm' => _AMAZON.to_string(),
+            'l' => _CAMEL.to_string(),
             _ => _NONE.to_string()
         }).collect();
-        let piece:u8 = _s.parse::<u8>().unwrap(); //Gör om bokstäver till siffror som kan motsvara till brädet t.ex.
-
-        return piece;
+        //Gör om bokstäver till siffror som kan motsvara till brädet t.ex.
+        _s.parse::<u8>().unwrap()
     }
 
 
+    /// The piece of the letter if the variant has it, fairy pieces are only read where they can move.
+    fn piece_from_fen(&self, c: char) -> u8 {
+        match Game::piece_from_symbol(c) {
+            piece if piece > _KING && self.variant.piece_definition(piece).is_none() => _NONE,
+            piece => piece,
+        }
+    }
+
     /**
-     * A function that makes the black player move a random piece that can move
-     * to a random possible position 
+     * A function that makes the player whose turn it is move a random piece that can move
+     * to a random possible position
      */
-    pub fn make_ai_move(&mut self) -> (){
+    pub fn make_ai_move(&mut self) {
 
         println!("now ai is making move");
 
-        let mut all_moves:Vec<Vec<u8>> = Vec::new();
-        let mut good_moves:Vec<Vec<u8>> = Vec::new();
-        self.state = GameState::Check;
-        for _in in 0..64{
-            if self.is_king_check() == false {
-                if self.board[_in as usize][0] == _BISHOP || self.board[_in as usize][0] == _ROOK || self.board[_in as usize][0] == _QUEEN {
-                    all_moves.append(&mut self.generate_long_moves(_in));
-                } else if self.board[_in as usize][0] == _PAWN{
-                    all_moves.append(&mut self.generate_short_moves(_in));
-                } else if self.board[_in as usize][0] == _KING{
-                    all_moves.append(&mut self.generate_king_moves(_in));
-                } else if self.board[_in as usize][0] == _KNIGHT{
-                    all_moves.append(&mut self.generate_knight_moves(_in));
-                }
-            }
-        }
-
-        good_moves.append(&mut self.get_good_moves(&all_moves));
+        let all_moves:Vec<Move> = self.generate_legal_moves();
 
         println!("Have calculated all moves");
 
-        if self.is_king_check() == false {
-            self.state = GameState::InProgress;
-        }
-
-        let mut rng = rand::thread_rng();
-
-        if all_moves.len() == 0 {
-            
+        if all_moves.is_empty() {
             self.state = GameState::GameOver;
             return;
-
-        }else{
-            if good_moves.len() == 0 {
-                
-                let len:u8 = (all_moves.len()-1) as u8;
-
-
-                loop{            
-        
-                    let _rand: u8 = rng.gen_range(0..len);
-        
-                    
-        
-                    let _from:u8 = all_moves[_rand as usize][0];
-                    let _to:u8 = all_moves[_rand as usize][1];
-                    if all_moves.contains(&all_moves[_rand as usize]) && self.board[_from as usize][1] == _BLACK{
-                        self.board[_to as usize][0] = self.board[_from as usize][0];
-                        self.board[_to as usize][1] = self.board[_from as usize][1];
-                        self.board[_from as usize] = vec![_NONE, 2];
-            
-                        self.print_board();
-                        
-                        if self.is_king_check() == true{
-            
-                            println!("Kungen är i shack");
-                            println!("Kungen är i shack");
-                            self.state = GameState::Check;
-                        }
-            
-                        self.change_turn();
-                        break;
-                    }
-                } 
-            }
-
         }
 
-    }
-
-    fn get_good_moves(&mut self, possible_moves: &Vec<Vec<u8>>) -> Vec<Vec<u8>>{
-        let mut good_moves:Vec<Vec<u8>> = Vec::new();
-        for _i in 0..possible_moves.len(){
-            if self.board[possible_moves[_i as usize][0]][1] == _BLACK {
-                if self.board[possible_moves[_i as usize][1]][1] == _WHITE {
-                    good_moves.push(possible_moves[_i]);
-                }
-            }
+        let mut rng = rand::thread_rng();
+        let _rand = rng.gen_range(0..all_moves.len());
+        self.do_move(all_moves[_rand]);
+        self.update_state();
+
+        self.print_board();
+        if self.state == GameState::Check {
+            println!("Kungen är i shack");
+            println!("Kungen är i shack");
         }
-        return good_moves;
+
     }
 
 
@@ -386,26 +1267,21 @@ impl Game {
      * Initialises the board and calls the load_fen_board function
      * As well as calls the get_edge function
      */
-    pub fn init_board(&mut self) -> () {
-        for _i in 0..self.board.capacity(){
-            self.board.push(Vec::new());
-            
-            self.board[_i] = vec![_NONE, 2];
-        }
-        self.last_moved_pawn = vec![65, 2];  
-        Game::load_fen_board(self, _STARTFEN.to_string());
+    pub fn init_board(&mut self) {
+        self.board = self.geometry.empty_board();
+        self.last_moved_pawn = vec![65, 2];
         Game::get_edge(self);
-        
+        let fen = self.variant.start_fen().to_string();
+        Game::load_fen_board(self, fen);
+
     }
 
     /**
      * A function that calculates the length/distance from each square on the board
      * To the edge of the board and stores those values in the vector move_to_edge
      */
-    fn get_edge(&mut self) -> (){
-        for _i in 0..self.move_to_edge.capacity(){
-            self.move_to_edge.push(Vec::new());
-        }
+    fn get_edge(&mut self) {
+        self.move_to_edge = vec![Vec::new(); 64];
 
         for file in 0..8{
             for rank in 0..8 {
@@ -422,142 +1298,54 @@ impl Game {
 
 
     /**
-     * A function that first calculates all of the possible_moves depending on the start posistion
-     * then if the game is in progress if the move is legal, moves the piece to the _to posistion
-     * If the game is in check the move is only legal if the resulting move results in the game no longer 
-     * being in check
+     * A function that first calculates all of the legal moves
+     * then if the move from _from to _to is one of them, moves the piece to the _to posistion.
+     * A move is only legal if the own king is not in check after the move.
+     * Pawns that reach the last rank are promoted to queens, use set_promotion to pick another piece
      */
-    pub fn make_move(&mut self, _from: String, _to: String) -> () {
-        
-        //Get all the moves
-        let _from_pos:u8 = self.pos_to_int(_from);
-        let _to_pos:u8 = self.pos_to_int(_to);
-        let mut possible_moves:Vec<Vec<u8>> = Vec::new();
-        if self.board[_from_pos as usize][0] == _BISHOP || self.board[_from_pos as usize][0] == _ROOK || self.board[_from_pos as usize][0] == _QUEEN {
-            possible_moves.append(&mut Game::generate_long_moves(self, _from_pos));
-        } else if self.board[_from_pos as usize][0] == _PAWN{
-            possible_moves.append(&mut Game::generate_short_moves(self, _from_pos));
-            self.should_promote(_from_pos);
-        } else if self.board[_from_pos as usize][0] == _KING{
-            possible_moves.append(&mut Game::generate_king_moves(self, _from_pos));
-        } else if self.board[_from_pos as usize][0] == _KNIGHT{
-            possible_moves.append(&mut Game::generate_knight_moves(self, _from_pos));
-        }
-
-        //If the game is inprogess
-        if self.state == GameState::InProgress {
-            //If move is legal, move piece
-            if Game::is_move_legal(self, possible_moves, _from_pos, _to_pos) {
-
-
-                if self.board[_from_pos as usize][0] == _PAWN {
-                    let _enPassante:bool = self.should_enpassante(_from_pos, _to_pos);
-
-                    if  self.last_moved_pawn[1] != self.board[_from_pos as usize][1] && self.last_moved_pawn[1] != 2 {
-                        if self.board[_from_pos as usize][1] == _WHITE {
-                            if self.last_moved_pawn[0]+8 == _to_pos {
-                                self.board[self.last_moved_pawn[0] as usize] = vec![_NONE, 2];
-                            }
-                        }else{
-                            if self.last_moved_pawn[0]-8 == _to_pos {
-                                self.board[self.last_moved_pawn[0] as usize] = vec![_NONE, 2];
-                            }
-                        }
-                    }
-                }
-
-
-                self.board[_to_pos as usize][0] = self.board[_from_pos as usize][0];
-                self.board[_to_pos as usize][1] = self.board[_from_pos as usize][1];
-                self.board[_from_pos as usize] = vec![_NONE, 2];
-
-                self.print_board();
-                //If it results in check, change gamestate to check
-                if self.is_king_check() == true{
-
-                    println!("Kungen är i shack");
-                    println!("Kungen är i shack");
-                    self.state = GameState::Check;
-                }
-
-                self.change_turn();
+    pub fn make_move(&mut self, _from: String, _to: String) {
 
+        //Get all the moves
+        let (_from_pos, _to_pos) = match (self.geometry.parse_square(&_from), self.geometry.parse_square(&_to)) {
+            (Some(_from_pos), Some(_to_pos)) => (_from_pos, _to_pos),
+            _ => return,
+        };
+        let possible_moves:Vec<Move> = self.generate_legal_moves();
+
+        //If move is legal, move piece
+        if self.state != GameState::GameOver && Game::is_move_legal(self, &possible_moves, _from_pos, _to_pos) {
+            let mut mv = Move::new(_from_pos, _to_pos);
+            if self.board[_from_pos as usize][0] == _PAWN && (_to_pos / self.geometry.files == 0 || _to_pos / self.geometry.files == self.geometry.ranks - 1) {
+                mv.promotion = _QUEEN;
             }
-
-
-      
-        }else if self.state == GameState::Check{
-
-            if Game::is_move_legal(self, possible_moves, _from_pos, _to_pos){
-                let mut temp_vec:Vec<Vec<u8>> = Vec::new();
-                temp_vec.push(vec![65,65]);
-                temp_vec.push(vec![65,65]);
-                temp_vec[0][0] = self.board[_from_pos as usize][0];
-                temp_vec[0][1] = self.board[_from_pos as usize][1];
-                temp_vec[1][0] = self.board[_to_pos as usize][0];
-                temp_vec[1][1] = self.board[_to_pos as usize][1];
-
-                self.board[_to_pos as usize][0] = self.board[_from_pos as usize][0];
-                self.board[_to_pos as usize][1] = self.board[_from_pos as usize][1];
-                self.board[_from_pos as usize] = vec![_NONE, 2];
-
-                if self.is_king_check() == true{
-                    self.board[_to_pos as usize][0] = temp_vec[1][0];
-                    self.board[_to_pos as usize][1] = temp_vec[1][1];
-                    self.board[_from_pos as usize][0] = temp_vec[0][0];
-                    self.board[_from_pos as usize][1] = temp_vec[0][1];
-                }else{
-                    
-                    self.change_turn();
-                    self.print_board();
-                    self.state = GameState::InProgress;
-                }
-            }else {
+            self.do_move(mv);
+            self.update_state();
+
+            self.print_board();
+            //If it results in check, tell the players
+            if self.state == GameState::Check {
+                println!("Kungen är i shack");
+                println!("Kungen är i shack");
             }
         }
         //return self;
     }
 
-    pub fn get_board_vec(&mut self) -> Vec<Vec<u8>> {
-        let mut return_vector: Vec<Vec<u8>> = Vec:with_capacity(64);
-        for _i in 0..64 {
-            return_vector.push(vec![0,2]);
-        }
-        for _i in 0..64 {
-            return_vector[_i][0] = self.board[_i][0];
-            return_vector[_i][1] = self.board[_i][1];
-        }
-        return return_vector;
+    pub fn get_board_vec(&self) -> Vec<Vec<u8>> {
+        self.board.clone()
     }
 
-    fn should_enpassante(&mut self, _from:u8, _to:u8) -> bool{
-        if self.board[_from as usize][1] == _WHITE {
-            if _from+8*2 == _to {
-                self.last_moved_pawn[0] = _to;
-                self.last_moved_pawn[1] = _WHITE;
-                return true;
-            }
-        }else{
-            if _from-8*2 == _to {
-                self.last_moved_pawn[0] = _to;
-                self.last_moved_pawn[1] = _BLACK;
-                return true;
-            }
-        }
-        return false;
-    }
 
-    
     /**
      * A function that changes a string of the posistion for example "a4"
      * To the corresponding index of the board vector
      */
-    pub fn pos_to_int(&mut self, _in: String) -> u8{
+    pub fn pos_to_int(&self, _in: String) -> u8{
 
-        let mut _c:String = _in.chars().nth(0).unwrap().to_string();
+        let mut _c:String = _in.chars().next().unwrap().to_string();
         _c = _c.chars().map(|_c| match _c {      // t.ex. a blir 0
-                'a' => "1", 
-                'b' => "2", 
+                'a' => "1",
+                'b' => "2",
                 'c' => "3",
                 'd' => "4",
                 'e' => "5",
@@ -570,8 +1358,8 @@ impl Game {
         let mut _s:String  = _in.chars().nth(1).unwrap().to_string();
 
         _s = _s.chars().map(|_s| match _s {      // t.ex. a blir 0
-            '1' => "0", 
-            '2' => "8", 
+            '1' => "0",
+            '2' => "8",
             '3' => "16",
             '4' => "24",
             '5' => "32",
@@ -582,78 +1370,42 @@ impl Game {
         }).collect();
         let _pos1:u8 = _c.parse::<u8>().unwrap();
         let _pos2:u8 = _s.parse::<u8>().unwrap();
-        return _pos1+_pos2-1;
+        _pos1+_pos2-1
     }
 
 
     /**
-     * A function that checks if the king is in check
-     * 
-     * The function does this my calculating all of the possible moves on the board
-     * if there is a move between one piece and the opponents king, then the game is in check
+     * A function that checks if the king of the player whose turn it is is in check
+     *
+     * The function does this by checking if any of the opponents pieces
+     * attacks the square the king is standing on
      */
     fn is_king_check(&self) -> bool {
+        self.variant.is_check(self)
+    }
 
-        let mut all_moves:Vec<Vec<u8>> = Vec::new();
-        let mut opponent_king:u8 = 0;
-        for _in in 0..64{
-            if self.board[_in as usize][0] == _BISHOP || self.board[_in as usize][0] == _ROOK || self.board[_in as usize][0] == _QUEEN {
-                all_moves.append(&mut self.generate_long_moves(_in));
-            } else if self.board[_in as usize][0] == _PAWN{
-                all_moves.append(&mut self.generate_short_moves(_in));
-            } else if self.board[_in as usize][0] == _KING{
-                all_moves.append(&mut self.generate_king_moves(_in));
-            } else if self.board[_in as usize][0] == _KNIGHT{
-                all_moves.append(&mut self.generate_knight_moves(_in));
-            }
-            
-            if self.state == GameState::InProgress {
-                if self.board[_in as usize][0] == _KING && self.board[_in as usize][1] == _BLACK && self.turn == ColorTurn::White{
-                    opponent_king = _in;
-                }else if self.board[_in as usize][0] == _KING && self.board[_in as usize][1] == _WHITE && self.turn == ColorTurn::Black{
-                    opponent_king = _in;
-                }
-            } else{
-                if self.board[_in as usize][0] == _KING && self.board[_in as usize][1] == _BLACK && self.turn == ColorTurn::Black{
-                    opponent_king = _in;
-                }else if self.board[_in as usize][0] == _KING && self.board[_in as usize][1] == _WHITE && self.turn == ColorTurn::White{
-                    opponent_king = _in;
-                }
-            }
-
-
-        }
-
-        let mut is_check:bool = false;
-
-
-        for _n in 0..64{
-            let wanted_move:Vec<u8> = vec![_n, opponent_king];
-            if all_moves.contains(&wanted_move) && (self.board[_n as usize][1] != self.board[opponent_king as usize][1]) {
-                is_check = true;
-            }
+    /// Checks if the king of the player whose turn it is is attacked, check in standard chess.
+    pub fn is_king_attacked(&self) -> bool {
+        let color = self.color_to_move();
+        match self.find_king(color) {
+            Some(king) => self.is_square_attacked(king, Game::opponent(color)),
+            None => false,
         }
-
-        return is_check;
     }
 
 
     /**
      * Check if move is legal
      */
-    pub fn is_move_legal(&self, possible_moves: Vec<Vec<u8>>, _from:u8, _to:u8) -> bool{
-        let wanted_move:Vec<u8> = vec![_from, _to];
-        if possible_moves.contains(&wanted_move) && ((self.board[_from as usize][1] == _WHITE && self.turn == ColorTurn::White) || (self.board[_from as usize][1] == _BLACK && self.turn == ColorTurn::Black)) {
-            return true;
-        }else{
-            return false;
-        }
+    pub fn is_move_legal(&self, possible_moves: &[Move], _from:u8, _to:u8) -> bool{
+        possible_moves.iter().any(|mv| mv.from == _from && mv.to == _to)
+            && self.board[_from as usize][1] == self.color_to_move()
     }
 
     /**
      * Promotes a pawn to a piecec of the players choice
      */
-    pub fn set_promotion(&mut self, _piece_pos: u8, promotion_piece:String) -> () {
+    pub fn set_promotion(&mut self, _piece_pos: u8, mut promotion_piece:String) {
         println!("Promote your pawn");
         println!("Print Q for queen");
         println!("Print K for king");
@@ -669,8 +1421,8 @@ impl Game {
         println!("Promotion piece = {}", promotion_piece);
 
         promotion_piece = promotion_piece.chars().map(|promotion_piece| match promotion_piece {      // t.ex. a blir 0
-            'q' => _QUEEN.to_string(), 
-            'k' => _KING.to_string(), 
+            'q' => _QUEEN.to_string(),
+            'k' => _KING.to_string(),
             'r' => _ROOK.to_string(),
             'b' => _BISHOP.to_string(),
             _ => _QUEEN.to_string()
@@ -683,7 +1435,7 @@ impl Game {
     /**
      * Changes the turn
      */
-    pub fn change_turn(&mut self) -> (){
+    pub fn change_turn(&mut self) {
         if self.turn == ColorTurn::White {
             if self.last_moved_pawn[1] == _BLACK {
                 self.last_moved_pawn = vec![_NONE, 2];
@@ -705,57 +1457,24 @@ impl Game {
     pub fn get_game_turn(&self) -> ColorTurn{
         self.turn
     }
-    
-    /// If a piece is standing on the given tile, return all possible 
-    /// new positions of that piece. Don't forget to the rules for check. 
-    /// 
+
+    /// If a piece is standing on the given tile, return all possible
+    /// new positions of that piece. Don't forget to the rules for check.
+    ///
     /// (optional) Don't forget to include en passent and castling.
-    /// 
-    /// Not used at the moment since all of it is calculated in make_move and other functions
     pub fn get_possible_moves(&self, _postion: String) -> Option<Vec<String>> {
-        None
-    }
-
-    /**
-     * Gets a piece value and converts it to the corresponding chess piece char
-     * For example
-     * Pawn is p and so on.
-     */
-    fn piece_to_char(_piece: u8, _color: u8) -> String {
-        let mut return_string = " ".to_string();
-        match _piece {
-            _NONE => return_string.push_str(" * "),
-            _PAWN => return_string.push_str("P"),
-            _KNIGHT => return_string.push_str("Kn"),
-            _BISHOP => return_string.push_str("B"),
-            _ROOK => return_string.push_str("R"),
-            _QUEEN => return_string.push_str("Q"),
-            _KING => return_string.push_str("K"),
-            _ => return_string.push_str(" * ")
-        }
-        if _color == _WHITE {
-            return_string.push_str("w ");
-        }else if _color == _BLACK {
-            return_string.push_str("b ");
-        }
-        return return_string;
-    }
-    /// Prints the board in the console
-    pub fn print_board(&self) -> (){
-        let mut print_board:String = String::new();
-        print_board += "   a   b   c   d   e   f   g   h";
-        let mut num:u8 = 8;
-        for n in 0..self.board.len(){
-            if n % 8 == 0 {
-                print_board += "\n";
-                print_board += &((n/8+1).to_string());
-                num -= 1;
-            }
-            //println!("{}", print_board);
-            print_board += &Game::piece_to_char(self.board[n][0], self.board[n][1]);
-        }
-        print!("\x1B[2J");
-        println!("{}", print_board);
+        let _pos = self.geometry.parse_square(&_postion)?;
+        let mut positions:Vec<String> = Vec::new();
+        for mv in self.legal_moves() {
+            let name = self.geometry.square_name(mv.to);
+            if mv.from == _pos && !positions.contains(&name) {
+                positions.push(name);
+            }
+        }
+        if positions.is_empty() {
+            return None;
+        }
+        Some(positions)
     }
 }
 
@@ -772,11 +1491,52 @@ impl Game {
 /// | P  P  P  P  P  P  P  P |
 /// | R  Kn B  K  Q  B  Kn R |
 /// |:----------------------:|
+/// 
+/// The board is seen from Black's side, rank 1 at the top and the h-file on the left, and
+/// both colors are written in upper case. The fen in the Debug output tells them apart.
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let files = self.geometry.files as usize;
+        let border = format!("|:{}:|", "-".repeat(3 * files - 2));
+        writeln!(f, "{}", border)?;
+        // a game that has not been set up has no squares yet
+        for rank in 0..self.board.len() / files {
+            let tokens: Vec<String> = self.board[rank * files..rank * files + files].iter().rev().map(|square| {
+                let token = match square[0] {
+                    _NONE => "*".to_string(),
+                    _KNIGHT => "Kn".to_string(),
+                    piece => Game::fen_symbol(piece, _WHITE).to_string(),
+                };
+                format!("{:<2}", token)
+            }).collect();
+            // only the last square of the rank is not padded
+            writeln!(f, "| {} |", tokens.join(" ").trim_end())?;
+        }
+        write!(f, "{}", border)
+    }
+}
+
+/// Writes the board followed by the rest of the position, which the fields alone do not show well.
 impl fmt::Debug for Game {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        /* build board representation string */
-        
-        write!(f, "")
+        writeln!(f, "{}", self)?;
+        if self.board.is_empty() {
+            return f.debug_struct("Game").field("variant", &self.variant.name()).field("state", &self.state).finish();
+        }
+        let mut debug = f.debug_struct("Game");
+        debug.field("variant", &self.variant.name())
+            .field("turn", &self.turn)
+            .field("castling", &self.castling_field())
+            .field("en_passant", &self.en_passant_square().map(|_pos| self.geometry.square_name(_pos)))
+            .field("halfmove_clock", &self.halfmove_clock)
+            .field("fullmove_number", &self.fullmove_number)
+            .field("clock", &self.clock)
+            .field("state", &self.state);
+        // the Polyglot hash only knows the standard board
+        if self.geometry == Geometry::STANDARD {
+            debug.field("hash", &format_args!("{:#018x}", self.polyglot_hash()));
+        }
+        debug.field("fen", &self.to_fen()).finish()
     }
 }
 
@@ -784,10 +1544,27 @@ impl fmt::Debug for Game {
 // ######### TESTS ##########
 // --------------------------
 
+/** Counts the legal move sequences of the given length, for checking move generation */
+#[cfg(test)]
+pub(crate) fn perft(game: &mut Game, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut nodes = 0;
+    for mv in game.generate_legal_moves() {
+        game.do_move(mv);
+        nodes += perft(game, depth - 1);
+        game.undo_move();
+    }
+    nodes
+}
+
 #[cfg(test)]
 mod tests {
+    use super::perft;
     use super::Game;
     use super::GameState;
+    use super::ColorTurn;
 
     // check test framework
     #[test]
@@ -795,10 +1572,34 @@ mod tests {
         assert_eq!(2 + 2, 4);
     }
 
+    #[test]
+    fn shows_the_board_and_the_position() {
+        let mut game = Game::new();
+        assert!(format!("{:?}", game).contains("state: InProgress"));
+        game.init_board();
+        game.play(game.parse_move("e2e4").unwrap());
+
+        let board = game.to_string();
+        let lines: Vec<&str> = board.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "|:----------------------:|");
+        assert_eq!(lines[1], "| R  Kn B  K  Q  B  Kn R |");
+        assert_eq!(lines[4], "| *  *  *  P  *  *  *  * |");
+        assert_eq!(lines[8], "| R  Kn B  K  Q  B  Kn R |");
+
+        let debug = format!("{:?}", game);
+        assert!(debug.starts_with(&board));
+        assert!(debug.contains("turn: Black"));
+        assert!(debug.contains("castling: \"KQkq\""));
+        assert!(debug.contains("en_passant: Some(\"e3\")"));
+        assert!(debug.contains("hash: 0x823c9b50fd114196"));
+    }
+
     
     //
     // check that game state is in progress after initialisation
     #[test]
+    #[allow(unused_mut)]
     fn game_in_progress_after_init() {
 
         let mut game = Game::new();
@@ -895,5 +1696,30 @@ mod tests {
         assert_eq!(game.board[26][0], 0);
     }
 
+    #[test]
+    fn does_move_generation_work() {
+        let mut game = Game::new();
+        game.init_board();
+        assert_eq!(perft(&mut game, 3), 8902);
+
+        // castling, en passante and promotions
+        game.load_fen_board("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string());
+        assert_eq!(perft(&mut game, 2), 2039);
+    }
+
+    #[test]
+    fn does_fen_and_undo_work() {
+        let mut game = Game::new();
+        game.init_board();
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+
+        game.play(game.parse_move("e2e4").unwrap());
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+
+        assert_eq!(game.undo().map(|mv| mv.to_uci()), Some("e2e4".to_string()));
+        assert_eq!(game.undo(), None);
+        assert_eq!(game.get_game_turn(), ColorTurn::White);
+    }
+
 
 }
\ No newline at end of file
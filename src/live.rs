@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde_json::{json, Value};
+use tungstenite::handshake::server::{Request, Response};
+use tungstenite::{Message, WebSocket};
+
+use crate::server::ServerGame;
+use crate::ColorTurn;
+
+/* How often a connection looks for messages to send while it waits for the client */
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/* How often the server pings every client, and how long a silent client is kept */
+const DEFAULT_HEARTBEAT: Duration = Duration::from_secs(10);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What a connection has joined a game as.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Role {
+    Player(ColorTurn),
+    Spectator,
+}
+
+/* What the game sends to a connection */
+enum Outgoing {
+    Text(String),
+    Close,
+}
+
+struct Client {
+    id: u64,
+    role: Role,
+    sender: Sender<Outgoing>,
+}
+
+/**
+ * One live game, the token of a seat is given to the first player who takes it and is
+ * needed to take the seat again after reconnecting
+ */
+struct Session {
+    game: ServerGame,
+    tokens: [Option<String>; 2],
+    clients: Vec<Client>,
+}
+
+impl Session {
+
+    /// The game together with who is connected, last_move is the move that was just played.
+    fn state(&self, id: &str, last_move: Option<&str>) -> String {
+        let mut state = self.game.to_json();
+        let connected = |color: ColorTurn| self.clients.iter().any(|client| client.role == Role::Player(color));
+        state["type"] = json!("state");
+        state["game"] = json!(id);
+        state["last_move"] = json!(last_move);
+        state["players"] = json!({ "white": connected(ColorTurn::White), "black": connected(ColorTurn::Black) });
+        state["spectators"] = json!(self.clients.iter().filter(|client| client.role == Role::Spectator).count());
+        state.to_string()
+    }
+
+    fn broadcast(&self, text: &str) {
+        for client in &self.clients {
+            let _ = client.sender.send(Outgoing::Text(text.to_string()));
+        }
+    }
+}
+
+fn seat(color: ColorTurn) -> usize {
+    if color == ColorTurn::White { 0 } else { 1 }
+}
+
+fn error(message: &str) -> String {
+    json!({ "type": "error", "message": message }).to_string()
+}
+
+/**
+ * Live games over WebSocket, where two players and any number of spectators are
+ * connected to the same game and every move is sent to all of them.
+ *
+ * A client connects to ws://host/live/{game} and sends JSON messages:
+ *
+ *  {"type": "join", "role": "white" | "black" | "spectator", "token": "...", "fen": "..."}
+ *  {"type": "move", "move": "e2e4"}
+ *  {"type": "resign"}
+ *  {"type": "ping"}
+ *
+ * The game is started from fen, or the start position, by the first join. Players get a
+ * token in the "joined" answer and join again with it after reconnecting, which also sends
+ * them the whole game again. Every change is sent to everyone as a "state" message, errors
+ * only to the client that caused them. The server pings every client and drops clients that
+ * have been silent for too long
+ */
+pub struct LiveServer {
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+    heartbeat: Duration,
+    timeout: Duration,
+}
+
+impl Default for LiveServer {
+    fn default() -> LiveServer {
+        LiveServer::new(DEFAULT_HEARTBEAT, DEFAULT_TIMEOUT)
+    }
+}
+
+impl LiveServer {
+    pub fn new(heartbeat: Duration, timeout: Duration) -> LiveServer {
+        LiveServer {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            heartbeat,
+            timeout,
+        }
+    }
+
+    /**
+     * Starts accepting WebSocket connections on the address, every connection gets its own
+     * thread. Returns the address that is listened on and the thread that accepts connections
+     */
+    pub fn listen(self, addr: &str) -> io::Result<(SocketAddr, thread::JoinHandle<()>)> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let this = Arc::new(self);
+        let next_id = Arc::new(AtomicU64::new(1));
+
+        let handle = thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let this = this.clone();
+                let id = next_id.fetch_add(1, Ordering::Relaxed);
+                thread::spawn(move || this.connection(stream, id));
+            }
+        });
+        Ok((local_addr, handle))
+    }
+
+    /**
+     * Runs one connection, reading messages from the client and sending it what its game
+     * sends, until the client closes or goes silent
+     */
+    #[allow(clippy::result_large_err)]
+    fn connection(&self, stream: TcpStream, client_id: u64) {
+        let mut path = String::new();
+        let mut socket = match tungstenite::accept_hdr(stream, |request: &Request, response: Response| {
+            path = request.uri().path().to_string();
+            Ok(response)
+        }) {
+            Ok(socket) => socket,
+            Err(_) => return,
+        };
+        let game_id = match path.strip_prefix("/live/") {
+            Some(id) if !id.is_empty() && !id.contains('/') => id.to_string(),
+            _ => {
+                let _ = socket.send(Message::Text(error("connect to /live/{game}")));
+                let _ = socket.close(None);
+                return;
+            }
+        };
+        if socket.get_mut().set_read_timeout(Some(POLL_INTERVAL)).is_err() {
+            return;
+        }
+
+        let (sender, inbox) = mpsc::channel();
+        let mut role: Option<Role> = None;
+        self.run_connection(&mut socket, &game_id, client_id, &sender, &inbox, &mut role);
+
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(session) = sessions.get_mut(&game_id) {
+            session.clients.retain(|client| client.id != client_id);
+            if role.is_some() {
+                session.broadcast(&session.state(&game_id, None));
+            }
+        }
+    }
+
+    fn run_connection(&self, socket: &mut WebSocket<TcpStream>, game_id: &str, client_id: u64,
+                      sender: &Sender<Outgoing>, inbox: &Receiver<Outgoing>, role: &mut Option<Role>) {
+        let mut last_heard = Instant::now();
+        let mut last_ping = Instant::now();
+
+        loop {
+            match socket.read() {
+                Ok(Message::Text(text)) => {
+                    last_heard = Instant::now();
+                    let reply = self.message(&text, game_id, client_id, sender, role);
+                    if let Some(reply) = reply {
+                        let _ = sender.send(Outgoing::Text(reply));
+                    }
+                }
+                Ok(Message::Close(_)) => return,
+                Ok(_) => last_heard = Instant::now(),
+                Err(tungstenite::Error::Io(err))
+                    if err.kind() == io::ErrorKind::WouldBlock || err.kind() == io::ErrorKind::TimedOut => {}
+                Err(_) => return,
+            }
+
+            while let Ok(outgoing) = inbox.try_recv() {
+                match outgoing {
+                    Outgoing::Text(text) => {
+                        if socket.send(Message::Text(text)).is_err() {
+                            return;
+                        }
+                    }
+                    Outgoing::Close => {
+                        let _ = socket.close(None);
+                        return;
+                    }
+                }
+            }
+
+            if last_ping.elapsed() >= self.heartbeat {
+                last_ping = Instant::now();
+                if socket.send(Message::Ping(Vec::new())).is_err() {
+                    return;
+                }
+            }
+            if last_heard.elapsed() >= self.timeout {
+                let _ = socket.close(None);
+                return;
+            }
+        }
+    }
+
+    /**
+     * Handles one message from a client, returns the answer that only goes to that client
+     */
+    fn message(&self, text: &str, game_id: &str, client_id: u64, sender: &Sender<Outgoing>, role: &mut Option<Role>) -> Option<String> {
+        let message: Value = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(_) => return Some(error("the message is not JSON")),
+        };
+        let field = |name: &str| message.get(name).and_then(Value::as_str);
+        let mut sessions = self.sessions.lock().unwrap();
+
+        match field("type") {
+            Some("ping") => Some(json!({ "type": "pong" }).to_string()),
+            Some("join") => {
+                if role.is_some() {
+                    return Some(error("already joined"));
+                }
+                let new_role = match field("role") {
+                    Some("white") => Role::Player(ColorTurn::White),
+                    Some("black") => Role::Player(ColorTurn::Black),
+                    Some("spectator") => Role::Spectator,
+                    _ => return Some(error("role must be white, black or spectator")),
+                };
+                let session = sessions.entry(game_id.to_string()).or_insert_with(|| Session {
+                    game: ServerGame::new(field("fen")),
+                    tokens: [None, None],
+                    clients: Vec::new(),
+                });
+
+                let mut joined = json!({ "type": "joined", "role": field("role") });
+                if let Role::Player(color) = new_role {
+                    let token = match &session.tokens[seat(color)] {
+                        Some(token) if field("token") == Some(token.as_str()) => token.clone(),
+                        Some(_) => return Some(error("the seat is taken, join with its token to take it back")),
+                        None => format!("{:016x}", rand::thread_rng().gen::<u64>()),
+                    };
+                    // a player who reconnects replaces the old connection if it is still there
+                    for client in session.clients.iter().filter(|client| client.role == new_role) {
+                        let _ = client.sender.send(Outgoing::Text(error("replaced by a new connection")));
+                        let _ = client.sender.send(Outgoing::Close);
+                    }
+                    session.clients.retain(|client| client.role != new_role);
+                    session.tokens[seat(color)] = Some(token.clone());
+                    joined["token"] = json!(token);
+                }
+
+                session.clients.push(Client { id: client_id, role: new_role, sender: sender.clone() });
+                *role = Some(new_role);
+                let _ = sender.send(Outgoing::Text(joined.to_string()));
+                session.broadcast(&session.state(game_id, None));
+                None
+            }
+            Some("move") => {
+                let session = match (sessions.get_mut(game_id), *role) {
+                    (Some(session), Some(Role::Player(color))) => {
+                        if session.game.is_over() {
+                            return Some(error("the game is over"));
+                        }
+                        if session.game.game.get_game_turn() != color {
+                            return Some(error("it is not your turn"));
+                        }
+                        session
+                    }
+                    _ => return Some(error("only players can move")),
+                };
+                let text = field("move").unwrap_or("");
+                let game = &session.game.game;
+                let mv = match game.parse_move(text).or_else(|| game.parse_san(text)) {
+                    Some(mv) => mv,
+                    None => return Some(error("the move is not legal")),
+                };
+                session.game.play(mv);
+                let san = session.game.history.last().cloned();
+                session.broadcast(&session.state(game_id, san.as_deref()));
+                None
+            }
+            Some("resign") => {
+                match (sessions.get_mut(game_id), *role) {
+                    (Some(session), Some(Role::Player(color))) if !session.game.is_over() => {
+                        session.game.resigned = Some(color);
+                        session.broadcast(&session.state(game_id, None));
+                        None
+                    }
+                    (Some(_), Some(Role::Player(_))) => Some(error("the game is over")),
+                    _ => Some(error("only players can resign")),
+                }
+            }
+            _ => Some(error("unknown message type")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LiveServer;
+    use serde_json::{json, Value};
+    use std::net::TcpStream;
+    use std::time::Duration;
+    use tungstenite::stream::MaybeTlsStream;
+    use tungstenite::{Message, WebSocket};
+
+    type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+    fn connect(addr: std::net::SocketAddr, game: &str) -> Socket {
+        let (socket, _) = tungstenite::connect(format!("ws://{}/live/{}", addr, game)).unwrap();
+        if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        }
+        socket
+    }
+
+    fn send(socket: &mut Socket, message: Value) {
+        socket.send(Message::Text(message.to_string())).unwrap();
+    }
+
+    /// Reads messages until one of the given type, pings are answered on the way.
+    fn read(socket: &mut Socket, kind: &str) -> Value {
+        loop {
+            if let Message::Text(text) = socket.read().unwrap() {
+                let message: Value = serde_json::from_str(&text).unwrap();
+                if message["type"] == kind {
+                    return message;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn moves_reach_players_and_spectators() {
+        let (addr, _) = LiveServer::default().listen("127.0.0.1:0").unwrap();
+        let mut white = connect(addr, "g1");
+        let mut black = connect(addr, "g1");
+        let mut spectator = connect(addr, "g1");
+
+        send(&mut white, json!({ "type": "join", "role": "white" }));
+        read(&mut white, "joined");
+        send(&mut black, json!({ "type": "join", "role": "black" }));
+        let token = read(&mut black, "joined")["token"].as_str().unwrap().to_string();
+        send(&mut spectator, json!({ "type": "join", "role": "spectator" }));
+        read(&mut spectator, "joined");
+
+        send(&mut black, json!({ "type": "move", "move": "e7e5" }));
+        assert_eq!(read(&mut black, "error")["message"], "it is not your turn");
+
+        send(&mut white, json!({ "type": "move", "move": "e4" }));
+        let state = loop {
+            let state = read(&mut spectator, "state");
+            if state["last_move"] == "e4" {
+                break state;
+            }
+        };
+        assert_eq!(state["turn"], "black");
+        assert_eq!(state["spectators"], 1);
+
+        // black reconnects with its token and gets the whole game again
+        drop(black);
+        let mut black = connect(addr, "g1");
+        send(&mut black, json!({ "type": "join", "role": "black", "token": "wrong" }));
+        read(&mut black, "error");
+        send(&mut black, json!({ "type": "join", "role": "black", "token": token }));
+        read(&mut black, "joined");
+        assert_eq!(read(&mut black, "state")["history"], json!(["e4"]));
+    }
+
+    #[test]
+    fn silent_clients_are_pinged_and_dropped() {
+        let server = LiveServer::new(Duration::from_millis(50), Duration::from_millis(300));
+        let (addr, _) = server.listen("127.0.0.1:0").unwrap();
+        let mut socket = connect(addr, "g2");
+
+        // the client does not read, so the pong to the ping is never sent
+        std::thread::sleep(Duration::from_millis(500));
+        let mut pinged = false;
+        let closed = loop {
+            match socket.read() {
+                Ok(Message::Ping(_)) => pinged = true,
+                Ok(Message::Close(_)) => break true,
+                Ok(_) => {}
+                // answering the ping may fail once the server has dropped the connection
+                Err(tungstenite::Error::Io(err)) => break err.kind() != std::io::ErrorKind::WouldBlock,
+                Err(_) => break true,
+            }
+        };
+        assert!(pinged);
+        assert!(closed);
+    }
+}
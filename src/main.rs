@@ -1,59 +1,487 @@
-use crate::lib::Game;
-use crate::lib::ColorTurn;
-use crate::lib::GameState;
+use ogronman_chess::{variant_from_name, Clock, ColorTurn, Game, Termination};
+use ogronman_chess::{Ai, OpeningBook};
+use ogronman_chess::{play_game, HumanPlayer, Outcome, Player, RemotePlayer, ScriptedPlayer};
+use ogronman_chess::set_tablebase_path;
+use ogronman_chess::{uci, xboard};
+use ogronman_chess::engine_match::{read_openings, run_match, Adjudication, MatchConfig, Opening, TimeControl, UciEngine};
+use ogronman_chess::sprt::{Sprt, SprtResult};
+use ogronman_chess::tournament::{Entrant, Format, Tournament, TournamentConfig, TournamentPlayer};
+use ogronman_chess::{AnalysisOptions, SvgOptions};
+use std::env;
+use std::fs;
+use std::fs::OpenOptions;
 use std::io;
-use std::io::BufRead;
-mod lib;
+use std::io::Write;
+use std::net::TcpListener;
+use std::process;
+
+/* How many plies of every game make-book puts in the book if --plies is not given */
+const DEFAULT_BOOK_PLIES: usize = 16;
+/* How many games and which time control match uses if --games and --tc are not given */
+const DEFAULT_MATCH_GAMES: u32 = 10;
+const DEFAULT_SPRT_GAMES: u32 = 100000;
+const DEFAULT_MATCH_TC: &str = "10+0.1";
+/* How many rounds a Swiss tournament has if --rounds is not given */
+const DEFAULT_SWISS_ROUNDS: u32 = 5;
+
+
+/**
+ * Builds a Polyglot book from PGN files
+ *
+ * usage: make-book <book.bin> <games.pgn>... [--plies N]
+ */
+fn make_book(args: &[String]) {
+    let mut plies = DEFAULT_BOOK_PLIES;
+    let mut files: Vec<&String> = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--plies" {
+            plies = iter.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| {
+                eprintln!("--plies needs a number");
+                process::exit(1);
+            });
+        } else {
+            files.push(arg);
+        }
+    }
+    if files.len() < 2 {
+        eprintln!("usage: make-book <book.bin> <games.pgn>... [--plies N]");
+        process::exit(1);
+    }
+
+    let mut text = String::new();
+    for file in &files[1..] {
+        match fs::read_to_string(file) {
+            Ok(pgn) => {
+                text.push_str(&pgn);
+                text.push('\n');
+            }
+            Err(err) => {
+                eprintln!("could not read {}: {}", file, err);
+                process::exit(1);
+            }
+        }
+    }
+
+    let book = OpeningBook::from_pgn(&text, plies);
+    if let Err(err) = book.save(files[0]) {
+        eprintln!("could not write {}: {}", files[0], err);
+        process::exit(1);
+    }
+    println!("Wrote {} entries to {}", book.len(), files[0]);
+}
+
+
+/// Prints the message and ends the program.
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+/**
+ * Reads a flag about how the games are played that match and tournament share, --tc,
+ * --openings, --resign, --draw and --max-moves. Returns false if arg is another flag
+ */
+fn parse_game_flag(arg: &str, iter: &mut std::slice::Iter<String>, time_control: &mut TimeControl,
+                   openings: &mut Vec<Opening>, adjudication: &mut Adjudication) -> bool {
+    let mut number = |flag: &str| -> i64 {
+        iter.next().and_then(|n| n.parse::<f64>().ok()).unwrap_or_else(|| fail(format!("{} needs a number", flag))) as i64
+    };
+    match arg {
+        "--max-moves" => adjudication.max_moves = Some(number("--max-moves").max(0) as u32),
+        "--resign" => {
+            adjudication.resign_score = Some(number("--resign") as i32);
+            adjudication.resign_moves = number("--resign").max(1) as u32;
+        }
+        "--draw" => {
+            adjudication.draw_score = Some(number("--draw") as i32);
+            adjudication.draw_moves = number("--draw").max(1) as u32;
+            adjudication.draw_start = number("--draw").max(0) as u32;
+        }
+        "--tc" => {
+            *time_control = iter.next().and_then(|tc| TimeControl::parse(tc))
+                .unwrap_or_else(|| fail(String::from("--tc needs seconds+increment, like 10+0.1")));
+        }
+        "--openings" => {
+            let path = iter.next().unwrap_or_else(|| fail(String::from("--openings needs a file")));
+            *openings = read_openings(path).unwrap_or_else(|err| fail(format!("could not read {}: {}", path, err)));
+        }
+        _ => return false,
+    }
+    true
+}
+
+
+/**
+ * Plays a match between two UCI engines, a missing engine is this program
+ *
+ * usage: match [--engine "command args"]... [--games N] [--tc seconds+increment]
+ *              [--openings file.epd|file.pgn] [--pgn out.pgn]
+ *              [--resign cp moves] [--draw cp moves from-move] [--max-moves N]
+ *              [--sprt elo0 elo1] [--alpha a] [--beta b]
+ */
+fn run_engine_match(args: &[String]) {
+    let mut commands: Vec<Vec<String>> = Vec::new();
+    let mut config = MatchConfig {
+        games: DEFAULT_MATCH_GAMES,
+        time_control: TimeControl::parse(DEFAULT_MATCH_TC).unwrap(),
+        openings: Vec::new(),
+        adjudication: Adjudication::default(),
+        sprt: None,
+    };
+    let mut pgn_path: Option<String> = None;
+    let mut games: Option<u32> = None;
+    let (mut alpha, mut beta): (Option<f64>, Option<f64>) = (None, None);
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if parse_game_flag(arg, &mut iter, &mut config.time_control, &mut config.openings, &mut config.adjudication) {
+            continue;
+        }
+        let mut decimal = |flag: &str| -> f64 {
+            iter.next().and_then(|n| n.parse().ok()).unwrap_or_else(|| fail(format!("{} needs a number", flag)))
+        };
+        match arg.as_str() {
+            "--games" => games = Some(decimal("--games").max(0.0) as u32),
+            "--sprt" => {
+                let elo0 = decimal("--sprt");
+                config.sprt = Some(Sprt::new(elo0, decimal("--sprt")));
+            }
+            "--alpha" => alpha = Some(decimal("--alpha")),
+            "--beta" => beta = Some(decimal("--beta")),
+            "--engine" => match iter.next() {
+                Some(command) => commands.push(command.split_whitespace().map(String::from).collect()),
+                None => fail(String::from("--engine needs a command")),
+            },
+            "--pgn" => pgn_path = Some(iter.next().unwrap_or_else(|| fail(String::from("--pgn needs a file"))).clone()),
+            _ => fail(format!("unknown argument {}", arg)),
+        }
+    }
+
+    // with an SPRT the match goes on until the test is done, unless --games is given
+    config.games = games.unwrap_or(if config.sprt.is_some() { DEFAULT_SPRT_GAMES } else { DEFAULT_MATCH_GAMES });
+    if let Some(sprt) = config.sprt.as_mut() {
+        sprt.alpha = alpha.unwrap_or(sprt.alpha);
+        sprt.beta = beta.unwrap_or(sprt.beta);
+        if !(sprt.alpha > 0.0 && sprt.alpha < 1.0 && sprt.beta > 0.0 && sprt.beta < 1.0 && sprt.elo0 < sprt.elo1) {
+            fail(String::from("--sprt needs elo0 below elo1, and --alpha and --beta between 0 and 1"));
+        }
+    }
+
+    // an engine that is not given is this program in UCI mode
+    while commands.len() < 2 {
+        let program = env::current_exe().unwrap_or_else(|err| fail(format!("could not find this program: {}", err)));
+        commands.push(vec![program.to_string_lossy().into_owned(), String::from("--uci")]);
+    }
+    let mut engines: Vec<UciEngine> = commands.iter().take(2).map(|command| {
+        UciEngine::start(&command[0], &command[1..])
+            .unwrap_or_else(|err| fail(format!("could not start {}: {}", command.join(" "), err)))
+    }).collect();
+    let mut pgn = pgn_path.as_ref().map(|path| {
+        OpenOptions::new().create(true).append(true).open(path)
+            .unwrap_or_else(|err| fail(format!("could not open {}: {}", path, err)))
+    });
+
+    let (first, second) = engines.split_at_mut(1);
+    let (first, second) = (&mut first[0], &mut second[0]);
+    let names = (first.name().to_string(), second.name().to_string());
+    let score = run_match(first, second, &config, |game, score| {
+        match config.sprt {
+            // a status line that is written over after every game
+            Some(sprt) => {
+                let (lower, upper) = sprt.bounds();
+                print!(
+                    "\rGames {}: {} - {} - {}  Ptnml(0-2) {}  LLR {:.2} ({:.2}, {:.2})  ",
+                    score.games(), score.wins, score.losses, score.draws, score.pairs, sprt.llr(&score.pairs), lower, upper
+                );
+                let _ = io::stdout().flush();
+            }
+            None => println!(
+                "Game {} of {}: {} - {} {} ({}), score of {}: {} - {} - {}",
+                score.games(), config.games, game.pgn.tag("White").unwrap_or("?"), game.pgn.tag("Black").unwrap_or("?"),
+                game.pgn.result, game.reason, names.0, score.wins, score.losses, score.draws
+            ),
+        }
+        if let Some(file) = pgn.as_mut() {
+            if let Err(err) = file.write_all(game.pgn.to_pgn().as_bytes()) {
+                eprintln!("could not write the game: {}", err);
+            }
+        }
+    });
+
+    if let Some(sprt) = config.sprt {
+        println!();
+        match sprt.result(&score.pairs) {
+            Some(SprtResult::H1) => println!("SPRT: H1 accepted, {} is at least {} Elo stronger", names.0, sprt.elo1),
+            Some(SprtResult::H0) => println!("SPRT: H0 accepted, {} is not more than {} Elo stronger", names.0, sprt.elo0),
+            None => println!("SPRT: no result after {} games", score.games()),
+        }
+    }
+    println!("{} vs {}: {} - {} - {} [{:.3}]", names.0, names.1, score.wins, score.losses, score.draws, score.score());
+    match score.elo() {
+        Some((elo, margin)) => println!("Elo difference: {:.1} +/- {:.1}", elo, margin),
+        None => println!("Elo difference: unknown"),
+    }
+}
+
+
+/**
+ * Plays a tournament between ai configurations and UCI engines
+ *
+ * usage: tournament [--ai name depth]... [--engine name "command args"]...
+ *                   [--format round-robin|swiss] [--cycles N] [--rounds N]
+ *                   [--tc seconds+increment] [--openings file.epd|file.pgn]
+ *                   [--resign cp moves] [--draw cp moves from-move] [--max-moves N]
+ *                   [--pgn out.pgn] [--crosstable out.txt]
+ */
+fn run_tournament(args: &[String]) {
+    let mut players: Vec<TournamentPlayer> = Vec::new();
+    let mut config = TournamentConfig {
+        format: Format::RoundRobin { cycles: 1 },
+        time_control: TimeControl::parse(DEFAULT_MATCH_TC).unwrap(),
+        openings: Vec::new(),
+        adjudication: Adjudication::default(),
+    };
+    let mut swiss = false;
+    let (mut cycles, mut rounds): (u32, u32) = (1, DEFAULT_SWISS_ROUNDS);
+    let (mut pgn_path, mut crosstable_path): (Option<String>, Option<String>) = (None, None);
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if parse_game_flag(arg, &mut iter, &mut config.time_control, &mut config.openings, &mut config.adjudication) {
+            continue;
+        }
+        let mut value = |flag: &str| -> String {
+            iter.next().cloned().unwrap_or_else(|| fail(format!("{} needs a value", flag)))
+        };
+        let number = |flag: &str, text: String| -> u32 {
+            text.parse().unwrap_or_else(|_| fail(format!("{} needs a number", flag)))
+        };
+        match arg.as_str() {
+            "--ai" => {
+                let name = value("--ai");
+                let depth = number("--ai", value("--ai"));
+                let ai = Ai { options: AnalysisOptions { depth, ..AnalysisOptions::default() }, ..Ai::default() };
+                players.push(TournamentPlayer { name, entrant: Entrant::Ai(ai) });
+            }
+            "--engine" => {
+                let name = value("--engine");
+                let command: Vec<String> = value("--engine").split_whitespace().map(String::from).collect();
+                match command.split_first() {
+                    Some((program, args)) => players.push(TournamentPlayer {
+                        name,
+                        entrant: Entrant::Engine { command: program.clone(), args: args.to_vec() },
+                    }),
+                    None => fail(String::from("--engine needs a command")),
+                }
+            }
+            "--format" => match value("--format").as_str() {
+                "round-robin" => swiss = false,
+                "swiss" => swiss = true,
+                _ => fail(String::from("--format must be round-robin or swiss")),
+            },
+            "--cycles" => cycles = number("--cycles", value("--cycles")),
+            "--rounds" => rounds = number("--rounds", value("--rounds")),
+            "--pgn" => pgn_path = Some(value("--pgn")),
+            "--crosstable" => crosstable_path = Some(value("--crosstable")),
+            _ => fail(format!("unknown argument {}", arg)),
+        }
+    }
+    if players.len() < 2 {
+        fail(String::from("a tournament needs at least two players, given with --ai or --engine"));
+    }
+    config.format = if swiss { Format::Swiss { rounds } } else { Format::RoundRobin { cycles } };
+
+    let mut tournament = Tournament::new(players, config);
+    let names: Vec<String> = tournament.players().iter().map(|player| player.name.clone()).collect();
+    let played = tournament.run(|pairing, game| {
+        let black = pairing.black.map_or("?", |black| names[black].as_str());
+        println!("Round {}: {} - {} {} ({})", pairing.round, names[pairing.white], black, game.pgn.result, game.reason);
+    });
+    if let Err(err) = played {
+        fail(format!("could not start the engines: {}", err));
+    }
+
+    let crosstable = tournament.crosstable();
+    println!();
+    print!("{}", crosstable);
+    for (path, text) in [(pgn_path, tournament.pgn()), (crosstable_path, crosstable)] {
+        if let Some(path) = path {
+            if let Err(err) = fs::write(&path, text) {
+                eprintln!("could not write {}: {}", path, err);
+            }
+        }
+    }
+}
 
 
 fn main() {
-    let mut game = Game::new();
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("make-book") {
+        make_book(&args[1..]);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("tournament") {
+        run_tournament(&args[1..]);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("match") {
+        run_engine_match(&args[1..]);
+        return;
+    }
+
+    // the ai plays from the book given with --book before it starts searching
+    let mut ai = Ai::default();
+    if let Some(index) = args.iter().position(|arg| arg == "--book") {
+        let path = args.get(index + 1).unwrap_or_else(|| {
+            eprintln!("--book needs a file");
+            process::exit(1);
+        });
+        match OpeningBook::open(path) {
+            Ok(book) => ai.book = Some(book),
+            Err(err) => {
+                eprintln!("could not read {}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
+
+    // the search uses the Syzygy tables in the directory given with --syzygy
+    if let Some(index) = args.iter().position(|arg| arg == "--syzygy") {
+        let dir = args.get(index + 1).unwrap_or_else(|| {
+            eprintln!("--syzygy needs a directory");
+            process::exit(1);
+        });
+        if let Err(err) = set_tablebase_path(Some(dir)) {
+            eprintln!("could not read {}: {}", dir, err);
+            process::exit(1);
+        }
+    }
+
+    // with --svg the position in the fen string is written as an SVG image and nothing is played
+    if let Some(index) = args.iter().position(|arg| arg == "--svg") {
+        let fen = args.get(index + 1).unwrap_or_else(|| {
+            eprintln!("--svg needs a fen string");
+            process::exit(1);
+        });
+        let mut game = Game::new();
+        game.load_fen_board(fen.to_string());
+        print!("{}", game.to_svg(&SvgOptions::default()));
+        return;
+    }
+
+    // with --uci or --xboard the program is an engine for chess GUIs instead of a game in the terminal
+    if args.iter().any(|arg| arg == "--uci") {
+        uci::run(ai, io::stdin().lock(), io::stdout());
+        return;
+    }
+    if args.iter().any(|arg| arg == "--xboard") {
+        xboard::run(ai, io::BufReader::new(io::stdin()), io::stdout());
+        return;
+    }
 
+    let mut game = Game::new();
     game.init_board();
 
-    game.print_board();
-    println!("Current turn is:");
-    println!("{:#?}", game.get_game_turn());
-
-    
-    let input = io::stdin();
-    
-    loop{
-        if game.get_game_state() != GameState::GameOver {
-            if game.get_game_turn() == ColorTurn::White{ 
-
-
-                //Gör så att att om man bara skriver en posistion visar den alla moves som den pjäsen kan göra
-                //Good idea
-                let lines = input.lock().lines().next().unwrap().unwrap();
-    
-                let mut pos:Vec<char> = lines.chars().collect();
-        
-                if pos.len() == 5{
-                    let mut _from:String = String::from("");
-                    _from.push(pos[0]);
-                    _from.push(pos[1]);
-                    let mut _to:String = String::from("");
-                    _to.push(pos[3]);
-                    _to.push(pos[4]);
-            
-                    game.make_move(_from, _to);
-                    game.print_board();
-                    println!("Current turn is:");
-                    println!("{:#?}", game.get_game_turn());
+    // the player here is the keyboard, or a file of moves given with --script
+    let mut local: Box<dyn Player> = match args.iter().position(|arg| arg == "--script") {
+        Some(index) => {
+            let path = args.get(index + 1).unwrap_or_else(|| {
+                eprintln!("--script needs a file");
+                process::exit(1);
+            });
+            match ScriptedPlayer::from_file(path) {
+                Ok(player) => Box::new(player),
+                Err(err) => {
+                    eprintln!("could not read {}: {}", path, err);
+                    process::exit(1);
                 }
-            } else {
-                game.make_ai_move();
-                game.print_board();
-                println!("Current turn is:");
-                println!("{:#?}", game.get_game_turn());
             }
-        } else {
-            break;
         }
+        None => Box::new(HumanPlayer),
+    };
+
+    // with --host or --connect the other player is in another terminal, otherwise it is the ai
+    let value = |flag: &str| -> Option<&String> {
+        let index = args.iter().position(|arg| arg == flag)?;
+        Some(args.get(index + 1).unwrap_or_else(|| {
+            eprintln!("{} needs a value", flag);
+            process::exit(1);
+        }))
+    };
+    let color = value("--color").map(|color| match color.as_str() {
+        "white" => ColorTurn::White,
+        "black" => ColorTurn::Black,
+        _ => {
+            eprintln!("--color must be white or black");
+            process::exit(1);
+        }
+    });
+
+    // with --variant the game is played by other rules, like --variant kingofthehill
+    if let Some(name) = value("--variant") {
+        game.set_variant(variant_from_name(name).unwrap_or_else(|| fail(format!("there is no variant called {}", name))));
+        game.init_board();
+    }
+
+    // with --chess960 the game starts from that Chess960 position, or a random one
+    if let Some(position) = value("--chess960") {
+        let index = match position.parse::<u16>() {
+            Ok(index) if game.init_chess960(index) => index,
+            Ok(_) => fail(format!("there is no Chess960 position {}", position)),
+            Err(_) => game.init_chess960_random(),
+        };
+        println!("Chess960-ställning nummer {}", index);
+    }
+
+    // the host sends its variant and starting position, which the side that connects plays
+    let remote = if let Some(port) = value("--host") {
+        let listener = TcpListener::bind(("0.0.0.0", port.parse::<u16>().unwrap_or_else(|_| {
+            eprintln!("--host needs a port");
+            process::exit(1);
+        })));
+        println!("Väntar på motståndaren på port {}", port);
+        Some(listener.and_then(|listener| RemotePlayer::accept(&listener, color, &game)))
+    } else {
+        value("--connect").map(|addr| RemotePlayer::connect(addr.as_str(), color).map(|(remote, color, hosted)| {
+            game = hosted;
+            (remote, color)
+        }))
+    };
+
+    // with --clock the game is played with time, like --clock 300+2 or --clock 40/5400:1800
+    if let Some(control) = value("--clock") {
+        game.set_clock(Some(Clock::parse(control).unwrap_or_else(|| fail(format!("could not read the time control {}", control)))));
     }
 
-    println!("Grattis du har vunnit");
-    
+    let (finished, local_color) = match remote {
+        Some(Ok((mut remote, local_color))) => {
+            println!("Du spelar {:?}", local_color);
+            let finished = if local_color == ColorTurn::White {
+                play_game(game, local.as_mut(), &mut remote)
+            } else {
+                play_game(game, &mut remote, local.as_mut())
+            };
+            remote.send_moves(&finished.game);
+            if let Some(err) = remote.error() {
+                eprintln!("{}", err);
+            }
+            (finished, local_color)
+        }
+        Some(Err(err)) => {
+            eprintln!("could not start the game: {}", err);
+            process::exit(1);
+        }
+        None => (play_game(game, local.as_mut(), &mut ai), ColorTurn::White),
+    };
+    finished.game.print_board();
+    if finished.termination == Termination::Timeout {
+        println!("Tiden tog slut");
+    }
 
+    match (finished.outcome, local_color) {
+        (Outcome::Draw, _) => println!("Det blev oavgjort"),
+        (Outcome::WhiteWins, ColorTurn::White) | (Outcome::BlackWins, ColorTurn::Black) => println!("Grattis du har vunnit"),
+        _ => println!("Du har förlorat"),
+    }
 }
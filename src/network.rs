@@ -0,0 +1,346 @@
+use std::fmt;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use rand::Rng;
+
+use crate::player::{Player, NO_MOVE};
+use crate::{variant_from_name, Game, Move, ColorTurn};
+
+/* The first word of the greeting and the version of the line protocol */
+const GREETING: &str = "hello ogronman-chess 2";
+
+/// Why the game with the other side could not go on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RemoteError {
+    /// The connection was closed or broke.
+    Disconnected,
+    /// The other side sent a move that is not legal in the position.
+    IllegalMove(String),
+    /// The position after a move is not the same on both sides.
+    Desync { local: String, remote: String },
+    /// The other side sent a line that is not part of the protocol.
+    Protocol(String),
+    /// The other side stopped the game and said why.
+    Remote(String),
+}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RemoteError::Disconnected => write!(f, "the connection was closed"),
+            RemoteError::IllegalMove(mv) => write!(f, "the other side played the illegal move {}", mv),
+            RemoteError::Desync { local, remote } => write!(f, "the positions differ, here {} and there {}", local, remote),
+            RemoteError::Protocol(line) => write!(f, "the other side sent \"{}\"", line),
+            RemoteError::Remote(reason) => write!(f, "the other side stopped the game: {}", reason),
+        }
+    }
+}
+
+fn color_name(color: ColorTurn) -> &'static str {
+    if color == ColorTurn::White { "white" } else { "black" }
+}
+
+fn parse_color(text: &str) -> Option<ColorTurn> {
+    match text {
+        "white" => Some(ColorTurn::White),
+        "black" => Some(ColorTurn::Black),
+        _ => None,
+    }
+}
+
+fn other(color: ColorTurn) -> ColorTurn {
+    if color == ColorTurn::White { ColorTurn::Black } else { ColorTurn::White }
+}
+
+/**
+ * The player at the other end of a TCP connection, for games between two terminals.
+ *
+ * The two sides speak a line protocol. The side that connects greets with
+ * "hello ogronman-chess 2" and the color it wants, if any, and the host answers with the
+ * same greeting and the color the connecting side plays. The host then sends the game
+ * to play, "variant <name>" and "start <standard|chess960> <fen>", which the connecting
+ * side sets up instead of its own. After that every move is sent
+ * as "move e2e4 <fen>", with the move in UCI form and the fen of the position after it.
+ * A received move is checked against the local game and its fen is compared with the
+ * local one, and "error <reason>" is sent back before the game is stopped if either
+ * check fails
+ */
+#[derive(Debug)]
+pub struct RemotePlayer {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    /* how many moves of the game the other side knows about */
+    sent: usize,
+    error: Option<RemoteError>,
+}
+
+impl RemotePlayer {
+
+    /**
+     * Waits for the other side to connect to the listener, agrees on the colors and sends
+     * the variant and starting position of the game. The host gets the color it asks for,
+     * otherwise the one the other side does not ask for, otherwise a random one. Returns the
+     * player and the color played here
+     */
+    pub fn accept(listener: &TcpListener, color: Option<ColorTurn>, game: &Game) -> io::Result<(RemotePlayer, ColorTurn)> {
+        let (stream, _) = listener.accept()?;
+        let mut player = RemotePlayer::new(stream)?;
+        let line = player.read_line()?;
+        let wanted = match line.strip_prefix(GREETING) {
+            Some(rest) => parse_color(rest.trim()),
+            None => {
+                player.send(&format!("error expected \"{}\"", GREETING));
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected greeting \"{}\"", line)));
+            }
+        };
+        let local = match (color, wanted) {
+            (Some(color), _) => color,
+            (None, Some(wanted)) => other(wanted),
+            (None, None) => if rand::thread_rng().gen::<bool>() { ColorTurn::White } else { ColorTurn::Black },
+        };
+        player.send(&format!("{} {}", GREETING, color_name(other(local))));
+        player.send(&format!("variant {}", game.variant().name()));
+        player.send(&format!("start {} {}", if game.is_chess960() { "chess960" } else { "standard" }, game.to_fen()));
+        Ok((player, local))
+    }
+
+    /**
+     * Connects to a host and agrees on the colors, the color asked for is only a wish.
+     * Returns the player, the color played here and the game the host sent, which is
+     * the one to play
+     */
+    pub fn connect<A: ToSocketAddrs>(addr: A, color: Option<ColorTurn>) -> io::Result<(RemotePlayer, ColorTurn, Game)> {
+        let mut player = RemotePlayer::new(TcpStream::connect(addr)?)?;
+        match color {
+            Some(color) => player.send(&format!("{} {}", GREETING, color_name(color))),
+            None => player.send(GREETING),
+        }
+        let unexpected = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("unexpected greeting \"{}\"", line));
+        let line = player.read_line()?;
+        let color = line.strip_prefix(GREETING).and_then(|rest| parse_color(rest.trim())).ok_or_else(|| unexpected(&line))?;
+
+        let line = player.read_line()?;
+        let variant = match line.strip_prefix("variant ").map(variant_from_name) {
+            Some(Some(variant)) => variant,
+            Some(None) => {
+                player.send(&format!("error there is no {} here", line["variant ".len()..].trim()));
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown variant in \"{}\"", line)));
+            }
+            None => return Err(unexpected(&line)),
+        };
+        let line = player.read_line()?;
+        let (chess960, fen) = match line.strip_prefix("start ").and_then(|rest| rest.split_once(' ')) {
+            Some(("standard", fen)) => (false, fen),
+            Some(("chess960", fen)) => (true, fen),
+            _ => return Err(unexpected(&line)),
+        };
+        let mut game = Game::new();
+        game.set_variant(variant);
+        game.set_chess960(chess960);
+        game.load_fen_board(fen.to_string());
+        Ok((player, color, game))
+    }
+
+    fn new(stream: TcpStream) -> io::Result<RemotePlayer> {
+        stream.set_nodelay(true)?;
+        Ok(RemotePlayer {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            sent: 0,
+            error: None,
+        })
+    }
+
+    /// Why the game with the other side was stopped, None if it was not.
+    pub fn error(&self) -> Option<&RemoteError> {
+        self.error.as_ref()
+    }
+
+    fn send(&mut self, line: &str) {
+        if writeln!(self.writer, "{}", line).is_err() && self.error.is_none() {
+            self.error = Some(RemoteError::Disconnected);
+        }
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(line.trim().to_string())
+    }
+
+    /**
+     * Sends the moves of the game the other side has not seen yet, the moves played here.
+     * Has to be called when the game is over, so the other side gets the last move
+     */
+    pub fn send_moves(&mut self, game: &Game) {
+        let played = game.history.len();
+        if self.sent >= played {
+            return;
+        }
+        let mut position = game.clone();
+        let mut moves: Vec<Move> = Vec::new();
+        while position.history.len() > self.sent {
+            match position.undo() {
+                Some(mv) => moves.push(mv),
+                None => break,
+            }
+        }
+        for mv in moves.into_iter().rev() {
+            position.play(mv);
+            let line = format!("move {} {}", mv.to_uci(), position.to_fen());
+            self.send(&line);
+        }
+        self.sent = played;
+    }
+
+    /**
+     * Reads the next move of the other side and checks it against the game, sends the
+     * reason back and remembers it if the move can not be played
+     */
+    fn receive_move(&mut self, game: &Game) -> Result<Move, RemoteError> {
+        let line = self.read_line().map_err(|_| RemoteError::Disconnected)?;
+        let words: Vec<&str> = line.splitn(3, ' ').collect();
+        match words.as_slice() {
+            ["move", text, fen] => {
+                let mv = match game.parse_move(text) {
+                    Some(mv) => mv,
+                    None => {
+                        self.send(&format!("error illegal move {}", text));
+                        return Err(RemoteError::IllegalMove(text.to_string()));
+                    }
+                };
+                let mut position = game.clone();
+                position.play(mv);
+                let local = position.to_fen();
+                if local != fen.trim() {
+                    self.send(&format!("error desync, the position here is {}", local));
+                    return Err(RemoteError::Desync { local, remote: fen.trim().to_string() });
+                }
+                // the move is played by play_game, so the other side already knows it
+                self.sent = game.history.len() + 1;
+                Ok(mv)
+            }
+            ["error", ..] => Err(RemoteError::Remote(line["error".len()..].trim().to_string())),
+            _ => {
+                self.send("error expected \"move <move> <fen>\"");
+                Err(RemoteError::Protocol(line))
+            }
+        }
+    }
+}
+
+impl Player for RemotePlayer {
+    fn choose_move(&mut self, game: &Game) -> Move {
+        if self.error.is_some() {
+            return NO_MOVE;
+        }
+        self.send_moves(game);
+        match self.receive_move(game) {
+            Ok(mv) => mv,
+            Err(err) => {
+                self.error = Some(err);
+                NO_MOVE
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RemoteError, RemotePlayer};
+    use crate::player::{play_game, Outcome, ScriptedPlayer, Termination};
+    use crate::{ColorTurn, Game, KingOfTheHill};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+
+    fn script(moves: &str) -> ScriptedPlayer {
+        ScriptedPlayer::new(moves.split_whitespace().map(String::from).collect())
+    }
+
+    fn start() -> Game {
+        let mut game = Game::new();
+        game.init_board();
+        game
+    }
+
+    #[test]
+    fn two_sides_play_a_game_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let host = thread::spawn(move || {
+            let (mut remote, color) = RemotePlayer::accept(&listener, None, &start()).unwrap();
+            assert_eq!(color, ColorTurn::White);
+            let finished = play_game(start(), &mut script("f3 g4"), &mut remote);
+            remote.send_moves(&finished.game);
+            finished
+        });
+
+        let (mut remote, color, game) = RemotePlayer::connect(addr, Some(ColorTurn::Black)).unwrap();
+        assert_eq!(color, ColorTurn::Black);
+        let finished = play_game(game, &mut remote, &mut script("e5 Qh4"));
+        remote.send_moves(&finished.game);
+        let hosted = host.join().unwrap();
+
+        assert_eq!(finished.termination, Termination::Checkmate);
+        assert_eq!(hosted.termination, Termination::Checkmate);
+        assert_eq!(hosted.outcome, Outcome::BlackWins);
+        assert_eq!(finished.game.to_fen(), hosted.game.to_fen());
+    }
+
+    #[test]
+    fn the_connecting_side_plays_the_game_of_the_host() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let host = thread::spawn(move || {
+            let mut game = Game::new();
+            game.set_variant(Arc::new(KingOfTheHill));
+            game.init_chess960(0);
+            let (mut remote, _) = RemotePlayer::accept(&listener, Some(ColorTurn::White), &game).unwrap();
+            let finished = play_game(game, &mut script("e4"), &mut remote);
+            remote.send_moves(&finished.game);
+            finished
+        });
+
+        let (mut remote, _, game) = RemotePlayer::connect(addr, None).unwrap();
+        assert_eq!(game.variant().name(), "King of the Hill");
+        assert!(game.is_chess960());
+        assert_eq!(game.chess960_index(), Some(0));
+        let finished = play_game(game, &mut remote, &mut script("e5"));
+        let hosted = host.join().unwrap();
+        assert_eq!(hosted.game.history.len(), 2);
+        assert_eq!(finished.game.to_fen(), hosted.game.to_fen());
+    }
+
+    #[test]
+    fn detects_illegal_moves_and_desync() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let peer = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut line = String::new();
+            writeln!(writer, "hello ogronman-chess 2 white").unwrap();
+            for _ in 0..3 {
+                reader.read_line(&mut line).unwrap();
+            }
+            writeln!(writer, "move e2e4 8/8/8/8/8/8/8/8 b - - 0 1").unwrap();
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            line
+        });
+
+        let (mut remote, color) = RemotePlayer::accept(&listener, Some(ColorTurn::Black), &start()).unwrap();
+        assert_eq!(color, ColorTurn::Black);
+        let finished = play_game(start(), &mut remote, &mut script("e5"));
+        assert_eq!(finished.termination, Termination::IllegalMove);
+        assert!(matches!(remote.error(), Some(RemoteError::Desync { .. })));
+        assert!(peer.join().unwrap().starts_with("error desync"));
+    }
+}
@@ -0,0 +1,199 @@
+use std::cmp;
+
+/**
+ * One game read from a PGN file, the tag pairs in the order they were written,
+ * the moves in standard algebraic notation and the result ("1-0", "0-1", "1/2-1/2" or "*")
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+    pub result: String,
+}
+
+impl PgnGame {
+    /// Returns the value of a tag, for example tag("White") for the name of the white player.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Changes the value of a tag, or adds the tag at the end if the game does not have it.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(key, _)| key == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /**
+     * Writes the game as PGN, the tags first and then the moves with move numbers, in lines
+     * of at most 80 characters. A game that starts from a FEN tag is numbered from it
+     */
+    pub fn to_pgn(&self) -> String {
+        let mut text = String::new();
+        for (key, value) in &self.tags {
+            text.push_str(&format!("[{} \"{}\"]\n", key, value.replace('\\', "\\\\").replace('"', "\\\"")));
+        }
+        text.push('\n');
+
+        let fen_fields: Vec<&str> = self.tag("FEN").map_or(Vec::new(), |fen| fen.split_whitespace().collect());
+        let mut number: u32 = fen_fields.get(5).and_then(|n| n.parse().ok()).unwrap_or(1);
+        let mut white_to_move = fen_fields.get(1) != Some(&"b");
+
+        let mut words: Vec<String> = Vec::new();
+        for (index, mv) in self.moves.iter().enumerate() {
+            if white_to_move {
+                words.push(format!("{}. {}", number, mv));
+            } else if index == 0 {
+                words.push(format!("{}... {}", number, mv));
+            } else {
+                words.push(mv.clone());
+            }
+            if !white_to_move {
+                number += 1;
+            }
+            white_to_move = !white_to_move;
+        }
+        words.push(if self.result.is_empty() { "*".to_string() } else { self.result.clone() });
+
+        let mut line = String::new();
+        for word in words {
+            if !line.is_empty() && line.len() + 1 + word.len() > 80 {
+                text.push_str(&line);
+                text.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        text.push_str(&line);
+        text.push_str("\n\n");
+        text
+    }
+}
+
+/// Checks if a movetext token is a game result.
+fn is_result(token: &str) -> bool {
+    token == "1-0" || token == "0-1" || token == "1/2-1/2" || token == "*"
+}
+
+/**
+ * Reads every game in a PGN text
+ *
+ * Comments, variations, numeric annotations and move numbers are skipped, so only the
+ * moves of the main line are kept. The moves are not checked, use Game::parse_san to play them
+ */
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    let mut games: Vec<PgnGame> = Vec::new();
+    let mut game = PgnGame::default();
+    let mut has_content = false;
+    let mut in_comment = false;
+    let mut variation_depth = 0;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+
+        if !in_comment && variation_depth == 0 && trimmed.starts_with('[') {
+            // a tag after moves starts a new game, even if the last one had no result
+            if !game.moves.is_empty() {
+                games.push(std::mem::take(&mut game));
+            }
+            if let Some((key, value)) = trimmed[1..].trim_end_matches(']').split_once(' ') {
+                game.tags.push((key.to_string(), value.trim().trim_matches('"').to_string()));
+            }
+            has_content = true;
+            continue;
+        }
+        if trimmed.starts_with('%') {
+            continue;
+        }
+
+        let mut token = String::new();
+        let mut chars = line.chars().chain(std::iter::once('\n'));
+        while let Some(c) = chars.next() {
+            if in_comment {
+                if c == '}' {
+                    in_comment = false;
+                }
+                continue;
+            }
+            if !c.is_whitespace() && !"{};()".contains(c) {
+                token.push(c);
+                continue;
+            }
+
+            if !token.is_empty() && variation_depth == 0 {
+                if is_result(&token) {
+                    game.result = token.clone();
+                    games.push(std::mem::take(&mut game));
+                    has_content = false;
+                } else {
+                    // move numbers are written "12." or "12..." and may be glued to the move
+                    let word = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                    if !word.is_empty() && !word.starts_with('$') {
+                        game.moves.push(word.to_string());
+                        has_content = true;
+                    }
+                }
+            }
+            token.clear();
+
+            match c {
+                '{' => in_comment = true,
+                // a ; comment lasts to the end of the line
+                ';' => {
+                    for rest in chars.by_ref() {
+                        if rest == '\n' {
+                            break;
+                        }
+                    }
+                }
+                '(' => variation_depth += 1,
+                ')' => variation_depth = cmp::max(variation_depth - 1, 0),
+                _ => {}
+            }
+        }
+    }
+
+    if has_content {
+        games.push(game);
+    }
+    for game in games.iter_mut() {
+        if game.result.is_empty() {
+            game.result = game.tag("Result").unwrap_or("*").to_string();
+        }
+    }
+    games
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_pgn, PgnGame};
+
+    #[test]
+    fn reads_games_with_comments_and_variations() {
+        let text = "[Event \"Test\"]\n[White \"A\"]\n\n1. e4 {best by test} e5 (1... c5 2. Nf3) 2. Nf3 $1 Nc6 ; a comment\n3.Bb5 1-0\n\n[Event \"Second\"]\n\n1. d4 d5 1/2-1/2\n";
+        let games = parse_pgn(text);
+
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("White"), Some("A"));
+        assert_eq!(games[0].moves, vec!["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        assert_eq!(games[0].result, "1-0");
+        assert_eq!(games[1].moves, vec!["d4", "d5"]);
+        assert_eq!(games[1].result, "1/2-1/2");
+    }
+
+    #[test]
+    fn writes_games_that_can_be_read_back() {
+        let game = PgnGame {
+            tags: vec![("White".to_string(), "A \"B\"".to_string()), ("FEN".to_string(), "8/8/8/4k3/8/8/4P3/4K3 b - - 0 12".to_string())],
+            moves: vec!["Kd5".to_string(), "e4+".to_string(), "Kxe4".to_string()],
+            result: "1/2-1/2".to_string(),
+        };
+        let text = game.to_pgn();
+        assert!(text.contains("12... Kd5 13. e4+ Kxe4 1/2-1/2"));
+        assert_eq!(parse_pgn(&text)[0].moves, game.moves);
+    }
+}
@@ -0,0 +1,260 @@
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+
+use rand::Rng;
+
+use crate::ai::Ai;
+use crate::clock::format_time;
+use crate::render::RenderOptions;
+use crate::result::GameResult;
+use crate::{Game, Move, ColorTurn};
+
+/**
+ * Anything that can pick moves in a game, a human at the keyboard, the computer or a script.
+ *
+ * choose_move is only called when the player has at least one legal move. A player that
+ * can not or will not move returns a move that is not legal, which loses the game
+ */
+pub trait Player {
+    fn choose_move(&mut self, game: &Game) -> Move;
+}
+
+/* The move returned by players that have no move to give, it is never legal */
+pub(crate) const NO_MOVE: Move = Move { from: 0, to: 0, promotion: 0 };
+
+/// Plays a random legal move.
+#[derive(Clone, Debug, Default)]
+pub struct RandomPlayer;
+
+impl Player for RandomPlayer {
+    fn choose_move(&mut self, game: &Game) -> Move {
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            return NO_MOVE;
+        }
+        moves[rand::thread_rng().gen_range(0..moves.len())]
+    }
+}
+
+impl Player for Ai {
+    fn choose_move(&mut self, game: &Game) -> Move {
+        Ai::choose_move(self, game).unwrap_or(NO_MOVE)
+    }
+}
+
+/**
+ * Reads a move from a line of text, written like "e2 e4", "e2e4", "e7e8q" or "Nf3"
+ */
+fn read_move(game: &Game, text: &str) -> Option<Move> {
+    let text: String = text.split_whitespace().collect();
+    game.parse_move(&text).or_else(|| game.parse_san(&text))
+}
+
+/**
+ * A player at the keyboard, the board is printed from their side and moves are read from stdin until
+ * a legal one is written. The game is lost if stdin is closed
+ */
+#[derive(Clone, Debug, Default)]
+pub struct HumanPlayer;
+
+impl Player for HumanPlayer {
+    fn choose_move(&mut self, game: &Game) -> Move {
+        let options = RenderOptions { perspective: game.get_game_turn(), ..RenderOptions::default() };
+        print!("{}", game.render(&options));
+        println!("Current turn is:");
+        println!("{:#?}", game.get_game_turn());
+        if let Some(clock) = game.clock() {
+            println!("Tid kvar: vit {}, svart {}", format_time(clock.remaining(ColorTurn::White)), format_time(clock.remaining(ColorTurn::Black)));
+        }
+
+        let input = io::stdin();
+        for line in input.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            match read_move(game, &line) {
+                Some(mv) => return mv,
+                None => println!("Det draget går inte att göra"),
+            }
+        }
+        NO_MOVE
+    }
+}
+
+/**
+ * Plays moves from a list, for example read from a file. The moves are written like
+ * "e2e4" or "Nf3" and move numbers like "1." are skipped. The game is lost when the
+ * list runs out or a move can not be played
+ */
+#[derive(Clone, Debug, Default)]
+pub struct ScriptedPlayer {
+    moves: Vec<String>,
+    next: usize,
+}
+
+impl ScriptedPlayer {
+    pub fn new(moves: Vec<String>) -> ScriptedPlayer {
+        ScriptedPlayer { moves, next: 0 }
+    }
+
+    /// Reads the moves from a file, separated by whitespace.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<ScriptedPlayer> {
+        let text = fs::read_to_string(path)?;
+        let moves = text.split_whitespace()
+            .map(|word| word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.'))
+            .filter(|word| !word.is_empty())
+            .map(String::from)
+            .collect();
+        Ok(ScriptedPlayer::new(moves))
+    }
+}
+
+impl Player for ScriptedPlayer {
+    fn choose_move(&mut self, game: &Game) -> Move {
+        let mv = self.moves.get(self.next).and_then(|text| read_move(game, text));
+        self.next += 1;
+        mv.unwrap_or(NO_MOVE)
+    }
+}
+
+/// Who won a finished game.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+/// Why a game ended.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    FiftyMoves,
+    /// The same position came up three times.
+    Repetition,
+    /// Neither player has the pieces left to mate.
+    InsufficientMaterial,
+    IllegalMove,
+    /// The player ran out of time, which is a draw if the other player can not mate.
+    Timeout,
+    /// A rule of the variant ended the game, for example a king reaching the centre.
+    Variant,
+    Resignation,
+    DrawAgreement,
+    /// The arbiter set the result.
+    Arbiter,
+}
+
+/**
+ * A game played to the end by play_game, with the moves made from the start position
+ * and the position the game ended in
+ */
+#[derive(Clone, Debug)]
+pub struct FinishedGame {
+    pub outcome: Outcome,
+    pub termination: Termination,
+    pub moves: Vec<Move>,
+    pub game: Game,
+}
+
+/**
+ * Lets two players play from the given position until the game is over.
+ *
+ * The game ends with checkmate, stalemate, after 50 moves without a capture or pawn
+ * move, by threefold repetition or insufficient material, by a rule of the variant, when
+ * a player gives a move that is not legal, which loses the game, or when the flag of a
+ * player falls if the game has a clock
+ */
+pub fn play_game(mut game: Game, white: &mut dyn Player, black: &mut dyn Player) -> FinishedGame {
+    let mut moves: Vec<Move> = Vec::new();
+    let (outcome, termination) = loop {
+        let turn = game.get_game_turn();
+        let winner_if_lost = if turn == ColorTurn::White { Outcome::BlackWins } else { Outcome::WhiteWins };
+
+        if let Some((result, termination)) = game.board_result() {
+            let outcome = match result {
+                GameResult::WhiteWins => Outcome::WhiteWins,
+                GameResult::BlackWins => Outcome::BlackWins,
+                _ => Outcome::Draw,
+            };
+            break (outcome, termination);
+        }
+
+        let player: &mut dyn Player = if turn == ColorTurn::White { &mut *white } else { &mut *black };
+        let mv = player.choose_move(&game);
+        if !game.play(mv) {
+            if game.flagged() == Some(turn) {
+                let other = if turn == ColorTurn::White { ColorTurn::Black } else { ColorTurn::White };
+                break (if game.can_mate(other) { winner_if_lost } else { Outcome::Draw }, Termination::Timeout);
+            }
+            break (winner_if_lost, Termination::IllegalMove);
+        }
+        moves.push(mv);
+    };
+
+    FinishedGame { outcome, termination, moves, game }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{play_game, Outcome, Player, RandomPlayer, ScriptedPlayer, Termination};
+    use crate::{Clock, Game, Move};
+    use std::thread;
+    use std::time::Duration;
+
+    fn script(moves: &str) -> ScriptedPlayer {
+        ScriptedPlayer::new(moves.split_whitespace().map(String::from).collect())
+    }
+
+    #[test]
+    fn scripted_players_play_fools_mate() {
+        let mut game = Game::new();
+        game.init_board();
+        let finished = play_game(game, &mut script("f3 g4"), &mut script("e7e5 Qh4"));
+
+        assert_eq!(finished.outcome, Outcome::BlackWins);
+        assert_eq!(finished.termination, Termination::Checkmate);
+        assert_eq!(finished.moves.len(), 4);
+    }
+
+    #[test]
+    fn running_out_of_moves_loses() {
+        let mut game = Game::new();
+        game.init_board();
+        let finished = play_game(game, &mut script("e4"), &mut RandomPlayer);
+
+        assert_eq!(finished.outcome, Outcome::BlackWins);
+        assert_eq!(finished.termination, Termination::IllegalMove);
+    }
+
+    /// Plays random moves after thinking for a while.
+    struct SlowPlayer(Duration);
+
+    impl Player for SlowPlayer {
+        fn choose_move(&mut self, game: &Game) -> Move {
+            thread::sleep(self.0);
+            RandomPlayer.choose_move(game)
+        }
+    }
+
+    #[test]
+    fn flag_fall_loses_unless_the_other_side_can_not_mate() {
+        let mut game = Game::new();
+        game.init_board();
+        game.set_clock(Clock::parse("0.05"));
+        let finished = play_game(game, &mut SlowPlayer(Duration::from_millis(100)), &mut RandomPlayer);
+        assert_eq!(finished.termination, Termination::Timeout);
+        assert_eq!(finished.outcome, Outcome::BlackWins);
+
+        let mut game = Game::new();
+        game.load_fen_board("4k3/8/8/8/8/8/4P3/4KN2 w - - 0 1".to_string());
+        game.set_clock(Clock::parse("0.05"));
+        let finished = play_game(game, &mut SlowPlayer(Duration::from_millis(100)), &mut RandomPlayer);
+        assert_eq!(finished.termination, Termination::Timeout);
+        assert_eq!(finished.outcome, Outcome::Draw);
+    }
+}
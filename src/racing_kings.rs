@@ -0,0 +1,135 @@
+use crate::eval;
+use crate::result::GameResult;
+use crate::variant::Variant;
+use crate::{Game, Move, ColorTurn, _WHITE, _BLACK};
+
+/* The bonus for a king on each rank on its way to the eighth */
+const RANK_BONUS: [i32; 8] = [0, 30, 70, 120, 180, 260, 360, 0];
+
+/**
+ * Racing Kings, both players start on the first two ranks and race their kings to the
+ * eighth rank. No player may ever give check. If the white king gets there first black
+ * has one move left to get there too, which draws the game
+ */
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RacingKings;
+
+impl Variant for RacingKings {
+    fn name(&self) -> &str {
+        "Racing Kings"
+    }
+
+    fn start_fen(&self) -> &str {
+        "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1"
+    }
+
+    fn is_legal(&self, game: &Game) -> bool {
+        !game.is_own_king_left_in_check() && !game.is_king_attacked()
+    }
+
+    fn outcome(&self, game: &Game) -> Option<GameResult> {
+        let on_goal = |color: u8| game.find_king(color).is_some_and(|king| king / 8 == 7);
+        match (on_goal(_WHITE), on_goal(_BLACK)) {
+            (true, true) => Some(GameResult::Draw),
+            (false, true) => Some(GameResult::win_for(ColorTurn::Black)),
+            (true, false) => {
+                // black may still catch up with the move left
+                let catches_up = game.get_game_turn() == ColorTurn::Black && can_reach_goal(game);
+                if catches_up { None } else { Some(GameResult::win_for(ColorTurn::White)) }
+            }
+            (false, false) => None,
+        }
+    }
+
+    fn evaluate(&self, game: &Game) -> i32 {
+        let mut score = eval::standard_evaluation(game);
+        for color in [_WHITE, _BLACK] {
+            if let Some(king) = game.find_king(color) {
+                let bonus = RANK_BONUS[(king / 8) as usize];
+                score += if color == game.color_to_move() { bonus } else { -bonus };
+            }
+        }
+        score
+    }
+}
+
+/**
+ * Checks if the black king can step onto the eighth rank, by trying the few king moves
+ * that get there instead of every move black has
+ */
+fn can_reach_goal(game: &Game) -> bool {
+    let king = match game.find_king(_BLACK) {
+        Some(king) if king / 8 == 6 => king,
+        _ => return false,
+    };
+    let mut position = game.clone();
+    for file in (king % 8).saturating_sub(1)..=(king % 8 + 1).min(7) {
+        let _to = 56 + file;
+        if position.board[_to as usize][1] == _BLACK {
+            continue;
+        }
+        position.do_move(Move::new(king, _to));
+        let legal = position.is_position_legal();
+        position.undo_move();
+        if legal {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RacingKings;
+    use crate::perft;
+    use crate::player::Termination;
+    use crate::result::GameResult;
+    use crate::Game;
+    use std::sync::Arc;
+
+    fn start(fen: &str) -> Game {
+        let mut game = Game::new();
+        game.set_variant(Arc::new(RacingKings));
+        game.load_fen_board(fen.to_string());
+        game
+    }
+
+    #[test]
+    fn counts_moves_from_the_start() {
+        let mut game = Game::new();
+        game.set_variant(Arc::new(RacingKings));
+        game.init_board();
+        assert_eq!(perft(&mut game, 1), 21);
+        assert_eq!(perft(&mut game, 2), 421);
+    }
+
+    #[test]
+    fn checks_are_not_allowed() {
+        let game = start("8/8/8/8/8/k7/8/1R5K w - - 0 1");
+        assert!(game.parse_san("Ra1").is_none());
+        assert!(game.parse_san("Rb3").is_none());
+        assert!(game.parse_san("Rb2").is_some());
+    }
+
+    #[test]
+    fn black_gets_one_move_to_catch_up() {
+        let mut game = start("8/6K1/8/8/8/8/k7/8 w - - 0 1");
+        game.play(game.parse_san("Kg8").unwrap());
+        assert_eq!(game.result(), GameResult::WhiteWins);
+        assert_eq!(game.termination(), Some(Termination::Variant));
+
+        let mut game = start("8/k5K1/8/8/8/8/8/8 w - - 0 1");
+        game.play(game.parse_san("Kg8").unwrap());
+        assert_eq!(game.result(), GameResult::Ongoing);
+        game.play(game.parse_san("Kb8").unwrap());
+        assert_eq!(game.result(), GameResult::Draw);
+        game.undo();
+        game.play(game.parse_san("Kb6").unwrap());
+        assert_eq!(game.result(), GameResult::WhiteWins);
+
+        // the rook guards b8 and the knight stands on a8, so black can not get there
+        let mut game = start("n7/k5K1/8/8/8/8/8/1R6 w - - 0 1");
+        game.play(game.parse_san("Kg8").unwrap());
+        assert_eq!(game.result(), GameResult::WhiteWins);
+    }
+}
@@ -0,0 +1,209 @@
+use std::io::{self, IsTerminal};
+
+use crate::{Game, ColorTurn, _NONE, _PAWN, _BISHOP, _KNIGHT, _ROOK, _QUEEN, _KING, _WHITE};
+
+/* The 256 color backgrounds of the light and dark squares */
+const LIGHT_SQUARE: u8 = 180;
+const DARK_SQUARE: u8 = 137;
+/* The backgrounds of the squares of the last move, a king in check and the moves of the selected piece */
+const LAST_MOVE: u8 = 143;
+const CHECK: u8 = 160;
+const TARGET: u8 = 108;
+/* The 256 color foregrounds of the white and the black pieces */
+const WHITE_PIECE: u8 = 231;
+const BLACK_PIECE: u8 = 16;
+
+/**
+ * How Game::render draws the board. The default draws Unicode pieces on colored squares
+ * with White at the bottom, and RenderOptions::ascii draws letters and nothing else
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderOptions {
+    /// Unicode chess pieces instead of the fen letters, "KQRBNP" for white and "kqrbnp" for black.
+    pub unicode: bool,
+    /// Colors the squares and pieces with ANSI escape codes.
+    pub colors: bool,
+    /// The player whose side of the board is at the bottom.
+    pub perspective: ColorTurn,
+    pub coordinates: bool,
+    pub highlight_last_move: bool,
+    pub highlight_check: bool,
+    /// Shows where the piece on the square can move.
+    pub selected: Option<u8>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            unicode: true,
+            colors: true,
+            perspective: ColorTurn::White,
+            coordinates: true,
+            highlight_last_move: true,
+            highlight_check: true,
+            selected: None,
+        }
+    }
+}
+
+impl RenderOptions {
+
+    /// Letters and dots only, for terminals and logs that show neither Unicode nor colors.
+    pub fn ascii() -> RenderOptions {
+        RenderOptions { unicode: false, colors: false, ..RenderOptions::default() }
+    }
+}
+
+/**
+ * The symbol of the piece. Without colors the white pieces are drawn hollow and the black
+ * pieces filled, with colors both are filled and told apart by their color
+ */
+fn piece_symbol(piece: u8, color: u8, options: &RenderOptions) -> char {
+    if !options.unicode {
+        return if piece == _NONE { '.' } else { Game::fen_symbol(piece, color) };
+    }
+    let hollow = color == _WHITE && !options.colors;
+    match (piece, hollow) {
+        (_KING, true) => '♔',
+        (_QUEEN, true) => '♕',
+        (_ROOK, true) => '♖',
+        (_BISHOP, true) => '♗',
+        (_KNIGHT, true) => '♘',
+        (_PAWN, true) => '♙',
+        (_KING, false) => '♚',
+        (_QUEEN, false) => '♛',
+        (_ROOK, false) => '♜',
+        (_BISHOP, false) => '♝',
+        (_KNIGHT, false) => '♞',
+        (_PAWN, false) => '♟',
+        // Unicode has no fairy pieces, so they keep their letters
+        (_NONE, _) => '·',
+        _ => Game::fen_symbol(piece, color),
+    }
+}
+
+impl Game {
+
+    /**
+     * Draws the board as text, one rank to a line, see RenderOptions. Without colors a king
+     * in check is drawn in brackets, "[K]", and the squares the selected piece can move to
+     * with a "*", or in parentheses if a piece is taken there. The last move is only shown
+     * with colors
+     */
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let white_bottom = options.perspective == ColorTurn::White;
+        let last_move = self.history.last().filter(|_| options.highlight_last_move).map(|undo| undo.mv);
+        let checked_king = if options.highlight_check && self.is_king_check() { self.find_king(self.color_to_move()) } else { None };
+        let targets: Vec<u8> = match options.selected {
+            Some(selected) => self.legal_moves().into_iter().filter(|mv| mv.from == selected).map(|mv| mv.to).collect(),
+            None => Vec::new(),
+        };
+
+        let geometry = self.geometry;
+        let ranks: Vec<u8> = if white_bottom { (0..geometry.ranks).rev().collect() } else { (0..geometry.ranks).collect() };
+        let files: Vec<u8> = if white_bottom { (0..geometry.files).collect() } else { (0..geometry.files).rev().collect() };
+
+        let mut text = String::new();
+        for rank in &ranks {
+            if options.coordinates {
+                text.push_str(&format!("{:<width$}", rank + 1, width = if geometry.ranks > 9 { 3 } else { 2 }));
+            }
+            for file in &files {
+                let _pos = rank * geometry.files + file;
+                let (piece, color) = (self.board[_pos as usize][0], self.board[_pos as usize][1]);
+                let symbol = piece_symbol(piece, color, options);
+                let is_target = targets.contains(&_pos);
+
+                if options.colors {
+                    let background = if Some(_pos) == checked_king {
+                        CHECK
+                    } else if is_target {
+                        TARGET
+                    } else if last_move.is_some_and(|mv| mv.from == _pos || mv.to == _pos) {
+                        LAST_MOVE
+                    } else if (rank + file) % 2 == 1 {
+                        LIGHT_SQUARE
+                    } else {
+                        DARK_SQUARE
+                    };
+                    let foreground = if color == _WHITE { WHITE_PIECE } else { BLACK_PIECE };
+                    text.push_str(&format!("\x1b[48;5;{}m\x1b[38;5;{}m {} \x1b[0m", background, foreground, symbol));
+                } else if Some(_pos) == checked_king {
+                    text.push_str(&format!("[{}]", symbol));
+                } else if is_target && piece == _NONE {
+                    text.push_str(" * ");
+                } else if is_target {
+                    text.push_str(&format!("({})", symbol));
+                } else {
+                    text.push_str(&format!(" {} ", symbol));
+                }
+            }
+            text.push('\n');
+        }
+        if options.coordinates {
+            text.push_str(if geometry.ranks > 9 { "   " } else { "  " });
+            for file in &files {
+                text.push_str(&format!(" {} ", (b'a' + file) as char));
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /**
+     * Prints the board in the console, with Unicode and colors when the output is a
+     * terminal and as plain letters when it is not
+     */
+    pub fn print_board(&self) {
+        let options = if io::stdout().is_terminal() { RenderOptions::default() } else { RenderOptions::ascii() };
+        print!("{}", self.render(&options));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RenderOptions;
+    use crate::{ColorTurn, Game};
+
+    fn start() -> Game {
+        let mut game = Game::new();
+        game.init_board();
+        game
+    }
+
+    #[test]
+    fn draws_the_board_in_letters() {
+        let game = start();
+        let text = game.render(&RenderOptions::ascii());
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "8  r  n  b  q  k  b  n  r ");
+        assert_eq!(lines[4], "4  .  .  .  .  .  .  .  . ");
+        assert_eq!(lines[7], "1  R  N  B  Q  K  B  N  R ");
+        assert_eq!(lines[8], "   a  b  c  d  e  f  g  h ");
+
+        let flipped = game.render(&RenderOptions { perspective: ColorTurn::Black, ..RenderOptions::ascii() });
+        assert!(flipped.starts_with("1  R  N  B  K  Q  B  N  R \n"));
+        assert!(flipped.ends_with("   h  g  f  e  d  c  b  a \n"));
+
+        let unicode = game.render(&RenderOptions { colors: false, coordinates: false, ..RenderOptions::default() });
+        assert_eq!(unicode.lines().next(), Some(" ♜  ♞  ♝  ♛  ♚  ♝  ♞  ♜ "));
+    }
+
+    #[test]
+    fn highlights_checks_and_moves() {
+        let mut game = start();
+        for text in ["e2e4", "f7f6", "d1h5"] {
+            game.play(game.parse_move(text).unwrap());
+        }
+        let text = game.render(&RenderOptions { coordinates: false, ..RenderOptions::ascii() });
+        assert_eq!(text.lines().next(), Some(" r  n  b  q [k] b  n  r "));
+
+        // the g pawn can only stop the check on g6, and the last move is colored
+        let options = RenderOptions { selected: Some(54), ..RenderOptions::ascii() };
+        assert_eq!(game.render(&options).lines().nth(2), Some("6  .  .  .  .  .  p  *  . "));
+        let colored = game.render(&RenderOptions::default());
+        assert!(colored.contains("\x1b[48;5;143m"));
+        assert!(colored.contains("\x1b[48;5;160m"));
+    }
+}
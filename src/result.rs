@@ -0,0 +1,426 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::player::Termination;
+use crate::{Game, GameState, ColorTurn, _NONE, _BISHOP, _KNIGHT, _KING};
+
+/**
+ * The result of a game the way PGN writes it. Display writes "1-0", "0-1", "1/2-1/2"
+ * or "*", and the alternate form {:#} writes a draw as "½-½"
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Ongoing,
+}
+
+impl GameResult {
+
+    /// The result where the player has won.
+    pub fn win_for(color: ColorTurn) -> GameResult {
+        if color == ColorTurn::White { GameResult::WhiteWins } else { GameResult::BlackWins }
+    }
+
+    /// Reads "1-0", "0-1", "1/2-1/2", "½-½" or "*".
+    pub fn parse(text: &str) -> Option<GameResult> {
+        match text.trim() {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" | "½-½" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Ongoing),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw if f.alternate() => "½-½",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Ongoing => "*",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// Something a player or the arbiter did in a game besides moving.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    Resign(ColorTurn),
+    OfferDraw(ColorTurn),
+    AcceptDraw(ColorTurn),
+    DeclineDraw(ColorTurn),
+    /// The arbiter set the result, and why.
+    SetResult(GameResult, String),
+}
+
+fn other(color: ColorTurn) -> ColorTurn {
+    if color == ColorTurn::White { ColorTurn::Black } else { ColorTurn::White }
+}
+
+impl Game {
+
+    /**
+     * The events of the game, each with the number of moves that had been played when it
+     * happened. Events after a move are taken back with the move by undo
+     */
+    pub fn events(&self) -> &[(usize, GameEvent)] {
+        &self.events
+    }
+
+    fn push_event(&mut self, event: GameEvent) {
+        self.events.push((self.history.len(), event));
+    }
+
+    /// Checks if the game is decided, by the board, the clock, or an event.
+    fn is_decided(&self) -> bool {
+        self.result() != GameResult::Ongoing
+    }
+
+    /**
+     * The player resigns and the other player wins. Returns false if the game was already over
+     */
+    pub fn resign(&mut self, color: ColorTurn) -> bool {
+        if self.is_decided() {
+            return false;
+        }
+        self.push_event(GameEvent::Resign(color));
+        self.state = GameState::GameOver;
+        true
+    }
+
+    /**
+     * The player offers a draw, the offer stands until the other player accepts or declines
+     * it or makes a move. Returns false if the game is over or the player has an offer standing
+     */
+    pub fn offer_draw(&mut self, color: ColorTurn) -> bool {
+        if self.is_decided() || self.draw_offer() == Some(color) {
+            return false;
+        }
+        self.push_event(GameEvent::OfferDraw(color));
+        true
+    }
+
+    /**
+     * The player that has offered a draw the other player can still accept, if any
+     */
+    pub fn draw_offer(&self) -> Option<ColorTurn> {
+        let (ply, event) = self.events.iter().rev()
+            .find(|(_, event)| matches!(event, GameEvent::OfferDraw(_) | GameEvent::AcceptDraw(_) | GameEvent::DeclineDraw(_)))?;
+        let by = match event {
+            GameEvent::OfferDraw(by) => *by,
+            _ => return None,
+        };
+        // the last move was made by the player not to move, the one before by the player to move
+        let played = self.history.len();
+        let mover = |index: usize| if (played - 1 - index).is_multiple_of(2) { other(self.turn) } else { self.turn };
+        if (*ply..played).any(|index| mover(index) != by) {
+            return None;
+        }
+        Some(by)
+    }
+
+    /**
+     * The player accepts the draw the other player offered. Returns false if there is no offer
+     */
+    pub fn accept_draw(&mut self, color: ColorTurn) -> bool {
+        if self.is_decided() || self.draw_offer() != Some(other(color)) {
+            return false;
+        }
+        self.push_event(GameEvent::AcceptDraw(color));
+        self.state = GameState::GameOver;
+        true
+    }
+
+    /**
+     * The player declines the draw the other player offered. Returns false if there is no offer
+     */
+    pub fn decline_draw(&mut self, color: ColorTurn) -> bool {
+        if self.draw_offer() != Some(other(color)) {
+            return false;
+        }
+        self.push_event(GameEvent::DeclineDraw(color));
+        true
+    }
+
+    /**
+     * The arbiter sets the result for the reason, which stands over anything else.
+     * Setting the result to Ongoing lets the game go on if the board allows it
+     */
+    pub fn set_result(&mut self, result: GameResult, reason: &str) {
+        self.push_event(GameEvent::SetResult(result, reason.to_string()));
+        if result == GameResult::Ongoing {
+            self.update_state();
+        } else {
+            self.state = GameState::GameOver;
+        }
+    }
+
+    /**
+     * The result of the game, from the last event that ended it, or else from the board
+     * and the clock
+     */
+    pub fn result(&self) -> GameResult {
+        self.result_and_termination().0
+    }
+
+    /// Why the game ended, None while it goes on.
+    pub fn termination(&self) -> Option<Termination> {
+        self.result_and_termination().1
+    }
+
+    /**
+     * Checks if the position has been on the board three times with the same player to
+     * move. Only the moves since the last capture or pawn move can lead back to it
+     */
+    pub fn is_threefold_repetition(&self) -> bool {
+        let hash = self.position_hash();
+        let mut position = self.clone();
+        let mut seen = 1;
+        for _ in 0..(self.halfmove_clock as usize).min(self.history.len()) {
+            position.undo_move();
+            if position.position_hash() == hash {
+                seen += 1;
+            }
+        }
+        seen >= 3
+    }
+
+    /**
+     * A hash of the position, the Polyglot hash on a standard board. Polyglot only knows
+     * the 64 squares and the standard pieces, so other boards hash the fields themselves.
+     * Pieces in hand and given checks belong to the position too, so they are added
+     * whenever the variant has pockets or a check has been counted
+     */
+    fn position_hash(&self) -> u64 {
+        let in_hand = self.variant.has_pockets() || self.checks.iter().any(|checks| *checks > 0);
+        if !self.uses_piece_definitions() && !in_hand {
+            return self.polyglot_hash();
+        }
+        let mut hasher = DefaultHasher::new();
+        if self.uses_piece_definitions() {
+            (&self.board, self.turn == ColorTurn::White, &self.castling, &self.last_moved_pawn).hash(&mut hasher);
+        } else {
+            self.polyglot_hash().hash(&mut hasher);
+        }
+        if in_hand {
+            (&self.pockets, self.promoted, &self.checks).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /**
+     * Checks if neither player can win with the material left, by the rule of the variant,
+     * see Variant::insufficient_material
+     */
+    pub fn is_insufficient_material(&self) -> bool {
+        self.variant.insufficient_material(self)
+    }
+
+    /**
+     * The rule of standard chess, only the kings and one bishop or knight are left, or
+     * only bishops that all stand on squares of one color
+     */
+    pub(crate) fn has_no_mating_material(&self) -> bool {
+        let mut knights = 0;
+        let mut bishop_squares = [0, 0];
+        for (_pos, square) in self.board.iter().enumerate() {
+            match square[0] {
+                _NONE | _KING => {}
+                _KNIGHT => knights += 1,
+                _BISHOP => bishop_squares[(_pos % 8 + _pos / 8) % 2] += 1,
+                _ => return false,
+            }
+        }
+        let bishops = bishop_squares[0] + bishop_squares[1];
+        knights + bishops <= 1 || (knights == 0 && bishop_squares.contains(&0))
+    }
+
+    /**
+     * The result the position on the board decides, by a rule of the variant, checkmate or
+     * stalemate, the fifty move rule, threefold repetition or insufficient material. None
+     * if the game goes on
+     */
+    pub(crate) fn board_result(&self) -> Option<(GameResult, Termination)> {
+        if let Some(result) = self.variant.outcome(self) {
+            return Some((result, Termination::Variant));
+        }
+        if self.legal_moves().is_empty() {
+            let termination = if self.is_king_check() { Termination::Checkmate } else { Termination::Stalemate };
+            return Some((self.variant.no_moves_result(self), termination));
+        }
+        if self.halfmove_clock >= 100 {
+            return Some((GameResult::Draw, Termination::FiftyMoves));
+        }
+        if self.is_threefold_repetition() {
+            return Some((GameResult::Draw, Termination::Repetition));
+        }
+        if self.is_insufficient_material() {
+            return Some((GameResult::Draw, Termination::InsufficientMaterial));
+        }
+        None
+    }
+
+    fn result_and_termination(&self) -> (GameResult, Option<Termination>) {
+        for (_, event) in self.events.iter().rev() {
+            match event {
+                GameEvent::Resign(color) => return (GameResult::win_for(other(*color)), Some(Termination::Resignation)),
+                GameEvent::AcceptDraw(_) => return (GameResult::Draw, Some(Termination::DrawAgreement)),
+                GameEvent::SetResult(GameResult::Ongoing, _) => break,
+                GameEvent::SetResult(result, _) => return (*result, Some(Termination::Arbiter)),
+                _ => {}
+            }
+        }
+
+        if let Some((result, termination)) = self.board_result() {
+            return (result, Some(termination));
+        }
+        if let Some(color) = self.flagged() {
+            let winner = other(color);
+            let result = if self.can_mate(winner) { GameResult::win_for(winner) } else { GameResult::Draw };
+            return (result, Some(Termination::Timeout));
+        }
+        (GameResult::Ongoing, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameEvent, GameResult};
+    use crate::crazyhouse::Crazyhouse;
+    use crate::player::Termination;
+    use crate::{ColorTurn, Game};
+    use std::sync::Arc;
+
+    fn start() -> Game {
+        let mut game = Game::new();
+        game.init_board();
+        game
+    }
+
+    fn play(game: &mut Game, moves: &str) {
+        for text in moves.split_whitespace() {
+            let mv = game.parse_move(text).unwrap();
+            assert!(game.play(mv));
+        }
+    }
+
+    #[test]
+    fn resigning_ends_the_game() {
+        let mut game = start();
+        play(&mut game, "e2e4");
+        assert!(game.resign(ColorTurn::Black));
+        assert_eq!(game.result(), GameResult::WhiteWins);
+        assert_eq!(game.termination(), Some(Termination::Resignation));
+        assert!(!game.play(game.parse_move("e7e5").unwrap()));
+        assert!(!game.resign(ColorTurn::White));
+
+        // taking back the move takes back the resignation too
+        game.undo();
+        assert_eq!(game.result(), GameResult::Ongoing);
+        assert!(game.events().is_empty());
+    }
+
+    #[test]
+    fn draw_offers_expire_when_the_other_player_moves() {
+        let mut game = start();
+        assert!(game.offer_draw(ColorTurn::White));
+        play(&mut game, "e2e4");
+        assert_eq!(game.draw_offer(), Some(ColorTurn::White));
+        play(&mut game, "e7e5");
+        assert_eq!(game.draw_offer(), None);
+        assert!(!game.accept_draw(ColorTurn::Black));
+
+        assert!(game.offer_draw(ColorTurn::White));
+        assert!(game.decline_draw(ColorTurn::Black));
+        assert!(!game.accept_draw(ColorTurn::Black));
+        assert!(game.offer_draw(ColorTurn::White));
+        assert!(game.accept_draw(ColorTurn::Black));
+        assert_eq!(game.result(), GameResult::Draw);
+        assert_eq!(format!("{:#}", game.result()), "½-½");
+        assert_eq!(game.events()[0], (0, GameEvent::OfferDraw(ColorTurn::White)));
+    }
+
+    #[test]
+    fn the_arbiter_and_the_board_decide_results() {
+        let mut game = start();
+        play(&mut game, "f2f3 e7e5 g2g4 d8h4");
+        assert_eq!(game.result().to_string(), "0-1");
+        assert_eq!(game.termination(), Some(Termination::Checkmate));
+
+        let mut game = start();
+        game.set_result(GameResult::WhiteWins, "black did not show up");
+        assert_eq!(game.termination(), Some(Termination::Arbiter));
+        game.set_result(GameResult::Ongoing, "the game goes on after all");
+        assert_eq!(game.result(), GameResult::Ongoing);
+        play(&mut game, "e2e4");
+        assert_eq!(GameResult::parse("1/2-1/2"), Some(GameResult::Draw));
+    }
+
+    #[test]
+    fn repetition_and_bare_kings_are_draws() {
+        let mut game = start();
+        play(&mut game, "g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1");
+        assert_eq!(game.result(), GameResult::Ongoing);
+        play(&mut game, "f6g8");
+        assert_eq!(game.result(), GameResult::Draw);
+        assert_eq!(game.termination(), Some(Termination::Repetition));
+
+        let mut game = Game::new();
+        game.load_fen_board("8/8/4k3/8/8/2B5/4K3/4b3 w - - 0 1".to_string());
+        assert_eq!(game.termination(), Some(Termination::InsufficientMaterial));
+        game.load_fen_board("8/8/4k3/8/8/2B5/4K3/5b2 w - - 0 1".to_string());
+        assert_eq!(game.result(), GameResult::Ongoing);
+    }
+
+    #[test]
+    fn mating_material_depends_on_the_other_side() {
+        let mut game = Game::new();
+        game.load_fen_board("8/8/4k3/8/8/2N5/4K3/8 w - - 0 1".to_string());
+        assert!(!game.can_mate(ColorTurn::White));
+        // the black pawn can block its own king
+        game.load_fen_board("8/8/4k3/4p3/8/2N5/4K3/8 w - - 0 1".to_string());
+        assert!(game.can_mate(ColorTurn::White));
+        // bishops on one color only mate with help from a piece of the other side
+        game.load_fen_board("8/8/4k3/8/8/2B5/4K3/4b3 w - - 0 1".to_string());
+        assert!(!game.can_mate(ColorTurn::White));
+        game.load_fen_board("8/8/4k3/8/8/2B5/4K3/5b2 w - - 0 1".to_string());
+        assert!(game.can_mate(ColorTurn::White));
+        game.load_fen_board("8/8/4k3/8/8/2BB4/4K3/8 w - - 0 1".to_string());
+        assert!(game.can_mate(ColorTurn::White));
+
+        // a lone king in crazyhouse can still take the knight and drop it
+        let mut game = Game::new();
+        game.set_variant(Arc::new(Crazyhouse));
+        game.load_fen_board("8/8/4k3/8/8/2N5/4K3/8 w - - 0 1".to_string());
+        assert!(game.can_mate(ColorTurn::Black));
+        game.load_fen_board("8/8/4k3/8/8/8/4K3/8 w - - 0 1".to_string());
+        assert!(!game.can_mate(ColorTurn::White));
+    }
+
+    #[test]
+    fn pockets_and_checks_are_part_of_the_position() {
+        let mut game = Game::new();
+        game.set_variant(Arc::new(Crazyhouse));
+        game.init_board();
+        let empty = game.position_hash();
+        game.pockets[0][1] = 1;
+        assert_ne!(game.position_hash(), empty);
+        game.pockets[0][1] = 0;
+        game.promoted = 1;
+        assert_ne!(game.position_hash(), empty);
+
+        let mut game = start();
+        let unchecked = game.position_hash();
+        game.checks[0] = 1;
+        assert_ne!(game.position_hash(), unchecked);
+        game.checks[0] = 0;
+        assert_eq!(game.position_hash(), unchecked);
+    }
+}
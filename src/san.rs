@@ -0,0 +1,187 @@
+use crate::{Game, Move, GameState, _NONE, _PAWN, _BISHOP, _KNIGHT, _ROOK, _QUEEN, _KING, _ARCHBISHOP, _CHANCELLOR, _AMAZON, _CAMEL};
+
+/**
+ * Returns the letter used for a piece in standard algebraic notation, pawns have no letter
+ */
+fn piece_letter(piece: u8) -> &'static str {
+    match piece {
+        _KNIGHT => "N",
+        _BISHOP => "B",
+        _ROOK => "R",
+        _QUEEN => "Q",
+        _KING => "K",
+        _ARCHBISHOP => "A",
+        _CHANCELLOR => "C",
+        _AMAZON => "M",
+        _CAMEL => "L",
+        _ => "",
+    }
+}
+
+impl Game {
+
+    /**
+     * Writes a legal move in standard algebraic notation, for example "Nf3", "exd5",
+     * "O-O", "e8=Q+" or the drop "N@f3". The move is only disambiguated with the file or rank if another
+     * piece of the same kind can move to the same square
+     */
+    pub fn move_to_san(&self, mv: Move) -> String {
+        if mv.is_drop() {
+            return format!("{}{}", mv.to_uci(), self.check_suffix(mv));
+        }
+        let piece = self.board[mv.from as usize][0];
+
+        if self.castling_squares(mv).is_some() {
+            let mut san = if mv.to > mv.from { "O-O".to_string() } else { "O-O-O".to_string() };
+            san.push_str(&self.check_suffix(mv));
+            return san;
+        }
+
+        let legal_moves = self.legal_moves();
+        let files = self.geometry.files;
+        let is_capture = self.board[mv.to as usize][0] != _NONE || (piece == _PAWN && mv.from % files != mv.to % files);
+        let from_name = self.geometry.square_name(mv.from);
+        let mut san = piece_letter(piece).to_string();
+
+        if piece == _PAWN {
+            if is_capture {
+                san.push_str(&from_name[0..1]);
+            }
+        } else {
+            let others: Vec<&Move> = legal_moves.iter()
+                .filter(|other| other.to == mv.to && other.from != mv.from && self.board[other.from as usize][0] == piece)
+                .collect();
+            if !others.is_empty() {
+                if others.iter().all(|other| other.from % files != mv.from % files) {
+                    san.push_str(&from_name[0..1]);
+                } else if others.iter().all(|other| other.from / files != mv.from / files) {
+                    san.push_str(&from_name[1..]);
+                } else {
+                    san.push_str(&from_name);
+                }
+            }
+        }
+
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&self.geometry.square_name(mv.to));
+        if mv.promotion != _NONE {
+            san.push('=');
+            san.push_str(piece_letter(mv.promotion));
+        }
+        san.push_str(&self.check_suffix(mv));
+        san
+    }
+
+    /// Returns "#" if the move mates, "+" if it checks and "" otherwise.
+    fn check_suffix(&self, mv: Move) -> String {
+        let mut after = self.clone();
+        after.do_move(mv);
+        after.update_state();
+        match after.state {
+            GameState::GameOver if after.is_king_check() => "#".to_string(),
+            _ if after.is_king_check() => "+".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /**
+     * Finds the legal move written in standard algebraic notation, for example "Nbd2",
+     * "exd6", "O-O-O", "e8=Q" or the drop "N@f3". Check marks and annotations such as "+", "#" and "!?"
+     * are ignored, and "0-0" is read as "O-O"
+     */
+    pub fn parse_san(&self, san: &str) -> Option<Move> {
+        let san = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        if san.contains('@') {
+            return self.parse_move(san);
+        }
+        let legal_moves = self.legal_moves();
+
+        if san == "O-O" || san == "0-0" || san == "O-O-O" || san == "0-0-0" {
+            let long = san.len() == 5;
+            return legal_moves.into_iter().find(|mv| self.castling_squares(*mv).is_some() && (mv.to < mv.from) == long);
+        }
+
+        let mut chars: Vec<char> = san.chars().filter(|c| *c != 'x' && *c != '=' && *c != '-').collect();
+        let piece = match chars.first() {
+            Some('N') => _KNIGHT,
+            Some('B') => _BISHOP,
+            Some('R') => _ROOK,
+            Some('Q') => _QUEEN,
+            Some('K') => _KING,
+            Some('A') => _ARCHBISHOP,
+            Some('C') => _CHANCELLOR,
+            Some('M') => _AMAZON,
+            Some('L') => _CAMEL,
+            Some(_) => _PAWN,
+            None => return None,
+        };
+        if piece != _PAWN {
+            chars.remove(0);
+        }
+
+        let mut promotion = _NONE;
+        if let Some(last) = chars.last() {
+            if last.is_ascii_uppercase() || (piece == _PAWN && "nbrqacml".contains(*last) && chars.len() > 2) {
+                promotion = Game::piece_from_symbol(last.to_ascii_lowercase());
+                chars.pop();
+            }
+        }
+
+        // the target square is the file letter before the digits at the end, boards with more than nine ranks have two
+        let digits = chars.iter().rev().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 || chars.len() <= digits {
+            return None;
+        }
+        let target: String = chars[chars.len() - digits - 1..].iter().collect();
+        let _to = self.geometry.parse_square(&target)?;
+        let hints = &chars[..chars.len() - digits - 1];
+        let files = self.geometry.files;
+        let from_file = hints.iter().find(|c| c.is_ascii_lowercase()).map(|c| *c as u8 - b'a');
+        let from_rank = hints.iter().filter(|c| c.is_ascii_digit()).collect::<String>().parse::<u8>().ok().map(|rank| rank.wrapping_sub(1));
+
+        let mut candidates = legal_moves.into_iter().filter(|mv| {
+            mv.to == _to
+                && !mv.is_drop()
+                && self.board[mv.from as usize][0] == piece
+                && from_file.is_none_or(|file| mv.from % files == file)
+                && from_rank.is_none_or(|rank| mv.from / files == rank)
+                && (mv.promotion == promotion || (promotion == _NONE && mv.promotion == _QUEEN))
+        });
+        let found = candidates.next()?;
+        match candidates.next() {
+            Some(_) => None,
+            None => Some(found),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Game;
+
+    #[test]
+    fn san_round_trips() {
+        let mut game = Game::new();
+        game.init_board();
+        for san in ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O", "Bg4"] {
+            let mv = game.parse_san(san).unwrap();
+            assert_eq!(game.move_to_san(mv), san);
+            assert!(game.play(mv));
+        }
+    }
+
+    #[test]
+    fn san_disambiguates_and_promotes() {
+        let mut game = Game::new();
+        game.load_fen_board("4k3/1P6/8/8/8/8/4K3/R6R w - - 0 1".to_string());
+        let mv = game.parse_san("Rad1").unwrap();
+        assert_eq!(game.move_to_san(mv), "Rad1");
+        let mv = game.parse_san("b8=N").unwrap();
+        assert_eq!(game.move_to_san(mv), "b8=N");
+        let mv = game.parse_san("b8Q").unwrap();
+        assert_eq!(game.move_to_san(mv), "b8=Q+");
+        assert_eq!(game.parse_san("Rd1"), None);
+    }
+}
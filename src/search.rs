@@ -0,0 +1,472 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::eval::{self, PIECE_VALUES};
+use crate::tablebase::{self, Tablebase, Wdl};
+use crate::result::GameResult;
+use crate::{Game, Move, _NONE, _PAWN};
+
+/* Scores above MATE_BOUND are mates, MATE_SCORE minus the number of plies to the mate */
+const MATE_SCORE: i32 = 30000;
+const MATE_BOUND: i32 = 29000;
+const INFINITY: i32 = 32000;
+/* A tablebase win is scored below every mate, minus the ply it was found at */
+const TABLEBASE_WIN: i32 = MATE_BOUND - 1000;
+/* How many nodes are searched between each check of the time limit */
+const CHECK_INTERVAL: u64 = 1024;
+
+/**
+ * The score of a line, either in centipawns or as a forced mate.
+ *
+ * Both are seen from the player whose turn it is, Mate(3) means that player mates in 3 moves
+ * and Mate(-2) means that player gets mated in 2 moves
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    Mate(i32),
+}
+
+impl Score {
+    /// Changes a search value to a score, counting mates in full moves instead of plies.
+    fn from_value(value: i32) -> Score {
+        if value > MATE_BOUND {
+            Score::Mate((MATE_SCORE - value + 1) / 2)
+        } else if value < -MATE_BOUND {
+            Score::Mate(-(MATE_SCORE + value) / 2)
+        } else {
+            Score::Centipawns(value)
+        }
+    }
+}
+
+/// Prints the score like "+0.35" for centipawns and "#3" or "#-2" for mates.
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Score::Centipawns(cp) => write!(f, "{:+.2}", *cp as f64 / 100.0),
+            Score::Mate(moves) => write!(f, "#{}", moves),
+        }
+    }
+}
+
+/**
+ * One principal variation, the best line the search found starting with its first move
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct PvLine {
+    pub score: Score,
+    pub moves: Vec<Move>,
+}
+
+/**
+ * The result of an analysis after a completed search depth.
+ *
+ * The lines are sorted with the best line first, there are fewer lines than asked for
+ * if the position does not have that many legal moves
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    pub depth: u32,
+    pub nodes: u64,
+    pub time: Duration,
+    pub lines: Vec<PvLine>,
+}
+
+impl Analysis {
+    /// The first move of the best line, if there is any legal move.
+    pub fn best_move(&self) -> Option<Move> {
+        self.lines.first().and_then(|line| line.moves.first().copied())
+    }
+}
+
+/**
+ * Settings for an analysis, multipv is the number of lines to return.
+ *
+ * The search goes deeper one ply at a time until depth is reached, or until
+ * the node or time limit runs out or stop is set from another thread. The first
+ * depth is always searched to the end
+ */
+#[derive(Clone, Debug)]
+pub struct AnalysisOptions {
+    pub multipv: usize,
+    pub depth: u32,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> AnalysisOptions {
+        AnalysisOptions {
+            multipv: 1,
+            depth: 4,
+            nodes: None,
+            movetime: None,
+            stop: None,
+        }
+    }
+}
+
+/**
+ * The state of one running search, it owns its own copy of the game so it can make
+ * and take back moves without touching the game that is analysed
+ */
+struct Searcher<'a> {
+    game: Game,
+    options: &'a AnalysisOptions,
+    start: Instant,
+    nodes: u64,
+    can_stop: bool,
+    aborted: bool,
+    tablebase: Option<Arc<Tablebase>>,
+}
+
+impl<'a> Searcher<'a> {
+
+    /**
+     * Checks if the node or time limit has run out or the search was told to stop, once it has the
+     * search is aborted and every search function returns straight away
+     */
+    fn should_stop(&mut self) -> bool {
+        if self.aborted {
+            return true;
+        }
+        if !self.can_stop || !self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            return false;
+        }
+        if let Some(nodes) = self.options.nodes {
+            if self.nodes >= nodes {
+                self.aborted = true;
+            }
+        }
+        if let Some(movetime) = self.options.movetime {
+            if self.start.elapsed() >= movetime {
+                self.aborted = true;
+            }
+        }
+        if let Some(stop) = &self.options.stop {
+            if stop.load(Ordering::Relaxed) {
+                self.aborted = true;
+            }
+        }
+        self.aborted
+    }
+
+    /**
+     * The value of a finished game for the player to move, a win or a loss found closer
+     * to the root is worth more
+     */
+    fn result_value(&self, result: GameResult, ply: i32) -> i32 {
+        match result {
+            GameResult::Draw | GameResult::Ongoing => 0,
+            result if result == GameResult::win_for(self.game.get_game_turn()) => MATE_SCORE - ply,
+            _ => -MATE_SCORE + ply,
+        }
+    }
+
+    /// Checks if the move takes a piece or promotes a pawn.
+    fn is_tactical(&self, mv: Move) -> bool {
+        if mv.is_drop() {
+            return false;
+        }
+        let from = &self.game.board[mv.from as usize];
+        let to = &self.game.board[mv.to as usize];
+        (to[0] != _NONE && to[1] != from[1])
+            || mv.promotion != _NONE
+            || (from[0] == _PAWN && mv.from % self.game.geometry.files != mv.to % self.game.geometry.files)
+    }
+
+    /**
+     * Sorts the moves so the best guesses are searched first, which makes the
+     * alpha beta search cut off more of the tree. The move from the previous
+     * principal variation goes first, then captures with the most valuable victim
+     * and least valuable attacker, then promotions and last the quiet moves
+     */
+    fn order_moves(&self, moves: &mut [Move], pv_move: Option<Move>) {
+        moves.sort_by_cached_key(|mv| {
+            if Some(*mv) == pv_move {
+                return i32::MIN;
+            }
+            let victim = self.game.board[mv.to as usize][0];
+            let mut key = 0;
+            // a Chess960 king taking its own rook is castling, and drops never take
+            if victim != _NONE && self.game.castling_squares(*mv).is_none() {
+                let attacker = self.game.board[mv.from as usize][0];
+                key -= 10 * PIECE_VALUES[victim as usize] - PIECE_VALUES[attacker as usize];
+            }
+            if mv.promotion != _NONE && !mv.is_drop() {
+                key -= PIECE_VALUES[mv.promotion as usize];
+            }
+            key
+        });
+    }
+
+    /**
+     * Searches only captures and promotions until the position is quiet, so that the
+     * evaluation is never done in the middle of an exchange
+     */
+    fn quiescence(&mut self, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        if let Some(result) = self.game.variant().outcome(&self.game) {
+            return self.result_value(result, 0);
+        }
+        let stand_pat = eval::evaluate(&self.game);
+        if stand_pat >= beta {
+            return beta;
+        }
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+
+        let mut moves: Vec<Move> = self.game.generate_moves().into_iter().filter(|mv| self.is_tactical(*mv)).collect();
+        self.order_moves(&mut moves, None);
+        for mv in moves {
+            self.game.do_move(mv);
+            if !self.game.is_position_legal() {
+                self.game.undo_move();
+                continue;
+            }
+            let score = -self.quiescence(-beta, -alpha);
+            self.game.undo_move();
+            if self.aborted {
+                return 0;
+            }
+            if score >= beta {
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+        alpha
+    }
+
+    /**
+     * Alpha beta search of the current position to the given depth
+     *
+     * hint is the principal variation from the previous iteration seen from this node,
+     * its first move is searched first. The best line found is written to pv
+     */
+    fn negamax(&mut self, depth: u32, ply: i32, mut alpha: i32, beta: i32, hint: &[Move], pv: &mut Vec<Move>) -> i32 {
+        if self.game.halfmove_clock >= 100 {
+            return 0;
+        }
+        if depth == 0 {
+            return self.quiescence(alpha, beta);
+        }
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        // right after a capture or pawn move the tables give the exact result, the
+        // 50 move rule makes cursed wins and blessed losses draws
+        if ply > 0 && self.game.halfmove_clock == 0 {
+            if let Some(wdl) = self.tablebase.as_ref().and_then(|tablebase| tablebase.probe_wdl(&self.game)) {
+                return match wdl {
+                    Wdl::Win => TABLEBASE_WIN - ply,
+                    Wdl::Loss => -TABLEBASE_WIN + ply,
+                    _ => 0,
+                };
+            }
+        }
+
+        if ply > 0 {
+            if let Some(result) = self.game.variant().outcome(&self.game) {
+                return self.result_value(result, ply);
+            }
+        }
+
+        let in_check = self.game.is_king_check();
+        let mut moves = self.game.generate_moves();
+        self.order_moves(&mut moves, hint.first().copied());
+
+        let mut legal_moves = 0;
+        for mv in moves {
+            self.game.do_move(mv);
+            if !self.game.is_position_legal() {
+                self.game.undo_move();
+                continue;
+            }
+            legal_moves += 1;
+
+            let child_hint = if hint.first() == Some(&mv) { &hint[1..] } else { &[] };
+            let mut child_pv = Vec::new();
+            // a check is searched one ply deeper so the search does not stop in the middle of a mating attack
+            let extension = if in_check { 1 } else { 0 };
+            let score = -self.negamax(depth - 1 + extension, ply + 1, -beta, -alpha, child_hint, &mut child_pv);
+            self.game.undo_move();
+            if self.aborted {
+                return 0;
+            }
+
+            if score >= beta {
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(mv);
+                pv.append(&mut child_pv);
+            }
+        }
+
+        if legal_moves == 0 {
+            // checkmate or stalemate in standard chess, found closer to the root is better
+            return self.result_value(self.game.variant().no_moves_result(&self.game), ply);
+        }
+        alpha
+    }
+
+    /**
+     * Searches the root moves to the given depth and returns the best lines,
+     * the best line is found first and then searched again without its first move
+     * to find the second best line, and so on until there are multipv lines
+     *
+     * Returns None if the search was aborted before all of the lines were found
+     */
+    fn search_root(&mut self, root_moves: &[Move], depth: u32, previous: &[PvLine]) -> Option<Vec<PvLine>> {
+        let mut lines: Vec<PvLine> = Vec::new();
+        let count = self.options.multipv.max(1).min(root_moves.len());
+
+        while lines.len() < count {
+            let mut best: Option<(i32, Vec<Move>)> = None;
+            let mut alpha = -INFINITY;
+
+            for mv in root_moves {
+                if lines.iter().any(|line| line.moves[0] == *mv) {
+                    continue;
+                }
+                let hint: &[Move] = previous.iter()
+                    .find(|line| line.moves[0] == *mv)
+                    .map(|line| &line.moves[1..])
+                    .unwrap_or(&[]);
+
+                self.game.do_move(*mv);
+                let mut child_pv = Vec::new();
+                let score = -self.negamax(depth - 1, 1, -INFINITY, -alpha, hint, &mut child_pv);
+                self.game.undo_move();
+                if self.aborted {
+                    return None;
+                }
+
+                if best.is_none() || score > alpha {
+                    alpha = score;
+                    let mut moves = vec![*mv];
+                    moves.append(&mut child_pv);
+                    best = Some((score, moves));
+                }
+            }
+
+            let (score, moves) = best?;
+            lines.push(PvLine { score: Score::from_value(score), moves });
+        }
+        Some(lines)
+    }
+}
+
+impl Game {
+
+    /**
+     * Analyses the position and returns the best lines found at the deepest completed depth
+     */
+    pub fn analyse(&self, options: &AnalysisOptions) -> Analysis {
+        self.analyse_with(options, |_| {})
+    }
+
+    /**
+     * Analyses the position with iterative deepening, on_update is called with the lines
+     * every time a depth is completed so they can be shown while the search goes on
+     *
+     * Returns the analysis of the deepest completed depth, which is depth 0 without any
+     * lines if there are no legal moves
+     */
+    pub fn analyse_with<F: FnMut(&Analysis)>(&self, options: &AnalysisOptions, mut on_update: F) -> Analysis {
+        let mut searcher = Searcher {
+            game: self.clone(),
+            options,
+            start: Instant::now(),
+            nodes: 0,
+            can_stop: false,
+            aborted: false,
+            tablebase: tablebase::tablebase(),
+        };
+
+        let mut analysis = Analysis {
+            depth: 0,
+            nodes: 0,
+            time: Duration::from_secs(0),
+            lines: Vec::new(),
+        };
+        let mut root_moves = searcher.game.generate_legal_moves();
+        if root_moves.is_empty() {
+            return analysis;
+        }
+
+        for depth in 1..=options.depth.max(1) {
+            // search the best lines from the last depth first
+            for line in analysis.lines.iter().rev() {
+                if let Some(index) = root_moves.iter().position(|mv| *mv == line.moves[0]) {
+                    let mv = root_moves.remove(index);
+                    root_moves.insert(0, mv);
+                }
+            }
+
+            let lines = match searcher.search_root(&root_moves, depth, &analysis.lines) {
+                Some(lines) => lines,
+                None => break,
+            };
+            searcher.can_stop = true;
+
+            analysis = Analysis {
+                depth,
+                nodes: searcher.nodes,
+                time: searcher.start.elapsed(),
+                lines,
+            };
+            on_update(&analysis);
+        }
+        analysis.nodes = searcher.nodes;
+        analysis.time = searcher.start.elapsed();
+        analysis
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnalysisOptions, Score};
+    use crate::Game;
+
+    #[test]
+    fn finds_mate_in_one() {
+        let mut game = Game::new();
+        game.load_fen_board("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1".to_string());
+        let analysis = game.analyse(&AnalysisOptions { depth: 2, ..AnalysisOptions::default() });
+
+        assert_eq!(analysis.best_move().unwrap().to_uci(), "a1a8");
+        assert_eq!(analysis.lines[0].score, Score::Mate(1));
+    }
+
+    #[test]
+    fn returns_several_lines_best_first() {
+        let mut game = Game::new();
+        game.init_board();
+        let mut updates = 0;
+        let options = AnalysisOptions { multipv: 3, depth: 3, ..AnalysisOptions::default() };
+        let analysis = game.analyse_with(&options, |_| updates += 1);
+
+        assert_eq!(updates, 3);
+        assert_eq!(analysis.depth, 3);
+        assert_eq!(analysis.lines.len(), 3);
+        assert!(analysis.nodes > 0);
+        let first = analysis.lines[0].moves[0];
+        assert!(analysis.lines[1..].iter().all(|line| line.moves[0] != first));
+    }
+}
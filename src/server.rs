@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::ai::Ai;
+use crate::player::Termination;
+use crate::result::GameResult;
+use crate::{Game, ColorTurn};
+
+/**
+ * One game held by the server, history is the moves in SAN and resigned is the
+ * color that resigned, if any
+ */
+#[derive(Clone, Debug)]
+pub(crate) struct ServerGame {
+    pub(crate) game: Game,
+    pub(crate) history: Vec<String>,
+    pub(crate) resigned: Option<ColorTurn>,
+}
+
+impl ServerGame {
+
+    /// Starts a game from the fen, or from the start position.
+    pub(crate) fn new(fen: Option<&str>) -> ServerGame {
+        let mut game = Game::new();
+        match fen {
+            Some(fen) => game.load_fen_board(fen.to_string()),
+            None => game.init_board(),
+        }
+        ServerGame { game, history: Vec::new(), resigned: None }
+    }
+
+    /// The status of the game and its result, "*" while it goes on.
+    fn status(&self) -> (&'static str, &'static str) {
+        if let Some(color) = self.resigned {
+            return ("resigned", if color == ColorTurn::White { "0-1" } else { "1-0" });
+        }
+        if let Some((result, termination)) = self.game.board_result() {
+            let status = match termination {
+                Termination::Checkmate => "checkmate",
+                Termination::Stalemate => "stalemate",
+                Termination::FiftyMoves => "fifty_moves",
+                Termination::Repetition => "repetition",
+                Termination::InsufficientMaterial => "insufficient_material",
+                _ => "variant",
+            };
+            let result = match result {
+                GameResult::WhiteWins => "1-0",
+                GameResult::BlackWins => "0-1",
+                _ => "1/2-1/2",
+            };
+            return (status, result);
+        }
+        if self.game.is_king_check() {
+            return ("check", "*");
+        }
+        ("in_progress", "*")
+    }
+
+    pub(crate) fn is_over(&self) -> bool {
+        self.status().1 != "*"
+    }
+
+    /// The game as JSON, the same form for every answer.
+    pub(crate) fn to_json(&self) -> Value {
+        let (status, result) = self.status();
+        let legal_moves: Vec<String> = if self.is_over() {
+            Vec::new()
+        } else {
+            self.game.legal_moves().iter().map(|mv| mv.to_uci()).collect()
+        };
+        json!({
+            "fen": self.game.to_fen(),
+            "turn": if self.game.get_game_turn() == ColorTurn::White { "white" } else { "black" },
+            "status": status,
+            "result": result,
+            "legal_moves": legal_moves,
+            "history": self.history,
+        })
+    }
+
+    /// Plays a move and writes it down, the move must be legal.
+    pub(crate) fn play(&mut self, mv: crate::Move) {
+        self.history.push(self.game.move_to_san(mv));
+        self.game.play(mv);
+    }
+}
+
+fn with_id(mut game: Value, id: u64) -> Value {
+    game["id"] = json!(id);
+    game
+}
+
+fn error(status: u16, message: &str) -> (u16, Value) {
+    (status, json!({ "error": message }))
+}
+
+/**
+ * A game server that keeps games in memory by id and lets them be played over HTTP with JSON.
+ *
+ *  POST /games                  start a game, the body may give {"fen": "..."}
+ *  GET  /games/{id}             the fen, turn, status, result, legal moves and history
+ *  POST /games/{id}/move        play {"move": "e2e4"} or {"move": "Nf3"}
+ *  POST /games/{id}/ai-move     let the ai play the side to move
+ *  POST /games/{id}/undo        take back the last move
+ *  POST /games/{id}/resign      resign for {"color": "white"}, or the side to move
+ *
+ * Every answer is the game in the same form as GET, or {"error": "..."}
+ */
+pub struct GameServer {
+    ai: Ai,
+    games: Mutex<HashMap<u64, ServerGame>>,
+    next_id: Mutex<u64>,
+}
+
+impl GameServer {
+    pub fn new(ai: Ai) -> GameServer {
+        GameServer {
+            ai,
+            games: Mutex::new(HashMap::new()),
+            next_id: Mutex::new(1),
+        }
+    }
+
+    /**
+     * Answers one request, returns the HTTP status and the JSON body
+     */
+    pub fn handle(&self, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let body: Value = if body.trim().is_empty() {
+            json!({})
+        } else {
+            match serde_json::from_str(body) {
+                Ok(body) => body,
+                Err(_) => return error(400, "the body is not JSON"),
+            }
+        };
+        let parts: Vec<&str> = path.split('?').next().unwrap_or("").split('/').filter(|part| !part.is_empty()).collect();
+
+        match (method, parts.as_slice()) {
+            ("POST", ["games"]) => self.create(&body),
+            (_, ["games", id, rest @ ..]) => {
+                let id = match id.parse::<u64>() {
+                    Ok(id) => id,
+                    Err(_) => return error(404, "no such game"),
+                };
+                match (method, rest) {
+                    ("GET", []) => self.with_game(id, |_| Ok(())),
+                    ("POST", ["move"]) => self.play_move(id, &body),
+                    ("POST", ["ai-move"]) => self.ai_move(id),
+                    ("POST", ["undo"]) => self.with_game(id, |game| {
+                        game.game.undo().ok_or_else(|| error(409, "there is no move to take back"))?;
+                        game.history.pop();
+                        game.resigned = None;
+                        Ok(())
+                    }),
+                    ("POST", ["resign"]) => {
+                        let color = body.get("color").and_then(Value::as_str).map(str::to_string);
+                        self.with_game(id, |game| {
+                            if game.is_over() {
+                                return Err(error(409, "the game is over"));
+                            }
+                            game.resigned = Some(match color.as_deref() {
+                                Some("white") => ColorTurn::White,
+                                Some("black") => ColorTurn::Black,
+                                None => game.game.get_game_turn(),
+                                Some(_) => return Err(error(400, "color must be white or black")),
+                            });
+                            Ok(())
+                        })
+                    }
+                    _ => error(404, "no such endpoint"),
+                }
+            }
+            _ => error(404, "no such endpoint"),
+        }
+    }
+
+    fn create(&self, body: &Value) -> (u16, Value) {
+        let game = ServerGame::new(body.get("fen").and_then(Value::as_str));
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id - 1
+        };
+        let answer = with_id(game.to_json(), id);
+        self.games.lock().unwrap().insert(id, game);
+        (201, answer)
+    }
+
+    /**
+     * Runs a change on a game and answers with the game afterwards,
+     * or with the error the change returned
+     */
+    fn with_game<F>(&self, id: u64, change: F) -> (u16, Value)
+        where F: FnOnce(&mut ServerGame) -> Result<(), (u16, Value)> {
+        let mut games = self.games.lock().unwrap();
+        let game = match games.get_mut(&id) {
+            Some(game) => game,
+            None => return error(404, "no such game"),
+        };
+        match change(game) {
+            Ok(()) => (200, with_id(game.to_json(), id)),
+            Err(err) => err,
+        }
+    }
+
+    fn play_move(&self, id: u64, body: &Value) -> (u16, Value) {
+        let text = match body.get("move").and_then(Value::as_str) {
+            Some(text) => text.to_string(),
+            None => return error(400, "the body needs a move"),
+        };
+        self.with_game(id, |game| {
+            if game.is_over() {
+                return Err(error(409, "the game is over"));
+            }
+            let mv = game.game.parse_move(&text).or_else(|| game.game.parse_san(&text))
+                .ok_or_else(|| error(400, "the move is not legal"))?;
+            game.play(mv);
+            Ok(())
+        })
+    }
+
+    /**
+     * Searches without holding the lock on the games, so other games can be played
+     * meanwhile. If the game changed during the search the move is not played
+     */
+    fn ai_move(&self, id: u64) -> (u16, Value) {
+        let (position, history_len) = match self.games.lock().unwrap().get(&id) {
+            Some(game) if game.is_over() => return error(409, "the game is over"),
+            Some(game) => (game.game.clone(), game.history.len()),
+            None => return error(404, "no such game"),
+        };
+        let mv = self.ai.choose_move(&position);
+        self.with_game(id, |game| {
+            if game.history.len() != history_len || game.game.to_fen() != position.to_fen() {
+                return Err(error(409, "the game changed while the ai was thinking"));
+            }
+            let mv = mv.ok_or_else(|| error(409, "the game is over"))?;
+            game.play(mv);
+            Ok(())
+        })
+    }
+
+    /**
+     * Starts answering requests on the address, like "127.0.0.1:8080" or "127.0.0.1:0" for
+     * any free port. Every request is answered on its own thread. Returns the address that
+     * is listened on and the thread that accepts requests
+     */
+    pub fn listen(self, addr: &str) -> io::Result<(SocketAddr, thread::JoinHandle<()>)> {
+        let server = Server::http(addr).map_err(|err| io::Error::other(err.to_string()))?;
+        let local_addr = server.server_addr().to_ip()
+            .ok_or_else(|| io::Error::other("not an IP address"))?;
+        let this = Arc::new(self);
+
+        let handle = thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let this = this.clone();
+                thread::spawn(move || {
+                    let mut body = String::new();
+                    let (status, answer) = match request.as_reader().read_to_string(&mut body) {
+                        Ok(_) => {
+                            let method = match request.method() {
+                                Method::Get => "GET",
+                                Method::Post => "POST",
+                                _ => "",
+                            };
+                            this.handle(method, request.url(), &body)
+                        }
+                        Err(_) => error(400, "the body is not text"),
+                    };
+                    let response = Response::from_string(answer.to_string())
+                        .with_status_code(status)
+                        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+                    let _ = request.respond(response);
+                });
+            }
+        });
+        Ok((local_addr, handle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GameServer;
+    use crate::search::AnalysisOptions;
+    use crate::Ai;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    fn server() -> GameServer {
+        GameServer::new(Ai { options: AnalysisOptions { depth: 2, ..AnalysisOptions::default() }, ..Ai::default() })
+    }
+
+    #[test]
+    fn plays_and_takes_back_moves() {
+        let server = server();
+        let (status, game) = server.handle("POST", "/games", "");
+        assert_eq!(status, 201);
+        assert_eq!(game["legal_moves"].as_array().unwrap().len(), 20);
+
+        let (status, game) = server.handle("POST", "/games/1/move", r#"{"move": "e4"}"#);
+        assert_eq!(status, 200);
+        assert_eq!(game["history"][0], "e4");
+        assert_eq!(game["turn"], "black");
+
+        assert_eq!(server.handle("POST", "/games/1/move", r#"{"move": "e2e4"}"#).0, 400);
+        let (_, game) = server.handle("POST", "/games/1/ai-move", "");
+        assert_eq!(game["history"].as_array().unwrap().len(), 2);
+
+        let (_, game) = server.handle("POST", "/games/1/undo", "");
+        assert_eq!(game["history"].as_array().unwrap().len(), 1);
+        assert_eq!(server.handle("GET", "/games/2", "").0, 404);
+    }
+
+    #[test]
+    fn reports_mate_and_resignation() {
+        let server = server();
+        server.handle("POST", "/games", r#"{"fen": "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"}"#);
+        let (_, game) = server.handle("POST", "/games/1/ai-move", "");
+        assert_eq!(game["status"], "checkmate");
+        assert_eq!(game["result"], "1-0");
+        assert_eq!(server.handle("POST", "/games/1/resign", "").0, 409);
+
+        server.handle("POST", "/games", "");
+        let (_, game) = server.handle("POST", "/games/2/resign", r#"{"color": "black"}"#);
+        assert_eq!(game["result"], "1-0");
+    }
+
+    #[test]
+    fn answers_over_http_on_localhost() {
+        let (addr, _) = server().listen("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let body = r#"{"fen": "8/8/8/4k3/8/8/8/4K3 w - - 0 1"}"#;
+        write!(stream, "POST /games HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body).unwrap();
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer).unwrap();
+
+        assert!(answer.starts_with("HTTP/1.1 201"));
+        assert!(answer.contains("\"fen\":\"8/8/8/4k3/8/8/8/4K3 w - - 0 1\""));
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt;
+
+/* The variance of a pair score is never taken to be lower than this, so that a run of
+ * equal pairs does not make the log-likelihood ratio infinite */
+const MIN_PAIR_VARIANCE: f64 = 1e-4;
+
+/**
+ * The scores of game pairs, where both engines played the same opening with each color.
+ * counts[n] is the number of pairs where the first engine got n half points, so
+ * counts[0] is two losses and counts[4] is two wins
+ */
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Pentanomial {
+    pub counts: [u32; 5],
+}
+
+impl Pentanomial {
+
+    /// Counts a pair where the first engine got half_points out of 4.
+    pub fn add(&mut self, half_points: usize) {
+        self.counts[half_points.min(4)] += 1;
+    }
+
+    pub fn pairs(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    /// The mean and the variance of the pair scores, scaled from 0 to 1.
+    fn mean_and_variance(&self) -> (f64, f64) {
+        let pairs = self.pairs() as f64;
+        let mean = self.counts.iter().enumerate()
+            .map(|(half_points, count)| half_points as f64 / 4.0 * *count as f64)
+            .sum::<f64>() / pairs;
+        let variance = self.counts.iter().enumerate()
+            .map(|(half_points, count)| (half_points as f64 / 4.0 - mean).powi(2) * *count as f64)
+            .sum::<f64>() / pairs;
+        (mean, variance.max(MIN_PAIR_VARIANCE))
+    }
+}
+
+impl fmt::Display for Pentanomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts: Vec<String> = self.counts.iter().map(|count| count.to_string()).collect();
+        write!(f, "[{}]", counts.join(", "))
+    }
+}
+
+/// Which hypothesis a sequential probability ratio test accepted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SprtResult {
+    /// The first engine is not stronger than elo0.
+    H0,
+    /// The first engine is at least elo1 stronger.
+    H1,
+}
+
+/// The expected score of a player that is elo stronger, from 0 to 1.
+fn logistic(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/**
+ * A sequential probability ratio test of whether the first engine of a match is elo0 or
+ * elo1 stronger than the second. Games are played in pairs until the log-likelihood ratio
+ * crosses one of the bounds, alpha is the chance of accepting H1 when H0 holds and beta
+ * the chance of accepting H0 when H1 holds
+ */
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Sprt {
+        Sprt { elo0, elo1, alpha: 0.05, beta: 0.05 }
+    }
+
+    /// The lower and upper bound, H0 is accepted below the lower and H1 above the upper.
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    /**
+     * The log-likelihood ratio of the pairs, with the scores of the pairs taken to be
+     * normally distributed around the expected score of elo0 or elo1
+     */
+    pub fn llr(&self, pairs: &Pentanomial) -> f64 {
+        if pairs.pairs() == 0 {
+            return 0.0;
+        }
+        let (mean, variance) = pairs.mean_and_variance();
+        let (score0, score1) = (logistic(self.elo0), logistic(self.elo1));
+        pairs.pairs() as f64 * (score1 - score0) * (2.0 * mean - score0 - score1) / (2.0 * variance)
+    }
+
+    /// The accepted hypothesis, or None if more games are needed.
+    pub fn result(&self, pairs: &Pentanomial) -> Option<SprtResult> {
+        let llr = self.llr(pairs);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Some(SprtResult::H1)
+        } else if llr <= lower {
+            Some(SprtResult::H0)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pentanomial, Sprt, SprtResult};
+
+    #[test]
+    fn bounds_follow_alpha_and_beta() {
+        let (lower, upper) = Sprt::new(0.0, 5.0).bounds();
+        assert!((lower + 2.944).abs() < 0.001);
+        assert!((upper - 2.944).abs() < 0.001);
+    }
+
+    #[test]
+    fn accepts_the_hypothesis_the_pairs_favour() {
+        let sprt = Sprt::new(0.0, 10.0);
+        let mut pairs = Pentanomial::default();
+        assert_eq!(sprt.llr(&pairs), 0.0);
+        for _ in 0..10 {
+            pairs.add(2);
+        }
+        pairs.add(3);
+        assert_eq!(sprt.result(&pairs), None);
+
+        let strong = Pentanomial { counts: [10, 50, 300, 120, 20] };
+        assert_eq!(sprt.result(&strong), Some(SprtResult::H1));
+        let weak = Pentanomial { counts: [20, 120, 300, 50, 10] };
+        assert_eq!(sprt.result(&weak), Some(SprtResult::H0));
+    }
+}
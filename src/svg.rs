@@ -0,0 +1,242 @@
+use std::fmt::Write;
+
+use crate::{Game, ColorTurn, _NONE, _PAWN, _BISHOP, _KNIGHT, _ROOK, _QUEEN, _KING, _WHITE, _BLACK};
+
+/* The side of a square in the units of the image, the pieces are drawn in a box this size */
+const SQUARE: u32 = 45;
+/* The border around the board where the coordinates are written */
+const MARGIN: u32 = 15;
+const LIGHT_SQUARE: &str = "#f0d9b5";
+const DARK_SQUARE: &str = "#b58863";
+const LAST_MOVE: &str = "#cdd26a";
+const MARK: &str = "#15781b";
+/**
+ * The pieces drawn with plain shapes, so the image needs no fonts or other files. Each
+ * is filled with the color of the piece, and DETAIL is the color of the lines drawn on it
+ */
+const PIECE_SHAPES: [(u8, &str); 6] = [
+    (_PAWN, r#"<circle cx="22.5" cy="15" r="5"/><path d="M15 35 L18.5 22 H26.5 L30 35 Z"/><rect x="11" y="34" width="23" height="5" rx="1"/>"#),
+    (_KNIGHT, r#"<path d="M14 35 C14 27 19 24 20 20 C17 21 14 23 12 21 C11 18 16 13 19 10 L20 7 L23 10 C30 11 33 18 32 35 Z"/><circle cx="19" cy="14" r="1.2" fill="DETAIL" stroke="none"/><rect x="11" y="34" width="23" height="5" rx="1"/>"#),
+    (_BISHOP, r#"<circle cx="22.5" cy="9.5" r="2.5"/><ellipse cx="22.5" cy="21" rx="7" ry="9"/><path d="M20 18 L25 23" stroke="DETAIL"/><path d="M15 34 L18 28 H27 L30 34 Z"/><rect x="11" y="34" width="23" height="5" rx="1"/>"#),
+    (_ROOK, r#"<path d="M12 10 H16 V13 H20 V10 H25 V13 H29 V10 H33 V16 L30 19 V31 L33 34 H12 L15 31 V19 L12 16 Z"/><path d="M15 19 H30" stroke="DETAIL"/><rect x="10" y="34" width="25" height="5" rx="1"/>"#),
+    (_QUEEN, r#"<path d="M10 31 L8 13 L15 24 L17 10 L22.5 23 L28 10 L30 24 L37 13 L35 31 Z"/><circle cx="8" cy="12" r="2"/><circle cx="17" cy="9" r="2"/><circle cx="28" cy="9" r="2"/><circle cx="37" cy="12" r="2"/><rect x="10" y="32" width="25" height="6" rx="1"/>"#),
+    (_KING, r#"<path d="M22.5 6 V15 M18.5 10 H26.5" fill="none"/><path d="M12 31 C7 23 13 16 22.5 23 C32 16 38 23 33 31 Z"/><path d="M22.5 23 V30" stroke="DETAIL"/><rect x="10" y="32" width="25" height="6" rx="1"/>"#),
+];
+
+/**
+ * How Game::to_svg draws the board. The default is a 400 pixel image with White at the
+ * bottom, coordinates and the last move and a check highlighted
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgOptions {
+    /// The width and height of the image in pixels.
+    pub size: u32,
+    /// The player whose side of the board is at the bottom.
+    pub perspective: ColorTurn,
+    pub coordinates: bool,
+    pub highlight_last_move: bool,
+    pub highlight_check: bool,
+    /// Arrows drawn from the first square to the second, arrows to or from squares that are not on the board are left out.
+    pub arrows: Vec<(u8, u8)>,
+    /// Squares with a circle drawn around them, squares that are not on the board are left out.
+    pub circles: Vec<u8>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            size: 400,
+            perspective: ColorTurn::White,
+            coordinates: true,
+            highlight_last_move: true,
+            highlight_check: true,
+            arrows: Vec::new(),
+            circles: Vec::new(),
+        }
+    }
+}
+
+/// The id the piece is defined with in the image, for example "wN".
+fn piece_id(piece: u8, color: u8) -> String {
+    format!("{}{}", if color == _WHITE { 'w' } else { 'b' }, Game::fen_symbol(piece, _WHITE))
+}
+
+impl Game {
+
+    /**
+     * Draws the board as a standalone SVG image, see SvgOptions. Everything is drawn with
+     * shapes in the image itself, only the coordinates and the fairy pieces, which are
+     * drawn as their letters, are text in a sans-serif font. The image is options.size
+     * wide, and as high as the ranks of the board make it
+     */
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        let (files, ranks) = (self.geometry.files as u32, self.geometry.ranks as u32);
+        let margin = if options.coordinates { MARGIN } else { 0 };
+        let (width, height) = (files * SQUARE + 2 * margin, ranks * SQUARE + 2 * margin);
+        let white_bottom = options.perspective == ColorTurn::White;
+        let on_board = |_pos: &u8| (*_pos as usize) < self.board.len();
+        // the top left corner of the square in the image
+        let corner = |_pos: u8| -> (u32, u32) {
+            let (file, rank) = (_pos as u32 % files, _pos as u32 / files);
+            let (column, row) = if white_bottom { (file, ranks - 1 - rank) } else { (files - 1 - file, rank) };
+            (margin + column * SQUARE, margin + row * SQUARE)
+        };
+        let centre = |_pos: u8| -> (f32, f32) {
+            let (x, y) = corner(_pos);
+            (x as f32 + SQUARE as f32 / 2.0, y as f32 + SQUARE as f32 / 2.0)
+        };
+
+        let mut svg = String::new();
+        let _ = write!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+            options.size, options.size * height / width, width, height);
+
+        svg.push_str("<defs>");
+        for color in [_WHITE, _BLACK] {
+            let (fill, detail) = if color == _WHITE { ("#ffffff", "#000000") } else { ("#000000", "#ffffff") };
+            for (piece, shape) in PIECE_SHAPES.iter() {
+                let _ = write!(svg, r##"<g id="{}" fill="{}" stroke="#000000" stroke-width="1.5" stroke-linejoin="round">{}</g>"##,
+                    piece_id(*piece, color), fill, shape.replace("DETAIL", detail));
+            }
+        }
+        let _ = write!(svg, r##"<radialGradient id="check"><stop offset="0%" stop-color="#ff0000"/><stop offset="50%" stop-color="#e70000"/><stop offset="100%" stop-color="#9e0000" stop-opacity="0"/></radialGradient>"##);
+        let _ = write!(svg, r#"<marker id="arrowhead" viewBox="0 0 10 10" refX="5" refY="5" markerWidth="3" markerHeight="3" orient="auto"><path d="M0 0 L10 5 L0 10 Z" fill="{}"/></marker>"#, MARK);
+        svg.push_str("</defs>");
+
+        if options.coordinates {
+            let _ = write!(svg, r##"<rect width="{}" height="{}" fill="#212121"/>"##, width, height);
+        }
+        for _pos in 0..self.geometry.squares() as u8 {
+            let (x, y) = corner(_pos);
+            let light = (_pos as u32 % files + _pos as u32 / files) % 2 == 1;
+            let _ = write!(svg, r#"<rect x="{}" y="{}" width="{2}" height="{2}" fill="{3}"/>"#, x, y, SQUARE, if light { LIGHT_SQUARE } else { DARK_SQUARE });
+        }
+
+        if let Some(mv) = self.history.last().filter(|_| options.highlight_last_move).map(|undo| undo.mv) {
+            let mut squares = vec![mv.to];
+            if !mv.is_drop() {
+                squares.push(mv.from);
+            }
+            for _pos in squares {
+                let (x, y) = corner(_pos);
+                let _ = write!(svg, r#"<rect x="{}" y="{}" width="{2}" height="{2}" fill="{3}" opacity="0.8"/>"#, x, y, SQUARE, LAST_MOVE);
+            }
+        }
+        if options.highlight_check && self.is_king_check() {
+            if let Some(king) = self.find_king(self.color_to_move()) {
+                let (x, y) = corner(king);
+                let _ = write!(svg, r#"<rect x="{}" y="{}" width="{2}" height="{2}" fill="url(#check)"/>"#, x, y, SQUARE);
+            }
+        }
+
+        for (_pos, square) in self.board.iter().enumerate().filter(|(_, square)| square[0] != _NONE) {
+            let (x, y) = corner(_pos as u8);
+            if PIECE_SHAPES.iter().any(|(piece, _)| *piece == square[0]) {
+                let _ = write!(svg, r##"<use xlink:href="#{}" x="{}" y="{}"/>"##, piece_id(square[0], square[1]), x, y);
+                continue;
+            }
+            let (fill, detail) = if square[1] == _WHITE { ("#ffffff", "#000000") } else { ("#000000", "#ffffff") };
+            let _ = write!(svg, r##"<circle cx="{}" cy="{}" r="15" fill="{}" stroke="#000000" stroke-width="1.5"/><text x="{0}" y="{}" font-family="sans-serif" font-size="18" font-weight="bold" fill="{}" text-anchor="middle">{}</text>"##,
+                x + SQUARE / 2, y + SQUARE / 2, fill, y + SQUARE / 2 + 6, detail, Game::fen_symbol(square[0], _WHITE));
+        }
+
+        for _pos in options.circles.iter().filter(|_pos| on_board(_pos)) {
+            let (x, y) = centre(*_pos);
+            let _ = write!(svg, r#"<circle cx="{}" cy="{}" r="{}" fill="none" stroke="{}" stroke-width="3" opacity="0.8"/>"#, x, y, SQUARE as f32 / 2.0 - 2.0, MARK);
+        }
+        for (from, to) in options.arrows.iter().filter(|(from, to)| on_board(from) && on_board(to)) {
+            let ((x1, y1), (x2, y2)) = (centre(*from), centre(*to));
+            // the line stops short of the centre so the head ends there
+            let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt().max(1.0);
+            let shorten = (SQUARE as f32 / 4.0).min(length);
+            let (x2, y2) = (x2 - (x2 - x1) / length * shorten, y2 - (y2 - y1) / length * shorten);
+            let _ = write!(svg, r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="8" stroke-linecap="round" opacity="0.8" marker-end="url(#arrowhead)"/>"#, x1, y1, x2, y2, MARK);
+        }
+
+        if options.coordinates {
+            for i in 0..files {
+                let file = if white_bottom { i } else { files - 1 - i };
+                let position = margin + i * SQUARE + SQUARE / 2;
+                let _ = write!(svg, r##"<text x="{}" y="{}" font-family="sans-serif" font-size="11" fill="#e5e5e5" text-anchor="middle">{}</text>"##,
+                    position, height - 4, (b'a' + file as u8) as char);
+            }
+            for i in 0..ranks {
+                let rank = if white_bottom { ranks - 1 - i } else { i };
+                let position = margin + i * SQUARE + SQUARE / 2;
+                let _ = write!(svg, r##"<text x="{}" y="{}" font-family="sans-serif" font-size="11" fill="#e5e5e5" text-anchor="middle">{}</text>"##,
+                    margin / 2, position + 4, rank + 1);
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SvgOptions;
+    use crate::{ColorTurn, Fairy, Game};
+    use std::sync::Arc;
+
+    fn start() -> Game {
+        let mut game = Game::new();
+        game.init_board();
+        game
+    }
+
+    #[test]
+    fn draws_the_pieces_on_the_board() {
+        let game = start();
+        let svg = game.to_svg(&SvgOptions::default());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<use ").count(), 32);
+        assert_eq!(svg.matches("<g ").count(), svg.matches("</g>").count());
+        // the white rook on a1 is in the bottom left corner and the black one in the top left
+        assert!(svg.contains(r##"<use xlink:href="#wR" x="15" y="330"/>"##));
+        assert!(svg.contains(r##"<use xlink:href="#bR" x="15" y="15"/>"##));
+        assert!(!svg.contains("href=\"http"));
+
+        let flipped = game.to_svg(&SvgOptions { perspective: ColorTurn::Black, coordinates: false, ..SvgOptions::default() });
+        assert!(flipped.contains(r##"<use xlink:href="#wR" x="315" y="0"/>"##));
+        assert!(!flipped.contains("<text"));
+    }
+
+    #[test]
+    fn draws_highlights_arrows_and_circles() {
+        let mut game = start();
+        for text in ["e2e4", "f7f6", "d1h5"] {
+            game.play(game.parse_move(text).unwrap());
+        }
+        let options = SvgOptions { arrows: vec![(6, 21)], circles: vec![36, 37], ..SvgOptions::default() };
+        let svg = game.to_svg(&options);
+        assert_eq!(svg.matches(r##"fill="#cdd26a""##).count(), 2);
+        assert!(svg.contains(r#"fill="url(#check)""#));
+        assert_eq!(svg.matches("<line ").count(), 1);
+        assert_eq!(svg.matches(r##"fill="none" stroke="#15781b""##).count(), 2);
+
+        let plain = game.to_svg(&SvgOptions { highlight_last_move: false, highlight_check: false, ..SvgOptions::default() });
+        assert!(!plain.contains(r#"fill="url(#check)""#));
+        assert!(!plain.contains(r##"fill="#cdd26a""##));
+
+        // marks on squares the board does not have are left out
+        let outside = game.to_svg(&SvgOptions { arrows: vec![(6, 64), (200, 21)], circles: vec![64, 255], ..SvgOptions::default() });
+        assert!(!outside.contains("<line "));
+        assert!(!outside.contains(r##"fill="none" stroke="#15781b""##));
+    }
+
+    #[test]
+    fn draws_boards_of_other_sizes() {
+        let mut game = Game::new();
+        game.set_variant(Arc::new(Fairy::capablanca()));
+        game.init_board();
+        let svg = game.to_svg(&SvgOptions { circles: vec![79], ..SvgOptions::default() });
+        assert!(svg.contains(r#"width="400" height="325" viewBox="0 0 480 390""#));
+        assert_eq!(svg.matches("<use ").count(), 36);
+        // the archbishops and chancellors have no shapes and are drawn as their letters
+        assert_eq!(svg.matches(">A</text>").count(), 2);
+        assert_eq!(svg.matches(">C</text>").count(), 2);
+        assert!(svg.contains(r##"<use xlink:href="#wR" x="420" y="330"/>"##));
+        assert!(svg.contains(">j</text>"));
+        assert!(svg.contains(r#"<circle cx="442.5" cy="37.5""#));
+    }
+}
@@ -0,0 +1,1340 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+use memmap2::Mmap;
+
+use crate::{Game, ColorTurn, _NONE, _PAWN, _BISHOP, _KNIGHT, _ROOK, _QUEEN, _KING, _WHITE};
+
+/* The first four bytes of every table file */
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+/* The most pieces a Syzygy table can have */
+const MAX_PIECES: usize = 7;
+
+/* Flags of a compressed table */
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+/* The largest block size and span exponents and symbol length a table may have, the decoder reads 32 bits at a time */
+const MAX_EXPONENT: u8 = 31;
+const MAX_SYM_LEN: u8 = 32;
+
+/**
+ * The result of a tablebase probe for the player whose turn it is.
+ *
+ * A cursed win is a win that takes more than 50 moves without a capture or pawn move,
+ * so it is a draw under the 50 move rule, and a blessed loss is the same for the losing side
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            1 => Wdl::CursedWin,
+            2 => Wdl::Win,
+            _ => Wdl::Draw,
+        }
+    }
+}
+
+/**
+ * The win, draw or loss of a position together with its distance to zero, the number of
+ * plies until the next capture or pawn move in the best play. dtz is positive when the
+ * player whose turn it is wins, negative when it loses and 0 for a draw
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TablebaseProbe {
+    pub wdl: Wdl,
+    pub dtz: i32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+/* What happened during a probe, besides the value it returned */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ProbeState {
+    Ok,
+    Fail,
+    ZeroingBestMove,
+    ChangeStm,
+}
+
+/**
+ * The index tables used to change a position into the index of a table entry, they
+ * are the same for every table and are only calculated once
+ */
+struct Maps {
+    map_b1h1h7: [u64; 64],
+    map_a1d1d4: [u64; 64],
+    map_kk: [[u64; 64]; 10],
+    binomial: [[u64; 64]; MAX_PIECES],
+    map_pawns: [u64; 64],
+    lead_pawn_idx: [[u64; 64]; MAX_PIECES],
+    lead_pawns_size: [[u64; 4]; MAX_PIECES],
+}
+
+/// The rank minus the file, negative below the a1-h8 diagonal and 0 on it.
+fn off_a1h8(_pos: usize) -> i32 {
+    (_pos / 8) as i32 - (_pos % 8) as i32
+}
+
+/// How far a file is from the nearest edge, a and h are 0 and d and e are 3.
+fn edge_distance(file: usize) -> usize {
+    file.min(7 - file)
+}
+
+fn maps() -> &'static Maps {
+    static MAPS: OnceLock<Maps> = OnceLock::new();
+    MAPS.get_or_init(|| {
+        let mut maps = Maps {
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; MAX_PIECES],
+            map_pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; MAX_PIECES],
+            lead_pawns_size: [[0; 4]; MAX_PIECES],
+        };
+
+        // the squares below the a1-h8 diagonal are numbered 0 to 27
+        let mut code = 0;
+        for _pos in 0..64 {
+            if off_a1h8(_pos) < 0 {
+                maps.map_b1h1h7[_pos] = code;
+                code += 1;
+            }
+        }
+
+        // the squares of the a1-d1-d4 triangle are numbered 0 to 9, the diagonal last
+        let mut diagonal: Vec<usize> = Vec::new();
+        code = 0;
+        for _pos in [0, 1, 2, 3, 8, 9, 10, 11, 16, 17, 18, 19, 24, 25, 26, 27] {
+            if off_a1h8(_pos) < 0 {
+                maps.map_a1d1d4[_pos] = code;
+                code += 1;
+            } else if off_a1h8(_pos) == 0 {
+                diagonal.push(_pos);
+            }
+        }
+        for _pos in diagonal {
+            maps.map_a1d1d4[_pos] = code;
+            code += 1;
+        }
+
+        // the 462 ways to place two kings with the first one in the a1-d1-d4 triangle,
+        // if the first king is on the diagonal the second one is not above it
+        let mut both_on_diagonal: Vec<(usize, usize)> = Vec::new();
+        code = 0;
+        for idx in 0..10 {
+            for first in 0..28 {
+                if maps.map_a1d1d4[first] != idx as u64 || (idx == 0 && first != 1) {
+                    continue;
+                }
+                for second in 0..64 {
+                    let file_distance = ((first % 8) as i32 - (second % 8) as i32).abs();
+                    let rank_distance = ((first / 8) as i32 - (second / 8) as i32).abs();
+                    if file_distance <= 1 && rank_distance <= 1 {
+                        continue;
+                    }
+                    if off_a1h8(first) == 0 && off_a1h8(second) > 0 {
+                        continue;
+                    }
+                    if off_a1h8(first) == 0 && off_a1h8(second) == 0 {
+                        both_on_diagonal.push((idx, second));
+                    } else {
+                        maps.map_kk[idx][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, second) in both_on_diagonal {
+            maps.map_kk[idx][second] = code;
+            code += 1;
+        }
+
+        maps.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                maps.binomial[k][n] = if k > 0 { maps.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { maps.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // the pawn with the highest map_pawns value is the leading pawn, the one nearest
+        // the edge and the lowest one of those on the same file
+        let mut available_squares = 47;
+        for lead_pawns in 1..=5 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let _pos = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        maps.map_pawns[_pos] = available_squares;
+                        available_squares -= 1;
+                        maps.map_pawns[_pos ^ 7] = available_squares;
+                        available_squares = available_squares.saturating_sub(1);
+                    }
+                    maps.lead_pawn_idx[lead_pawns][_pos] = idx;
+                    idx += maps.binomial[lead_pawns - 1][maps.map_pawns[_pos] as usize];
+                }
+                maps.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+        maps
+    })
+}
+
+/* Little and big endian reads from the table data */
+fn read_u16_le(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32_le(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn read_u64_be(bytes: &[u8], offset: usize) -> u64 {
+    (read_u32_be(bytes, offset) as u64) << 32 | read_u32_be(bytes, offset + 4) as u64
+}
+
+/**
+ * One compressed table in a file, there is one for every side to move and, in tables
+ * with pawns, for every file a to d of the leading pawn. The offsets point into the file data
+ */
+#[derive(Clone, Debug, Default)]
+struct PairsData {
+    flags: u8,
+    min_sym_len: u8,
+    num_blocks: u32,
+    block_size: usize,
+    span: usize,
+    lowest_sym: usize,
+    btree: usize,
+    block_length: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    pieces: [u8; MAX_PIECES],
+    group_idx: [u64; MAX_PIECES + 1],
+    group_len: [usize; MAX_PIECES + 1],
+    map_idx: [usize; 4],
+}
+
+/**
+ * A WDL or DTZ table file mapped into memory, key is the material of the table such as
+ * "KRvK" and key2 the same material with the colors swapped
+ */
+struct Table {
+    kind: TableKind,
+    bytes: Mmap,
+    key: String,
+    key2: String,
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    pawn_count: [usize; 2],
+    items: Vec<Vec<PairsData>>,
+    map: usize,
+}
+
+/**
+ * Changes a piece to the numbering used in the table files, 1 to 6 for the white pawn,
+ * knight, bishop, rook, queen and king and 9 to 14 for the black ones
+ */
+fn table_piece(piece: u8, color: u8) -> u8 {
+    let kind = match piece {
+        _PAWN => 1,
+        _KNIGHT => 2,
+        _BISHOP => 3,
+        _ROOK => 4,
+        _QUEEN => 5,
+        _KING => 6,
+        _ => 0,
+    };
+    if color == _WHITE { kind } else { kind + 8 }
+}
+
+impl Table {
+
+    /**
+     * Reads the header of a table file and sets up the compressed tables,
+     * returns None if the file is not a table for the given material
+     */
+    fn new(kind: TableKind, code: &str, bytes: Mmap) -> Option<Table> {
+        let magic = if kind == TableKind::Wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if bytes.len() < 5 || bytes[0..4] != magic {
+            return None;
+        }
+
+        let (white, black) = code.split_once('v')?;
+        let count = |side: &str, letter: char| side.chars().filter(|c| *c == letter).count();
+        let white_pawns = count(white, 'P');
+        let black_pawns = count(black, 'P');
+        let has_unique_pieces = [white, black].iter()
+            .any(|side| "QRBN".chars().any(|letter| count(side, letter) == 1));
+        // the leading color is the one with fewer pawns, since that compresses better
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+
+        let mut table = Table {
+            kind,
+            bytes,
+            key: code.to_string(),
+            key2: format!("{}v{}", black, white),
+            piece_count: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            pawn_count: if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] },
+            items: Vec::new(),
+            map: 0,
+        };
+        // the flags byte says if the table has pawns, which must agree with the file name
+        if table.piece_count > MAX_PIECES || (table.bytes[4] & 2 != 0) != table.has_pawns {
+            return None;
+        }
+        table.init()?;
+        Some(table)
+    }
+
+    /// Returns the compressed table for the side to move and the file of the leading pawn.
+    fn get(&self, stm: usize, file: usize) -> &PairsData {
+        let side = stm % self.items.len();
+        &self.items[side][if self.has_pawns { file } else { 0 }]
+    }
+
+    /**
+     * Reads the piece order, group sizes and offsets of all of the compressed tables
+     */
+    fn init(&mut self) -> Option<()> {
+        let bytes_len = self.bytes.len();
+        let mut data = 5;
+        let sides = if self.kind == TableKind::Wdl && self.key != self.key2 { 2 } else { 1 };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let pp = self.has_pawns && self.pawn_count[1] > 0;
+        self.items = vec![vec![PairsData::default(); files]; sides];
+
+        for file in 0..files {
+            let order_byte = *self.bytes.get(data)?;
+            let pawn_order_byte = if pp { *self.bytes.get(data + 1)? } else { 0xFF };
+            let order = [
+                [(order_byte & 0xF) as usize, if pp { (pawn_order_byte & 0xF) as usize } else { 0xF }],
+                [(order_byte >> 4) as usize, if pp { (pawn_order_byte >> 4) as usize } else { 0xF }],
+            ];
+            data += 1 + pp as usize;
+
+            for k in 0..self.piece_count {
+                let byte = *self.bytes.get(data)?;
+                for (i, side) in self.items.iter_mut().enumerate() {
+                    side[file].pieces[k] = if i == 1 { byte >> 4 } else { byte & 0xF };
+                }
+                data += 1;
+            }
+
+            for (i, side_order) in order.iter().enumerate().take(sides) {
+                let mut pairs = std::mem::take(&mut self.items[i][file]);
+                if !self.has_material(&pairs.pieces) {
+                    return None;
+                }
+                self.set_groups(&mut pairs, *side_order, file)?;
+                self.items[i][file] = pairs;
+            }
+        }
+
+        data += data & 1;
+
+        for file in 0..files {
+            for i in 0..sides {
+                data = self.set_sizes(i, file, data)?;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            self.map = data;
+            for file in 0..files {
+                let flags = self.items[0][file].flags;
+                if flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if flags & FLAG_WIDE != 0 {
+                    data += data & 1;
+                    for i in 0..4 {
+                        // the index counts 16 bit words from the start of the map
+                        self.items[0][file].map_idx[i] = (data - self.map) / 2 + 1;
+                        if data + 2 > bytes_len {
+                            return None;
+                        }
+                        data += 2 * read_u16_le(&self.bytes, data) as usize + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        self.items[0][file].map_idx[i] = data - self.map + 1;
+                        data += *self.bytes.get(data)? as usize + 1;
+                    }
+                }
+            }
+            data += data & 1;
+        }
+
+        // the sizes come from the file, so every offset is checked against its length
+        let advance = |data: usize, count: usize, size: usize| count.checked_mul(size)
+            .and_then(|bytes| data.checked_add(bytes))
+            .filter(|end| *end <= bytes_len);
+        for file in 0..files {
+            for i in 0..sides {
+                let pairs = &mut self.items[i][file];
+                pairs.sparse_index = data;
+                data = advance(data, pairs.sparse_index_size, 6)?;
+            }
+        }
+        for file in 0..files {
+            for i in 0..sides {
+                let pairs = &mut self.items[i][file];
+                pairs.block_length = data;
+                data = advance(data, pairs.block_length_size, 2)?;
+            }
+        }
+        for file in 0..files {
+            for i in 0..sides {
+                // the compressed blocks start at a multiple of 64 bytes
+                data = (data + 0x3F) & !0x3F;
+                let pairs = &mut self.items[i][file];
+                pairs.data = data;
+                data = advance(data, pairs.num_blocks as usize, pairs.block_size)?;
+            }
+        }
+        Some(())
+    }
+
+    /**
+     * Checks if the pieces read from the file are the material of the table, the white
+     * pieces of the key and the black ones in any order, with a pawn first in tables with pawns
+     */
+    fn has_material(&self, pieces: &[u8; MAX_PIECES]) -> bool {
+        let (white, black) = self.key.split_once('v').unwrap_or_default();
+        let piece_of = |letter: char| match letter {
+            'P' => _PAWN,
+            'N' => _KNIGHT,
+            'B' => _BISHOP,
+            'R' => _ROOK,
+            'Q' => _QUEEN,
+            _ => _KING,
+        };
+        let mut expected: Vec<u8> = white.chars().map(|c| table_piece(piece_of(c), _WHITE))
+            .chain(black.chars().map(|c| table_piece(piece_of(c), 0)))
+            .collect();
+        let mut found = pieces[..self.piece_count].to_vec();
+        expected.sort_unstable();
+        found.sort_unstable();
+        found == expected && (!self.has_pawns || pieces[0] & 7 == 1)
+    }
+
+    /**
+     * Splits the pieces into groups that are encoded together and calculates how much
+     * each group is multiplied with in the index of a position
+     */
+    fn set_groups(&self, pairs: &mut PairsData, order: [usize; 2], file: usize) -> Option<()> {
+        let maps = maps();
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns { 0 } else if self.has_unique_pieces { 3 } else { 2 };
+        pairs.group_len[0] = 1;
+
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || pairs.pieces[i] == pairs.pieces[i - 1] {
+                pairs.group_len[n] += 1;
+            } else {
+                n += 1;
+                pairs.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        pairs.group_len[n] = 0;
+
+        // the first group is encoded at order[0] and the pawns of the other color at order[1]
+        let pp = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if pp { 2 } else { 1 };
+        let mut free_squares = 64 - pairs.group_len[0] - if pp { pairs.group_len[1] } else { 0 };
+        let mut idx: u64 = 1;
+
+        // the order comes from the file, each of the groups it names must be there
+        if order[0] >= n || (pp && order[1] >= n) {
+            return None;
+        }
+        let mut k = 0;
+        while next < n || k == order[0] || k == order[1] {
+            let size = if k == order[0] {
+                pairs.group_idx[0] = idx;
+                if self.has_pawns {
+                    *maps.lead_pawns_size.get(pairs.group_len[0])?.get(file)?
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                }
+            } else if k == order[1] {
+                pairs.group_idx[1] = idx;
+                *maps.binomial.get(pairs.group_len[1])?.get(48usize.checked_sub(pairs.group_len[0])?)?
+            } else {
+                pairs.group_idx[next] = idx;
+                let size = *maps.binomial.get(pairs.group_len[next])?.get(free_squares)?;
+                free_squares = free_squares.checked_sub(pairs.group_len[next])?;
+                next += 1;
+                size
+            };
+            idx = idx.checked_mul(size)?;
+            k += 1;
+        }
+        pairs.group_idx[n] = idx;
+        Some(())
+    }
+
+    /**
+     * Reads the sizes and the Huffman code of one compressed table, returns the offset after it
+     */
+    fn set_sizes(&mut self, side: usize, file: usize, mut data: usize) -> Option<usize> {
+        let bytes = &self.bytes;
+        let pairs = &mut self.items[side][file];
+        pairs.flags = *bytes.get(data)?;
+        data += 1;
+
+        if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+            // every position in the table has the same value, which is stored here
+            pairs.min_sym_len = *bytes.get(data)?;
+            return Some(data + 1);
+        }
+
+        let end = pairs.group_len.iter().position(|len| *len == 0).unwrap_or(MAX_PIECES);
+        let table_size = pairs.group_idx[end];
+
+        if data + 10 > bytes.len() || bytes[data] > MAX_EXPONENT || bytes[data + 1] > MAX_EXPONENT {
+            return None;
+        }
+        pairs.block_size = 1 << bytes[data];
+        pairs.span = 1 << bytes[data + 1];
+        pairs.sparse_index_size = table_size.div_ceil(pairs.span as u64) as usize;
+        let padding = bytes[data + 2] as usize;
+        pairs.num_blocks = read_u32_le(bytes, data + 3);
+        pairs.block_length_size = pairs.num_blocks as usize + padding;
+        let max_sym_len = bytes[data + 7];
+        pairs.min_sym_len = bytes[data + 8];
+        data += 9;
+        pairs.lowest_sym = data;
+
+        if max_sym_len < pairs.min_sym_len || pairs.min_sym_len == 0 || max_sym_len > MAX_SYM_LEN {
+            return None;
+        }
+        let lengths = (max_sym_len - pairs.min_sym_len + 1) as usize;
+        if data + lengths * 2 + 2 > bytes.len() {
+            return None;
+        }
+
+        // canonical Huffman code: longer symbols have lower values, base64[i] is the lowest
+        // code of length min_sym_len + i, left aligned in 64 bits
+        pairs.base64 = vec![0; lengths];
+        for i in (0..lengths.saturating_sub(1)).rev() {
+            pairs.base64[i] = (pairs.base64[i + 1] + read_u16_le(bytes, pairs.lowest_sym + 2 * i) as u64)
+                .checked_sub(read_u16_le(bytes, pairs.lowest_sym + 2 * (i + 1)) as u64)? / 2;
+        }
+        for (i, base) in pairs.base64.iter_mut().enumerate() {
+            *base <<= 64 - i - pairs.min_sym_len as usize;
+        }
+
+        data += lengths * 2;
+        let symbols = read_u16_le(bytes, data) as usize;
+        data += 2;
+        pairs.btree = data;
+        if data + symbols * 3 > bytes.len() {
+            return None;
+        }
+
+        // every symbol stands for a pair of smaller symbols, symlen is the number of
+        // values a symbol expands to minus one
+        pairs.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                pairs.symlen[sym] = Table::set_symlen(bytes, pairs, sym, &mut visited)?;
+            }
+        }
+        Some(data + symbols * 3 + (symbols & 1))
+    }
+
+    /// The number of values the symbol expands to minus one, None if the pair tree points outside itself.
+    fn set_symlen(bytes: &[u8], pairs: &mut PairsData, sym: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[sym] = true;
+        let right = Table::btree_right(bytes, pairs, sym);
+        if right == 0xFFF {
+            return Some(0);
+        }
+        let left = Table::btree_left(bytes, pairs, sym);
+        if left >= visited.len() || right >= visited.len() {
+            return None;
+        }
+        if !visited[left] {
+            pairs.symlen[left] = Table::set_symlen(bytes, pairs, left, visited)?;
+        }
+        if !visited[right] {
+            pairs.symlen[right] = Table::set_symlen(bytes, pairs, right, visited)?;
+        }
+        Some(pairs.symlen[left].wrapping_add(pairs.symlen[right]).wrapping_add(1))
+    }
+
+    /* Every node of the pair tree is 3 bytes, 12 bits for the left and 12 for the right symbol */
+    fn btree_left(bytes: &[u8], pairs: &PairsData, sym: usize) -> usize {
+        let node = pairs.btree + 3 * sym;
+        ((bytes[node + 1] as usize & 0xF) << 8) | bytes[node] as usize
+    }
+
+    fn btree_right(bytes: &[u8], pairs: &PairsData, sym: usize) -> usize {
+        let node = pairs.btree + 3 * sym;
+        ((bytes[node + 2] as usize) << 4) | (bytes[node + 1] as usize >> 4)
+    }
+
+    /**
+     * Finds the value stored at idx in a compressed table
+     *
+     * The sparse index gives a block close to the one with the value, from there the block
+     * lengths are followed to the right block. The block is Huffman coded symbols where every
+     * symbol expands to a number of values, so symbols are skipped until the one holding idx
+     * and then it is expanded through the pair tree until a single value is left. Returns
+     * None if the table points outside itself, which only a broken file does
+     */
+    fn decompress_pairs(&self, pairs: &PairsData, idx: u64) -> Option<i32> {
+        if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(pairs.min_sym_len as i32);
+        }
+        let bytes = &self.bytes;
+        // a read of len bytes at offset, if the file has them
+        let fits = |offset: usize, len: usize| offset.checked_add(len).is_some_and(|end| end <= bytes.len());
+
+        let k = (idx / pairs.span as u64) as usize;
+        if k >= pairs.sparse_index_size {
+            return None;
+        }
+        let mut block = read_u32_le(bytes, pairs.sparse_index + 6 * k) as usize;
+        let mut offset = read_u16_le(bytes, pairs.sparse_index + 6 * k + 4) as i64;
+        offset += (idx % pairs.span as u64) as i64 - (pairs.span / 2) as i64;
+
+        let block_length = |block: usize| (block < pairs.block_length_size)
+            .then(|| read_u16_le(bytes, pairs.block_length + 2 * block) as i64);
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        if block >= pairs.num_blocks as usize {
+            return None;
+        }
+        let mut ptr = pairs.data + block * pairs.block_size;
+        if !fits(ptr, 8) {
+            return None;
+        }
+        let mut buf64 = read_u64_be(bytes, ptr);
+        ptr += 8;
+        let mut buf64_size = 64;
+        let min_sym_len = pairs.min_sym_len as usize;
+        let mut sym;
+
+        loop {
+            let mut len = 0;
+            while buf64 < *pairs.base64.get(len)? {
+                len += 1;
+            }
+            sym = ((buf64 - pairs.base64[len]) >> (64 - len - min_sym_len)) as usize;
+            sym += read_u16_le(bytes, pairs.lowest_sym + 2 * len) as usize;
+
+            let symlen = *pairs.symlen.get(sym)? as i64;
+            if offset < symlen + 1 {
+                break;
+            }
+            offset -= symlen + 1;
+            len += min_sym_len;
+            buf64 <<= len;
+            buf64_size -= len;
+
+            if buf64_size <= 32 {
+                if !fits(ptr, 4) {
+                    return None;
+                }
+                buf64_size += 32;
+                buf64 |= (read_u32_be(bytes, ptr) as u64) << (64 - buf64_size);
+                ptr += 4;
+            }
+        }
+
+        while pairs.symlen[sym] != 0 {
+            let left = Table::btree_left(bytes, pairs, sym);
+            if offset < pairs.symlen[left] as i64 + 1 {
+                sym = left;
+            } else {
+                offset -= pairs.symlen[left] as i64 + 1;
+                sym = Table::btree_right(bytes, pairs, sym);
+            }
+        }
+        Some(Table::btree_left(bytes, pairs, sym) as i32)
+    }
+
+    /**
+     * DTZ tables only store one side to move, returns false if the position has the other one
+     */
+    fn has_side_to_move(&self, stm: usize, file: usize) -> bool {
+        if self.kind == TableKind::Wdl {
+            return true;
+        }
+        let flags = self.get(stm, file).flags;
+        (flags & FLAG_STM) as usize == stm || (self.key == self.key2 && !self.has_pawns)
+    }
+
+    /**
+     * Changes a stored value to the result, for WDL tables the stored value is 0 to 4 for
+     * loss to win and for DTZ tables it is the distance to zero, possibly through a map
+     * and possibly in full moves instead of plies
+     */
+    fn map_score(&self, file: usize, mut value: i32, wdl: i32) -> Option<i32> {
+        if self.kind == TableKind::Wdl {
+            return Some(value - 2);
+        }
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        let pairs = self.get(0, file);
+        let flags = pairs.flags;
+        if flags & FLAG_MAPPED != 0 {
+            let index = pairs.map_idx[WDL_MAP[(wdl + 2) as usize]] + value as usize;
+            value = if flags & FLAG_WIDE != 0 {
+                let at = self.map + 2 * index;
+                u16::from_le_bytes([*self.bytes.get(at)?, *self.bytes.get(at + 1)?]) as i32
+            } else {
+                *self.bytes.get(self.map + index)? as i32
+            };
+        }
+        if (wdl == 2 && flags & FLAG_WIN_PLIES == 0)
+            || (wdl == -2 && flags & FLAG_LOSS_PLIES == 0)
+            || wdl == 1
+            || wdl == -1 {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+
+    /**
+     * Looks up the position in the table
+     *
+     * Tables are made with white as the stronger side, so if black is stronger the colors
+     * are swapped and the board is flipped. Then the board is mirrored so the leading piece
+     * is in the a1-d1-d4 triangle, or the leading pawn is on the a to d files, and the
+     * pieces are changed to an index one group at a time
+     */
+    fn probe(&self, position: &TablePosition, wdl: i32) -> (i32, ProbeState) {
+        let maps = maps();
+        let symmetric_black_to_move = self.key == self.key2 && position.stm == 1;
+        let black_stronger = position.key != self.key;
+        let flip = symmetric_black_to_move || black_stronger;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = flip as usize ^ position.stm;
+
+        let mut squares: Vec<usize> = Vec::with_capacity(MAX_PIECES);
+        let mut pieces: Vec<u8> = Vec::with_capacity(MAX_PIECES);
+        let mut lead_pawns_count = 0;
+        let mut lead_pawn = 0;
+        let mut tb_file = 0;
+
+        if self.has_pawns {
+            lead_pawn = self.get(0, 0).pieces[0] ^ flip_color;
+            for (_pos, piece) in &position.pieces {
+                if *piece == lead_pawn {
+                    squares.push(_pos ^ flip_squares);
+                    pieces.push(_NONE);
+                }
+            }
+            lead_pawns_count = squares.len();
+            let mut best = 0;
+            for i in 1..lead_pawns_count {
+                if maps.map_pawns[squares[i]] > maps.map_pawns[squares[best]] {
+                    best = i;
+                }
+            }
+            squares.swap(0, best);
+            tb_file = edge_distance(squares[0] % 8);
+        }
+
+        if !self.has_side_to_move(stm, tb_file) {
+            return (0, ProbeState::ChangeStm);
+        }
+
+        for (_pos, piece) in &position.pieces {
+            if !self.has_pawns || *piece != lead_pawn {
+                squares.push(_pos ^ flip_squares);
+                pieces.push(piece ^ flip_color);
+            }
+        }
+        let size = squares.len();
+        let pairs = self.get(stm, tb_file);
+
+        // put the pieces in the same order as in the table
+        for i in lead_pawns_count..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if pairs.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        if squares[0] % 8 > 3 {
+            for _pos in squares.iter_mut() {
+                *_pos ^= 7;
+            }
+        }
+
+        let mut idx: u64;
+        if self.has_pawns {
+            idx = maps.lead_pawn_idx[lead_pawns_count][squares[0]];
+            squares[1..lead_pawns_count].sort_by_key(|_pos| maps.map_pawns[*_pos]);
+            for (i, _pos) in squares.iter().enumerate().take(lead_pawns_count).skip(1) {
+                idx += maps.binomial[i][maps.map_pawns[*_pos] as usize];
+            }
+        } else {
+            if squares[0] / 8 > 3 {
+                for _pos in squares.iter_mut() {
+                    *_pos ^= 56;
+                }
+            }
+            // the first piece of the leading group that is not on the diagonal goes below it
+            for i in 0..pairs.group_len[0] {
+                if off_a1h8(squares[i]) == 0 {
+                    continue;
+                }
+                if off_a1h8(squares[i]) > 0 {
+                    for _pos in squares[i..].iter_mut() {
+                        *_pos = ((*_pos >> 3) | (*_pos << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if self.has_unique_pieces {
+                let s = [squares[0] as u64, squares[1] as u64, squares[2] as u64];
+                let rank = |x: u64| x / 8;
+                let adjust1 = (s[1] > s[0]) as u64;
+                let adjust2 = (s[2] > s[0]) as u64 + (s[2] > s[1]) as u64;
+                idx = if off_a1h8(squares[0]) != 0 {
+                    (maps.map_a1d1d4[squares[0]] * 63 + (s[1] - adjust1)) * 62 + s[2] - adjust2
+                } else if off_a1h8(squares[1]) != 0 {
+                    (6 * 63 + rank(s[0]) * 28 + maps.map_b1h1h7[squares[1]]) * 62 + s[2] - adjust2
+                } else if off_a1h8(squares[2]) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62
+                        + rank(s[0]) * 7 * 28
+                        + (rank(s[1]) - adjust1) * 28
+                        + maps.map_b1h1h7[squares[2]]
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28
+                        + rank(s[0]) * 7 * 6
+                        + (rank(s[1]) - adjust1) * 6
+                        + (rank(s[2]) - adjust2)
+                };
+            } else {
+                idx = maps.map_kk[maps.map_a1d1d4[squares[0]] as usize][squares[1]];
+            }
+        }
+
+        idx *= pairs.group_idx[0];
+        let mut group_start = pairs.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+
+        while pairs.group_len[next] != 0 {
+            let len = pairs.group_len[next];
+            squares[group_start..group_start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let _pos = squares[group_start + i];
+                // squares taken by earlier groups can not be used, so count them away
+                let adjust = squares[..group_start].iter().filter(|other| _pos > **other).count();
+                n += maps.binomial[i + 1][_pos - adjust - if remaining_pawns { 8 } else { 0 }];
+            }
+            remaining_pawns = false;
+            idx += n * pairs.group_idx[next];
+            group_start += len;
+            next += 1;
+        }
+
+        match self.decompress_pairs(pairs, idx).and_then(|value| self.map_score(tb_file, value, wdl)) {
+            Some(value) => (value, ProbeState::Ok),
+            None => (0, ProbeState::Fail),
+        }
+    }
+}
+
+/**
+ * The pieces of a position in the numbering of the table files, sorted by square,
+ * together with the material key such as "KRvK" and the side to move (0 for white)
+ */
+struct TablePosition {
+    pieces: Vec<(usize, u8)>,
+    key: String,
+    stm: usize,
+}
+
+impl TablePosition {
+    fn new(game: &Game) -> TablePosition {
+        let mut pieces: Vec<(usize, u8)> = Vec::new();
+        for (_pos, square) in game.board.iter().enumerate() {
+            if square[0] != _NONE {
+                pieces.push((_pos, table_piece(square[0], square[1])));
+            }
+        }
+        TablePosition {
+            pieces,
+            key: material_key(game),
+            stm: if game.get_game_turn() == ColorTurn::White { 0 } else { 1 },
+        }
+    }
+}
+
+/**
+ * Returns the material of the position the way table files are named,
+ * the white pieces then "v" and the black pieces, both in the order KQRBNP
+ */
+fn material_key(game: &Game) -> String {
+    let mut sides = [String::new(), String::new()];
+    for (letter, piece) in [('K', _KING), ('Q', _QUEEN), ('R', _ROOK), ('B', _BISHOP), ('N', _KNIGHT), ('P', _PAWN)] {
+        for square in &game.board {
+            if square[0] == piece {
+                sides[if square[1] == _WHITE { 0 } else { 1 }].push(letter);
+            }
+        }
+    }
+    format!("{}v{}", sides[0], sides[1])
+}
+
+/* The tables read so far by material and DTZ or not, None if the file was broken */
+type TableCache = HashMap<(String, bool), Option<Arc<Table>>>;
+
+/**
+ * The Syzygy tablebase files found in a directory.
+ *
+ * The files are mapped into memory the first time a position with their material is
+ * probed, so only the blocks that are looked up are ever read from the disk. The 6 and 7
+ * piece files are far too big to read whole
+ */
+pub struct Tablebase {
+    files: HashMap<(String, bool), PathBuf>,
+    tables: Mutex<TableCache>,
+    max_pieces: usize,
+}
+
+impl Tablebase {
+
+    /**
+     * Finds every .rtbw (WDL) and .rtbz (DTZ) file in the directory
+     */
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Tablebase> {
+        let mut files: HashMap<(String, bool), PathBuf> = HashMap::new();
+        let mut max_pieces = 0;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_dtz = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("rtbw") => false,
+                Some("rtbz") => true,
+                _ => continue,
+            };
+            let code = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(code) if code.split('v').count() == 2 && code.chars().all(|c| "KQRBNPv".contains(c)) => code.to_string(),
+                _ => continue,
+            };
+            if !is_dtz {
+                max_pieces = max_pieces.max(code.len() - 1);
+            }
+            files.insert((code, is_dtz), path);
+        }
+        Ok(Tablebase {
+            files,
+            tables: Mutex::new(HashMap::new()),
+            max_pieces,
+        })
+    }
+
+    /// The most pieces of any WDL table in the directory, 0 if there are none.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /**
+     * Checks if the position has few enough pieces and no castling rights, so it may be in
+     * the tables. The tables only hold the results of standard chess
+     */
+    fn can_probe(&self, game: &Game) -> bool {
+        let pieces = game.board.iter().filter(|square| square[0] != _NONE).count();
+        pieces <= self.max_pieces && !game.castling.iter().any(|right| *right) && game.variant().uses_standard_endgames()
+    }
+
+    /// Reads the table for the material key in either color order, if there is a file for it.
+    fn table(&self, key: &str, is_dtz: bool) -> Option<Arc<Table>> {
+        let (white, black) = key.split_once('v')?;
+        let swapped = format!("{}v{}", black, white);
+        // a probe that panicked while holding the lock has left the cache as it was, so it is still good
+        let mut tables = self.tables.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for code in [key, swapped.as_str()] {
+            let id = (code.to_string(), is_dtz);
+            if let Some(table) = tables.get(&id) {
+                return table.clone();
+            }
+            if let Some(path) = self.files.get(&id) {
+                let kind = if is_dtz { TableKind::Dtz } else { TableKind::Wdl };
+                // the file must not be changed while it is mapped, which tables never are
+                let table = File::open(path).ok()
+                    .and_then(|file| unsafe { Mmap::map(&file) }.ok())
+                    .and_then(|bytes| Table::new(kind, code, bytes))
+                    .map(Arc::new);
+                tables.insert(id, table.clone());
+                return table;
+            }
+        }
+        None
+    }
+
+    /// Probes the table of the position directly, which only works without en passante captures.
+    fn probe_table(&self, game: &Game, is_dtz: bool, wdl: i32) -> (i32, ProbeState) {
+        let position = TablePosition::new(game);
+        if position.pieces.len() == 2 {
+            return (0, ProbeState::Ok);
+        }
+        match self.table(&position.key, is_dtz) {
+            Some(table) => table.probe(&position, wdl),
+            None => (0, ProbeState::Fail),
+        }
+    }
+
+    /**
+     * Finds the WDL value of the position, first by searching captures since the tables
+     * do not know about en passante and the stored value may be wrong when the best move is
+     * a capture. With zeroing the pawn moves are searched too, which DTZ probing needs
+     */
+    fn search(&self, game: &mut Game, zeroing: bool, state: &mut ProbeState) -> i32 {
+        let mut best_value = -2;
+        let moves = game.generate_legal_moves();
+        let mut move_count = 0;
+
+        for mv in &moves {
+            let is_capture = game.is_capture(*mv);
+            if !is_capture && (!zeroing || game.board[mv.from as usize][0] != _PAWN) {
+                continue;
+            }
+            move_count += 1;
+            game.do_move(*mv);
+            let value = -self.search(game, false, state);
+            game.undo_move();
+
+            if *state == ProbeState::Fail {
+                return 0;
+            }
+            if value > best_value {
+                best_value = value;
+                if value >= 2 {
+                    *state = ProbeState::ZeroingBestMove;
+                    return value;
+                }
+            }
+        }
+
+        let no_more_moves = move_count > 0 && move_count == moves.len();
+        let value = if no_more_moves {
+            best_value
+        } else {
+            let (value, probe_state) = self.probe_table(game, false, 0);
+            if probe_state == ProbeState::Fail {
+                *state = ProbeState::Fail;
+                return 0;
+            }
+            value
+        };
+
+        if best_value >= value {
+            *state = if best_value > 0 || no_more_moves { ProbeState::ZeroingBestMove } else { ProbeState::Ok };
+            return best_value;
+        }
+        *state = ProbeState::Ok;
+        value
+    }
+
+    /**
+     * Probes the WDL value of the position, returns None if the position is
+     * not in the tables
+     */
+    pub fn probe_wdl(&self, game: &Game) -> Option<Wdl> {
+        if !self.can_probe(game) {
+            return None;
+        }
+        let mut state = ProbeState::Ok;
+        let value = self.search(&mut game.clone(), false, &mut state);
+        if state == ProbeState::Fail {
+            return None;
+        }
+        Some(Wdl::from_value(value))
+    }
+
+    /// The DTZ of the position just before a zeroing move with the given result.
+    fn dtz_before_zeroing(wdl: i32) -> i32 {
+        match wdl {
+            2 => 1,
+            1 => 101,
+            -1 => -101,
+            -2 => -1,
+            _ => 0,
+        }
+    }
+
+    fn dtz(&self, game: &mut Game, state: &mut ProbeState) -> i32 {
+        *state = ProbeState::Ok;
+        let wdl = self.search(game, true, state);
+        if *state == ProbeState::Fail || wdl == 0 {
+            return 0;
+        }
+        if *state == ProbeState::ZeroingBestMove {
+            return Tablebase::dtz_before_zeroing(wdl);
+        }
+
+        let (dtz, probe_state) = self.probe_table(game, true, wdl);
+        if probe_state == ProbeState::Fail {
+            *state = ProbeState::Fail;
+            return 0;
+        }
+        if probe_state != ProbeState::ChangeStm {
+            let cursed = if wdl == 1 || wdl == -1 { 100 } else { 0 };
+            return (dtz + cursed) * wdl.signum();
+        }
+
+        // the table has the other side to move, so search one ply and take the best DTZ
+        let mut min_dtz = 0xFFFF;
+        for mv in game.generate_legal_moves() {
+            let zeroing = game.is_capture(mv) || game.board[mv.from as usize][0] == _PAWN;
+            game.do_move(mv);
+            let mut dtz = if zeroing {
+                -Tablebase::dtz_before_zeroing(self.search(game, false, state))
+            } else {
+                -self.dtz(game, state)
+            };
+            if dtz == 1 && game.is_king_check() && game.generate_legal_moves().is_empty() {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+            game.undo_move();
+            if *state == ProbeState::Fail {
+                return 0;
+            }
+        }
+        if min_dtz == 0xFFFF { -1 } else { min_dtz }
+    }
+
+    /**
+     * Probes the distance to zero of the position in plies, returns None if the position
+     * is not in the tables. The value is counted the way the DTZ tables do, so a position one
+     * move before a winning capture or pawn move has DTZ 1, and wins that are only draws under
+     * the 50 move rule have 100 added
+     */
+    pub fn probe_dtz(&self, game: &Game) -> Option<i32> {
+        if !self.can_probe(game) {
+            return None;
+        }
+        let mut state = ProbeState::Ok;
+        let dtz = self.dtz(&mut game.clone(), &mut state);
+        if state == ProbeState::Fail {
+            return None;
+        }
+        Some(dtz)
+    }
+}
+
+/* The tablebase used by Game::probe_tablebase and the search */
+static TABLEBASE: RwLock<Option<Arc<Tablebase>>> = RwLock::new(None);
+
+/**
+ * Sets the directory with the Syzygy files used by Game::probe_tablebase and by the search,
+ * returns the most pieces of the tables found. None turns probing off
+ */
+pub fn set_tablebase_path<P: AsRef<Path>>(dir: Option<P>) -> io::Result<usize> {
+    let tablebase = match dir {
+        Some(dir) => Some(Arc::new(Tablebase::open(dir)?)),
+        None => None,
+    };
+    let max_pieces = tablebase.as_ref().map_or(0, |tablebase| tablebase.max_pieces());
+    *TABLEBASE.write().unwrap() = tablebase;
+    Ok(max_pieces)
+}
+
+/// Returns the tablebase set with set_tablebase_path, if there is one.
+pub(crate) fn tablebase() -> Option<Arc<Tablebase>> {
+    TABLEBASE.read().unwrap().clone()
+}
+
+impl Game {
+
+    /// Checks if the move takes a piece, en passante included.
+    pub(crate) fn is_capture(&self, mv: crate::Move) -> bool {
+        (self.board[mv.to as usize][0] != _NONE && self.board[mv.to as usize][1] != self.board[mv.from as usize][1])
+            || (self.board[mv.from as usize][0] == _PAWN && mv.from % 8 != mv.to % 8)
+    }
+
+    /**
+     * Looks up the position in the Syzygy tables set with set_tablebase_path and returns
+     * the win, draw or loss for the player whose turn it is and the distance to zero.
+     * Returns None if no tables are set or the position is not in them, positions with
+     * castling rights are never in the tables
+     */
+    pub fn probe_tablebase(&self) -> Option<TablebaseProbe> {
+        let tablebase = tablebase()?;
+        let wdl = tablebase.probe_wdl(self)?;
+        let dtz = tablebase.probe_dtz(self)?;
+        Some(TablebaseProbe { wdl, dtz })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{maps, material_key, Tablebase, Wdl};
+    use crate::Game;
+
+    #[test]
+    fn index_tables_have_the_right_sizes() {
+        let maps = maps();
+        // 462 ways to place the kings, so the biggest code is 461
+        assert_eq!(maps.map_kk.iter().flatten().max(), Some(&461));
+        assert_eq!(maps.binomial[2][10], 45);
+        assert_eq!(maps.map_pawns[8], 47);
+        assert_eq!(maps.map_a1d1d4[27], 9);
+    }
+
+    #[test]
+    fn material_key_lists_white_first() {
+        let mut game = Game::new();
+        game.load_fen_board("8/8/8/4k3/8/8/2P5/R3K3 b - - 0 1".to_string());
+        assert_eq!(material_key(&game), "KRPvK");
+    }
+
+    #[test]
+    fn lone_kings_are_a_draw_without_files() {
+        let dir = std::env::temp_dir().join("ogronman-chess-empty-tablebase");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("KvK.rtbw"), [0x71, 0xE8, 0x23, 0x5D]).unwrap();
+        let tablebase = Tablebase::open(&dir).unwrap();
+
+        let mut game = Game::new();
+        game.load_fen_board("8/8/8/4k3/8/8/8/4K3 w - - 0 1".to_string());
+        assert_eq!(tablebase.probe_wdl(&game), Some(Wdl::Draw));
+        assert_eq!(tablebase.probe_dtz(&game), Some(0));
+
+        // a position with more pieces than the tables is never probed
+        game.init_board();
+        assert_eq!(tablebase.probe_wdl(&game), None);
+    }
+
+    #[test]
+    fn broken_tables_are_not_probed() {
+        let dir = std::env::temp_dir().join("ogronman-chess-broken-tablebase");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut game = Game::new();
+        // random bytes after the right magic and flags, every probe of them fails instead of panicking
+        let mut seed: u64 = 0x9E3779B97F4A7C15;
+        for round in 0..200 {
+            let mut bytes = vec![0x71, 0xE8, 0x23, 0x5D, 0];
+            for _ in 0..64 + round * 20 {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                bytes.push(seed as u8);
+            }
+            std::fs::write(dir.join("KQvK.rtbw"), &bytes).unwrap();
+            bytes[4] = 2;
+            std::fs::write(dir.join("KPvK.rtbw"), &bytes).unwrap();
+
+            let tablebase = Tablebase::open(&dir).unwrap();
+            for fen in ["7k/8/6K1/8/8/8/8/Q7 w - - 0 1", "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"] {
+                game.load_fen_board(fen.to_string());
+                let _ = tablebase.probe_wdl(&game);
+                let _ = tablebase.probe_dtz(&game);
+            }
+        }
+
+        // a block size of 2^64 was a shift overflow while the cache was locked
+        let mut bytes = vec![0x71, 0xE8, 0x23, 0x5D, 0, 0x00, 0x55, 0x66, 0xEE, 0, 0, 64];
+        bytes.resize(256, 0);
+        std::fs::write(dir.join("KQvK.rtbw"), &bytes).unwrap();
+        let tablebase = Tablebase::open(&dir).unwrap();
+        game.load_fen_board("7k/8/6K1/8/8/8/8/Q7 w - - 0 1".to_string());
+        assert_eq!(tablebase.probe_wdl(&game), None);
+        assert_eq!(tablebase.probe_wdl(&game), None);
+    }
+
+    /**
+     * Probes the real KQvK, KRvK and KPvK files in tests/syzygy. They are not in the
+     * repository, download them first and run the test with cargo test -- --ignored:
+     *
+     * mkdir -p tests/syzygy && cd tests/syzygy
+     * for t in KQvK KRvK KPvK; do for e in rtbw rtbz; do
+     *     curl -O https://tablebase.lichess.ovh/tables/standard/3-4-5/$t.$e; done; done
+     */
+    #[test]
+    #[ignore = "needs the KQvK, KRvK and KPvK tables in tests/syzygy"]
+    fn probes_real_tables() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("syzygy");
+        for file in ["KQvK", "KRvK", "KPvK"].iter().flat_map(|code| [format!("{}.rtbw", code), format!("{}.rtbz", code)]) {
+            assert!(dir.join(&file).exists(), "{} is not in {}", file, dir.display());
+        }
+        let tablebase = Tablebase::open(&dir).unwrap();
+        assert_eq!(tablebase.max_pieces(), 3);
+
+        let probe = |fen: &str| {
+            let mut game = Game::new();
+            game.load_fen_board(fen.to_string());
+            (tablebase.probe_wdl(&game).unwrap(), tablebase.probe_dtz(&game).unwrap())
+        };
+        // mate in one with Qa8 and Ra8
+        assert_eq!(probe("7k/8/6K1/8/8/8/8/Q7 w - - 0 1"), (Wdl::Win, 1));
+        assert_eq!(probe("7k/8/6K1/8/8/8/8/R7 w - - 0 1"), (Wdl::Win, 1));
+        let (wdl, dtz) = probe("7k/8/6K1/8/8/8/8/Q7 b - - 0 1");
+        assert!(wdl == Wdl::Loss && dtz < 0);
+        // the king takes the rook
+        assert_eq!(probe("8/8/8/8/8/8/1k6/R6K b - - 0 1"), (Wdl::Draw, 0));
+        // the king in front of its pawn on the sixth rank wins, a rook pawn does not
+        assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1").0, Wdl::Win);
+        assert_eq!(probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1").0, Wdl::Loss);
+        assert_eq!(probe("k7/8/8/8/8/8/P7/K7 w - - 0 1"), (Wdl::Draw, 0));
+    }
+}
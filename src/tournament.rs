@@ -0,0 +1,498 @@
+use std::io;
+
+use crate::ai::Ai;
+use crate::engine_match::{play_engine_game, Adjudication, EngineGame, Opening, TimeControl, UciEngine};
+use crate::pgn::PgnGame;
+use crate::player::Outcome;
+
+/* How many pairings the Swiss search tries before it gives up and allows rematches, the search grows factorially */
+const PAIRING_TRIES: u32 = 100_000;
+
+/// What plays for a player in a tournament.
+#[derive(Clone, Debug)]
+pub enum Entrant {
+    /// The ai of this crate with its own settings.
+    Ai(Ai),
+    /// A UCI engine started as a subprocess.
+    Engine { command: String, args: Vec<String> },
+}
+
+/// A player of a tournament, the name is used in the standings and the games.
+#[derive(Clone, Debug)]
+pub struct TournamentPlayer {
+    pub name: String,
+    pub entrant: Entrant,
+}
+
+/**
+ * How players are paired. In a round robin everyone plays everyone once in each cycle, with
+ * the colors switched in every other cycle. In a Swiss tournament players with the same
+ * points are paired for the given number of rounds, without rematches when it can be helped
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    RoundRobin { cycles: u32 },
+    Swiss { rounds: u32 },
+}
+
+/// How the games of a tournament are played.
+#[derive(Clone, Debug)]
+pub struct TournamentConfig {
+    pub format: Format,
+    pub time_control: TimeControl,
+    pub openings: Vec<Opening>,
+    pub adjudication: Adjudication,
+}
+
+/**
+ * A game of a tournament by the numbers of the players. A bye has no black player,
+ * it is worth a point in a Swiss tournament and nothing in a round robin
+ */
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Pairing {
+    pub round: u32,
+    pub white: usize,
+    pub black: Option<usize>,
+}
+
+/// A played game or a bye, with the outcome of the game.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TournamentResult {
+    pub pairing: Pairing,
+    pub outcome: Outcome,
+}
+
+/**
+ * The points of a player and the tiebreaks. Buchholz is the sum of the points of the
+ * opponents, Sonneborn-Berger the points of the beaten opponents and half the points
+ * of the opponents drawn against
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standing {
+    pub player: usize,
+    pub points: f64,
+    pub games: u32,
+    pub sonneborn_berger: f64,
+    pub buchholz: f64,
+}
+
+/**
+ * Pairs players for every round of a round robin with the circle method, one player stays
+ * in place and the others turn around it. With an odd number of players the one paired
+ * with the empty place has a bye
+ */
+pub fn round_robin_pairings(players: usize, cycles: u32) -> Vec<Vec<Pairing>> {
+    let seats = players + players % 2;
+    let mut circle: Vec<Option<usize>> = (0..players).map(Some).collect();
+    if seats > players {
+        circle.push(None);
+    }
+    let rounds_per_cycle = seats.saturating_sub(1) as u32;
+    let mut rounds = Vec::new();
+
+    for cycle in 0..cycles {
+        let mut order = circle.clone();
+        for round in 0..rounds_per_cycle {
+            let number = cycle * rounds_per_cycle + round + 1;
+            let mut pairings = Vec::new();
+            for i in 0..seats / 2 {
+                let (a, b) = (order[i], order[seats - 1 - i]);
+                // the player that stays in place switches colors every round, the others by table
+                let swap = if i == 0 { round % 2 == 1 } else { i % 2 == 1 };
+                let (white, black) = if swap != (cycle % 2 == 1) { (b, a) } else { (a, b) };
+                match (white, black) {
+                    (Some(white), black) => pairings.push(Pairing { round: number, white, black }),
+                    (None, Some(player)) => pairings.push(Pairing { round: number, white: player, black: None }),
+                    (None, None) => {}
+                }
+            }
+            rounds.push(pairings);
+            let last = order.pop().unwrap_or(None);
+            order.insert(1, last);
+        }
+    }
+    rounds
+}
+
+/**
+ * The Swiss pairings of the next round. Players are ranked by points and then by their
+ * number, the lowest ranked player that has not had a bye gets one if the number of players
+ * is odd, and the rest are paired from the top with the next player they have not met.
+ * The player that has had white less often gets white
+ */
+pub fn swiss_pairings(players: usize, results: &[TournamentResult], round: u32) -> Vec<Pairing> {
+    let standings = standings(players, results, true);
+    let mut ranked: Vec<usize> = standings.iter().map(|standing| standing.player).collect();
+    let mut pairings = Vec::new();
+
+    if ranked.len() % 2 == 1 {
+        let had_bye = |player: usize| results.iter().any(|result| result.pairing.white == player && result.pairing.black.is_none());
+        let index = ranked.iter().rposition(|player| !had_bye(*player)).unwrap_or(ranked.len() - 1);
+        pairings.push(Pairing { round, white: ranked.remove(index), black: None });
+    }
+
+    let met = |a: usize, b: usize| results.iter().any(|result| {
+        let pairing = result.pairing;
+        (pairing.white == a && pairing.black == Some(b)) || (pairing.white == b && pairing.black == Some(a))
+    });
+    let mut tries = PAIRING_TRIES;
+    let pairs = pair_without_rematches(&ranked, &met, &mut tries).unwrap_or_else(|| pair_greedily(&ranked, &met));
+
+    let whites = |player: usize| results.iter().filter(|result| result.pairing.white == player && result.pairing.black.is_some()).count();
+    let blacks = |player: usize| results.iter().filter(|result| result.pairing.black == Some(player)).count();
+    for (a, b) in pairs {
+        let balance = |player: usize| whites(player) as i64 - blacks(player) as i64;
+        let (white, black) = if balance(b) < balance(a) { (b, a) } else { (a, b) };
+        pairings.push(Pairing { round, white, black: Some(black) });
+    }
+    pairings
+}
+
+/**
+ * Pairs the players in order, each with the highest ranked player left it has not met,
+ * and goes back to try other opponents when the players left can not be paired. Returns
+ * None if there is no such pairing, or if it is not found in the given number of tries
+ */
+fn pair_without_rematches<F: Fn(usize, usize) -> bool>(players: &[usize], met: &F, tries: &mut u32) -> Option<Vec<(usize, usize)>> {
+    let (first, rest) = match players.split_first() {
+        Some(split) => split,
+        None => return Some(Vec::new()),
+    };
+    for (index, opponent) in rest.iter().enumerate() {
+        if met(*first, *opponent) {
+            continue;
+        }
+        if *tries == 0 {
+            return None;
+        }
+        *tries -= 1;
+        let mut left = rest.to_vec();
+        left.remove(index);
+        if let Some(mut pairs) = pair_without_rematches(&left, met, tries) {
+            pairs.insert(0, (*first, *opponent));
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+/**
+ * Pairs the players in order, each with the highest ranked player left it has not met or
+ * with the next one if it has met them all, so the rematches end up at the bottom
+ */
+fn pair_greedily<F: Fn(usize, usize) -> bool>(players: &[usize], met: &F) -> Vec<(usize, usize)> {
+    let mut left = players.to_vec();
+    let mut pairs = Vec::new();
+    while left.len() >= 2 {
+        let first = left.remove(0);
+        let index = left.iter().position(|opponent| !met(first, *opponent)).unwrap_or(0);
+        pairs.push((first, left.remove(index)));
+    }
+    pairs
+}
+
+/// The points white and black got in a result.
+fn points(result: &TournamentResult, bye_point: bool) -> (f64, f64) {
+    if result.pairing.black.is_none() {
+        return (if bye_point { 1.0 } else { 0.0 }, 0.0);
+    }
+    match result.outcome {
+        Outcome::WhiteWins => (1.0, 0.0),
+        Outcome::BlackWins => (0.0, 1.0),
+        Outcome::Draw => (0.5, 0.5),
+    }
+}
+
+/**
+ * The standings after the results, ranked by points, then Sonneborn-Berger, then Buchholz
+ * and then by the number of the player. bye_point tells if a bye is worth a point
+ */
+pub fn standings(players: usize, results: &[TournamentResult], bye_point: bool) -> Vec<Standing> {
+    let mut totals = vec![0.0; players];
+    for result in results {
+        let (white, black) = points(result, bye_point);
+        totals[result.pairing.white] += white;
+        if let Some(player) = result.pairing.black {
+            totals[player] += black;
+        }
+    }
+
+    let mut standings: Vec<Standing> = (0..players).map(|player| Standing {
+        player,
+        points: totals[player],
+        games: 0,
+        sonneborn_berger: 0.0,
+        buchholz: 0.0,
+    }).collect();
+    for result in results {
+        let (white, black) = match result.pairing.black {
+            Some(black) => (result.pairing.white, black),
+            None => continue,
+        };
+        let (white_points, black_points) = points(result, bye_point);
+        for (player, opponent, scored) in [(white, black, white_points), (black, white, black_points)] {
+            let standing = &mut standings[player];
+            standing.games += 1;
+            standing.buchholz += totals[opponent];
+            standing.sonneborn_berger += scored * totals[opponent];
+        }
+    }
+
+    standings.sort_by(|a, b| {
+        b.points.total_cmp(&a.points)
+            .then(b.sonneborn_berger.total_cmp(&a.sonneborn_berger))
+            .then(b.buchholz.total_cmp(&a.buchholz))
+            .then(a.player.cmp(&b.player))
+    });
+    standings
+}
+
+/**
+ * A tournament between players, which plays all its rounds with run and keeps the results
+ * and the games for the standings, the crosstable and the PGN archive
+ */
+pub struct Tournament {
+    players: Vec<TournamentPlayer>,
+    config: TournamentConfig,
+    results: Vec<TournamentResult>,
+    games: Vec<PgnGame>,
+}
+
+impl Tournament {
+    pub fn new(players: Vec<TournamentPlayer>, config: TournamentConfig) -> Tournament {
+        Tournament { players, config, results: Vec::new(), games: Vec::new() }
+    }
+
+    pub fn players(&self) -> &[TournamentPlayer] {
+        &self.players
+    }
+
+    pub fn results(&self) -> &[TournamentResult] {
+        &self.results
+    }
+
+    pub fn games(&self) -> &[PgnGame] {
+        &self.games
+    }
+
+    fn bye_point(&self) -> bool {
+        matches!(self.config.format, Format::Swiss { .. })
+    }
+
+    /**
+     * Starts the engines of the players and plays every round, on_game is called after
+     * every game with the pairing and the game
+     */
+    pub fn run<F>(&mut self, mut on_game: F) -> io::Result<()>
+        where F: FnMut(&Pairing, &EngineGame) {
+        let mut engines = Vec::new();
+        for player in &self.players {
+            let mut engine = match &player.entrant {
+                Entrant::Ai(ai) => UciEngine::from_ai(ai.clone())?,
+                Entrant::Engine { command, args } => UciEngine::start(command, args)?,
+            };
+            engine.set_name(&player.name);
+            engines.push(engine);
+        }
+
+        let rounds = match self.config.format {
+            Format::RoundRobin { cycles } => round_robin_pairings(self.players.len(), cycles),
+            Format::Swiss { rounds } => (0..rounds).map(|_| Vec::new()).collect(),
+        };
+        let start = vec![Opening::default()];
+        let openings = if self.config.openings.is_empty() { &start } else { &self.config.openings };
+
+        for (index, round) in rounds.into_iter().enumerate() {
+            let round = match self.config.format {
+                Format::Swiss { .. } => swiss_pairings(self.players.len(), &self.results, index as u32 + 1),
+                Format::RoundRobin { .. } => round,
+            };
+            // every round is played from the same opening
+            let opening = &openings[index % openings.len()];
+            for pairing in round {
+                let black = match pairing.black {
+                    Some(black) => black,
+                    None => {
+                        self.results.push(TournamentResult { pairing, outcome: Outcome::WhiteWins });
+                        continue;
+                    }
+                };
+                let (white_engine, black_engine) = two_mut(&mut engines, pairing.white, black);
+                let mut game = play_engine_game(white_engine, black_engine, opening, self.config.time_control, &self.config.adjudication);
+                game.pgn.set_tag("Event", "Tournament");
+                game.pgn.set_tag("Round", &pairing.round.to_string());
+
+                self.results.push(TournamentResult { pairing, outcome: game.outcome });
+                on_game(&pairing, &game);
+                self.games.push(game.pgn);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn standings(&self) -> Vec<Standing> {
+        standings(self.players.len(), &self.results, self.bye_point())
+    }
+
+    /**
+     * The standings as a table, with the result against every other player by rank,
+     * "1" for a win, "=" for a draw and "0" for a loss, and the tiebreaks
+     */
+    pub fn crosstable(&self) -> String {
+        let standings = self.standings();
+        let name_width = self.players.iter().map(|player| player.name.len()).max().unwrap_or(4).max(4);
+        let games_per_pair = match self.config.format {
+            Format::RoundRobin { cycles } => cycles.max(1) as usize,
+            Format::Swiss { .. } => 1,
+        };
+
+        let mut text = format!("{:>3}  {:<width$}  {:>5}", "#", "Name", "Pts", width = name_width);
+        for rank in 1..=standings.len() {
+            text.push_str(&format!("  {:>width$}", rank, width = games_per_pair));
+        }
+        text.push_str("     SB  Buchholz\n");
+
+        for (rank, standing) in standings.iter().enumerate() {
+            text.push_str(&format!("{:>3}  {:<width$}  {:>5.1}", rank + 1, self.players[standing.player].name, standing.points, width = name_width));
+            for opponent in &standings {
+                let mut cell = String::new();
+                for result in &self.results {
+                    let (white, black) = (result.pairing.white, result.pairing.black);
+                    let (white_points, black_points) = points(result, true);
+                    let scored = if white == standing.player && black == Some(opponent.player) {
+                        white_points
+                    } else if Some(standing.player) == black && white == opponent.player {
+                        black_points
+                    } else {
+                        continue;
+                    };
+                    cell.push(if scored == 1.0 { '1' } else if scored == 0.0 { '0' } else { '=' });
+                }
+                if opponent.player == standing.player {
+                    cell = "*".repeat(games_per_pair);
+                }
+                text.push_str(&format!("  {:>width$}", cell, width = games_per_pair));
+            }
+            text.push_str(&format!("  {:>5.2}  {:>8.1}\n", standing.sonneborn_berger, standing.buchholz));
+        }
+        text
+    }
+
+    /// Every game of the tournament as PGN, in the order they were played.
+    pub fn pgn(&self) -> String {
+        self.games.iter().map(PgnGame::to_pgn).collect()
+    }
+}
+
+/// Two different elements of a slice, both mutable.
+fn two_mut<T>(items: &mut [T], a: usize, b: usize) -> (&mut T, &mut T) {
+    if a < b {
+        let (left, right) = items.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    } else {
+        let (left, right) = items.split_at_mut(a);
+        (&mut right[0], &mut left[b])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{round_robin_pairings, standings, swiss_pairings, Entrant, Format, Pairing, Tournament, TournamentConfig, TournamentPlayer, TournamentResult};
+    use crate::engine_match::{Adjudication, TimeControl};
+    use crate::player::Outcome;
+    use crate::Ai;
+    use std::time::Duration;
+
+    fn result(round: u32, white: usize, black: usize, outcome: Outcome) -> TournamentResult {
+        TournamentResult { pairing: Pairing { round, white, black: Some(black) }, outcome }
+    }
+
+    #[test]
+    fn round_robin_pairs_everyone_once_with_byes() {
+        let rounds = round_robin_pairings(5, 1);
+        assert_eq!(rounds.len(), 5);
+        let mut met = Vec::new();
+        for round in &rounds {
+            assert_eq!(round.iter().filter(|pairing| pairing.black.is_none()).count(), 1);
+            for pairing in round.iter().filter(|pairing| pairing.black.is_some()) {
+                let black = pairing.black.unwrap();
+                met.push((pairing.white.min(black), pairing.white.max(black)));
+            }
+        }
+        met.sort();
+        met.dedup();
+        assert_eq!(met.len(), 10);
+
+        let double = round_robin_pairings(4, 2);
+        assert_eq!(double.len(), 6);
+        assert_eq!(double[0][0].white, double[3][0].black.unwrap());
+    }
+
+    #[test]
+    fn swiss_pairs_quickly_when_rematches_can_not_be_avoided() {
+        // 0 to 16 have not met each other and 17 to 19 have not met each other, everyone
+        // else has met, so the odd groups can not be paired without a rematch. The search
+        // only finds out at the bottom, after trying the pairings of the top group
+        let mut results = Vec::new();
+        for a in 0..17 {
+            for b in 17..20 {
+                results.push(result(1, a, b, Outcome::WhiteWins));
+            }
+        }
+        let pairings = swiss_pairings(20, &results, 2);
+        assert_eq!(pairings.len(), 10);
+        let mut paired: Vec<usize> = pairings.iter().flat_map(|pairing| [pairing.white, pairing.black.unwrap()]).collect();
+        paired.sort();
+        assert_eq!(paired, (0..20).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn swiss_avoids_rematches_and_gives_one_bye() {
+        let results = vec![
+            result(1, 0, 1, Outcome::WhiteWins),
+            result(1, 2, 3, Outcome::WhiteWins),
+            TournamentResult { pairing: Pairing { round: 1, white: 4, black: None }, outcome: Outcome::WhiteWins },
+        ];
+        let pairings = swiss_pairings(5, &results, 2);
+        assert_eq!(pairings.len(), 3);
+        let bye = pairings.iter().find(|pairing| pairing.black.is_none()).unwrap();
+        assert_ne!(bye.white, 4);
+        assert!(!pairings.iter().any(|pairing| (pairing.white, pairing.black) == (0, Some(1)) || (pairing.white, pairing.black) == (1, Some(0))));
+    }
+
+    #[test]
+    fn standings_use_sonneborn_berger_and_buchholz() {
+        let results = vec![
+            result(1, 0, 1, Outcome::WhiteWins),
+            result(2, 1, 2, Outcome::WhiteWins),
+            result(3, 2, 0, Outcome::WhiteWins),
+            result(4, 3, 0, Outcome::Draw),
+        ];
+        let table = standings(4, &results, false);
+        assert_eq!(table[0].player, 0);
+        assert_eq!(table[0].points, 1.5);
+        assert_eq!(table[0].sonneborn_berger, 1.0 + 0.25);
+        assert_eq!(table[0].buchholz, 1.0 + 1.0 + 0.5);
+        // 1 and 2 have a point each, but 2 beat the winner
+        assert_eq!(table[1].player, 2);
+        assert_eq!(table[3].player, 3);
+    }
+
+    #[test]
+    fn plays_a_small_tournament() {
+        let players = (0..3).map(|n| TournamentPlayer { name: format!("Ai {}", n), entrant: Entrant::Ai(Ai::default()) }).collect();
+        let config = TournamentConfig {
+            format: Format::RoundRobin { cycles: 1 },
+            time_control: TimeControl { base: Duration::from_secs(1), increment: Duration::ZERO },
+            openings: Vec::new(),
+            adjudication: Adjudication { max_moves: Some(4), ..Adjudication::default() },
+        };
+        let mut tournament = Tournament::new(players, config);
+        tournament.run(|_, _| {}).unwrap();
+
+        assert_eq!(tournament.games().len(), 3);
+        assert_eq!(tournament.results().len(), 6);
+        assert!(tournament.pgn().contains("[Event \"Tournament\"]"));
+        assert!(tournament.crosstable().contains("Ai 2"));
+    }
+}
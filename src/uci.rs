@@ -0,0 +1,450 @@
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::ai::Ai;
+use crate::search::{Analysis, AnalysisOptions, Score};
+use crate::tablebase::set_tablebase_path;
+use crate::variant::{variant_from_name, Standard, Variant};
+use crate::{Game, Move, ColorTurn};
+
+/* The deepest a search without a depth limit goes */
+pub(crate) const MAX_DEPTH: u32 = 64;
+/* Time kept back from the clock for the time it takes to send the move */
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+/* How many moves are left in the game when the GUI does not send movestogo */
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+/* The strongest skill level, which plays the best move found */
+const MAX_SKILL: u32 = 20;
+
+/* The output is shared with the thread that searches */
+pub(crate) type Output<W> = Arc<Mutex<W>>;
+
+pub(crate) fn send<W: Write>(output: &Output<W>, line: &str) {
+    let mut output = output.lock().unwrap();
+    // if the GUI has gone away there is no one left to tell
+    let _ = writeln!(output, "{}", line);
+    let _ = output.flush();
+}
+
+/**
+ * The limits of one go command, time is the time the search may use once it is not
+ * pondering anymore. The best move of an infinite or ponder search is not sent until
+ * stop or ponderhit
+ */
+#[derive(Clone, Debug, Default)]
+struct GoLimits {
+    depth: Option<u32>,
+    nodes: Option<u64>,
+    time: Option<Duration>,
+    infinite: bool,
+    ponder: bool,
+}
+
+/**
+ * Splits the time left on the clock over the moves left to the next time control,
+ * or DEFAULT_MOVES_TO_GO if there is no time control, and adds most of the increment
+ */
+pub(crate) fn time_for_move(clock: Duration, increment: Duration, moves_to_go: Option<u32>) -> Duration {
+    let moves_left = moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+    let budget = clock / moves_left + increment * 3 / 4;
+    // never plan to use more than the clock minus the overhead, or half of it when it is low
+    let most = clock.saturating_sub(MOVE_OVERHEAD).max(clock / 2);
+    budget.min(most)
+}
+
+/**
+ * Reads the arguments of a go command, the time for the move is either movetime or
+ * a part of the clock of the player whose turn it is
+ */
+fn parse_go(words: &[&str], turn: ColorTurn) -> GoLimits {
+    let mut limits = GoLimits::default();
+    let mut clock: Option<u64> = None;
+    let mut increment: u64 = 0;
+    let mut moves_to_go: Option<u32> = None;
+    let (time_word, inc_word) = if turn == ColorTurn::White { ("wtime", "winc") } else { ("btime", "binc") };
+
+    let mut iter = words.iter();
+    while let Some(word) = iter.next() {
+        let mut number = || iter.next().and_then(|value| value.parse::<i64>().ok()).unwrap_or(0).max(0) as u64;
+        match *word {
+            "depth" => limits.depth = Some(number() as u32),
+            "nodes" => limits.nodes = Some(number()),
+            "movetime" => limits.time = Some(Duration::from_millis(number())),
+            "movestogo" => moves_to_go = Some(number() as u32),
+            "infinite" => limits.infinite = true,
+            "ponder" => limits.ponder = true,
+            w if w == time_word => clock = Some(number()),
+            w if w == inc_word => increment = number(),
+            _ => {}
+        }
+    }
+
+    if let (Some(clock), None) = (clock, limits.time) {
+        limits.time = Some(time_for_move(Duration::from_millis(clock), Duration::from_millis(increment), moves_to_go));
+    }
+    if limits.depth.is_none() && limits.nodes.is_none() && limits.time.is_none() {
+        limits.infinite = true;
+    }
+    limits
+}
+
+/**
+ * Sets up the position from "startpos" or "fen <fen>" followed by "moves ...",
+ * moves after the first one that is not legal are left out. In Chess960 castling
+ * is written as the king taking its own rook
+ */
+fn parse_position(words: &[&str], engine: &EngineOptions) -> Game {
+    let mut game = Game::new();
+    game.set_chess960(engine.chess960);
+    game.set_variant(engine.variant.clone());
+    let moves_at = words.iter().position(|word| *word == "moves").unwrap_or(words.len());
+    match words.first() {
+        Some(&"fen") => game.load_fen_board(words[1..moves_at].join(" ")),
+        _ => game.init_board(),
+    }
+    for word in words.iter().skip(moves_at + 1) {
+        match game.parse_move(word) {
+            Some(mv) => {
+                game.play(mv);
+            }
+            None => break,
+        }
+    }
+    game
+}
+
+/// Writes a score the UCI way, "cp 35" or "mate -2".
+fn uci_score(score: Score) -> String {
+    match score {
+        Score::Centipawns(cp) => format!("cp {}", cp),
+        Score::Mate(moves) => format!("mate {}", moves),
+    }
+}
+
+/// Sends one info line for every line of the analysis.
+fn send_info<W: Write>(output: &Output<W>, analysis: &Analysis) {
+    let millis = analysis.time.as_millis() as u64;
+    let nps = analysis.nodes * 1000 / millis.max(1);
+    for (i, line) in analysis.lines.iter().enumerate() {
+        let pv: Vec<String> = line.moves.iter().map(Move::to_uci).collect();
+        send(output, &format!(
+            "info depth {} multipv {} score {} nodes {} nps {} time {} pv {}",
+            analysis.depth, i + 1, uci_score(line.score), analysis.nodes, nps, millis, pv.join(" ")
+        ));
+    }
+}
+
+/**
+ * Picks the move to play from the analysis. Below the highest skill level the score
+ * of every line gets a random bonus, the lower the skill the bigger, and the line with
+ * the best score after that is played
+ */
+fn pick_line(analysis: &Analysis, skill: u32) -> Option<&[Move]> {
+    if skill >= MAX_SKILL {
+        return analysis.lines.first().map(|line| line.moves.as_slice());
+    }
+    let mut rng = rand::thread_rng();
+    let noise = (MAX_SKILL - skill) as i32 * 15;
+    analysis.lines.iter()
+        .max_by_key(|line| {
+            let value = match line.score {
+                Score::Centipawns(cp) => cp,
+                Score::Mate(moves) if moves > 0 => 100_000 - moves,
+                Score::Mate(moves) => -100_000 - moves,
+            };
+            value + rng.gen_range(0..=noise)
+        })
+        .map(|line| line.moves.as_slice())
+}
+
+/// A search running on its own thread, with the flags used to control it.
+struct RunningSearch {
+    stop: Arc<AtomicBool>,
+    hold: Arc<AtomicBool>,
+    time: Option<Duration>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl RunningSearch {
+    fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.hold.store(false, Ordering::Relaxed);
+        let _ = self.handle.join();
+    }
+
+    fn is_done(&self) -> bool {
+        self.handle.is_finished()
+    }
+}
+
+/* The settings changed with setoption */
+struct EngineOptions {
+    hash: u32,
+    threads: u32,
+    multipv: usize,
+    skill: u32,
+    chess960: bool,
+    variant: Arc<dyn Variant>,
+}
+
+/**
+ * Starts searching the position on its own thread. The best move is sent when
+ * the search is done, unless it is held back by an infinite or ponder search
+ */
+fn start_search<W: Write + Send + 'static>(ai: &Ai, game: &Game, engine: &EngineOptions, limits: GoLimits, output: &Output<W>) -> RunningSearch {
+    let stop = Arc::new(AtomicBool::new(false));
+    let hold = Arc::new(AtomicBool::new(limits.infinite || limits.ponder));
+
+    // lower skill levels search less deep and look at more lines to pick from
+    let skill_depth = if engine.skill >= MAX_SKILL { MAX_DEPTH } else { 1 + engine.skill / 4 };
+    let options = AnalysisOptions {
+        multipv: if engine.skill >= MAX_SKILL { engine.multipv } else { engine.multipv.max(4) },
+        depth: limits.depth.unwrap_or(MAX_DEPTH).min(skill_depth),
+        nodes: limits.nodes,
+        movetime: if limits.infinite || limits.ponder { None } else { limits.time },
+        stop: Some(stop.clone()),
+    };
+    let book_move = if limits.infinite || limits.ponder {
+        None
+    } else {
+        ai.book.as_ref().and_then(|book| book.choose_move(game, ai.book_selection))
+    };
+
+    let game = game.clone();
+    let skill = engine.skill;
+    let output = output.clone();
+    let thread_stop = stop.clone();
+    let thread_hold = hold.clone();
+    let handle = thread::spawn(move || {
+        let line = match book_move {
+            Some(mv) => vec![mv],
+            None => {
+                let analysis = game.analyse_with(&options, |analysis| send_info(&output, analysis));
+                pick_line(&analysis, skill).map(<[Move]>::to_vec).unwrap_or_default()
+            }
+        };
+
+        // the GUI has to say stop or ponderhit before the move of an infinite or ponder search is sent
+        while thread_hold.load(Ordering::Relaxed) && !thread_stop.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        let reply = match line.as_slice() {
+            [] => "bestmove 0000".to_string(),
+            [best] => format!("bestmove {}", best.to_uci()),
+            [best, ponder, ..] => format!("bestmove {} ponder {}", best.to_uci(), ponder.to_uci()),
+        };
+        send(&output, &reply);
+    });
+
+    RunningSearch { stop, hold, time: limits.time, handle }
+}
+
+/**
+ * Runs the engine with the Universal Chess Interface, reading commands from input and
+ * writing replies to output until "quit" or the end of the input.
+ *
+ * The ai gives the opening book used outside of infinite and ponder searches. The search
+ * runs on its own thread, so "stop", "ponderhit" and "isready" are answered while it runs.
+ * Hash and Threads can be set but there is no hash table and the search uses one thread
+ */
+pub fn run<R: BufRead, W: Write + Send + 'static>(ai: Ai, input: R, output: W) {
+    let output: Output<W> = Arc::new(Mutex::new(output));
+    let mut engine = EngineOptions { hash: 16, threads: 1, multipv: 1, skill: MAX_SKILL, chess960: false, variant: Arc::new(Standard) };
+    let mut game = Game::new();
+    game.init_board();
+    let mut search: Option<RunningSearch> = None;
+
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => continue,
+        };
+
+        match command {
+            "uci" => {
+                send(&output, "id name ogronman-chess");
+                send(&output, "id author ogronman");
+                send(&output, &format!("option name Hash type spin default {} min 1 max 1024", engine.hash));
+                send(&output, &format!("option name Threads type spin default {} min 1 max 1", engine.threads));
+                send(&output, "option name MultiPV type spin default 1 min 1 max 64");
+                send(&output, &format!("option name Skill Level type spin default {} min 0 max {}", MAX_SKILL, MAX_SKILL));
+                send(&output, "option name Ponder type check default false");
+                send(&output, "option name SyzygyPath type string default <empty>");
+                send(&output, "option name UCI_Chess960 type check default false");
+                send(&output, "option name UCI_Variant type combo default chess var chess var kingofthehill var 3check var crazyhouse var atomic var antichess var horde var racingkings");
+                send(&output, "uciok");
+            }
+            "isready" => send(&output, "readyok"),
+            "ucinewgame" => {
+                if let Some(running) = search.take() {
+                    running.stop();
+                }
+                game = Game::new();
+                game.init_board();
+            }
+            "setoption" => {
+                let name_at = args.iter().position(|word| *word == "name").map_or(0, |i| i + 1);
+                let value_at = args.iter().position(|word| *word == "value").unwrap_or(args.len());
+                let name = args[name_at.min(value_at)..value_at].join(" ").to_lowercase();
+                let value = args.get(value_at + 1..).map(|words| words.join(" ")).unwrap_or_default();
+                let number = value.parse::<u32>().ok();
+                match name.as_str() {
+                    "hash" => engine.hash = number.unwrap_or(engine.hash).clamp(1, 1024),
+                    "threads" => engine.threads = number.unwrap_or(engine.threads).clamp(1, 1),
+                    "multipv" => engine.multipv = number.unwrap_or(1).clamp(1, 64) as usize,
+                    "skill level" | "skill" => engine.skill = number.unwrap_or(MAX_SKILL).min(MAX_SKILL),
+                    "uci_chess960" => engine.chess960 = value.eq_ignore_ascii_case("true"),
+                    "uci_variant" => match variant_from_name(&value) {
+                        Some(variant) => engine.variant = variant,
+                        None => send(&output, &format!("info string unknown variant {}", value)),
+                    },
+                    "syzygypath" => {
+                        let dir = if value.is_empty() || value == "<empty>" { None } else { Some(value.as_str()) };
+                        match set_tablebase_path(dir) {
+                            Ok(pieces) if dir.is_some() => send(&output, &format!("info string found tables with up to {} pieces", pieces)),
+                            Ok(_) => {}
+                            Err(err) => send(&output, &format!("info string could not read {}: {}", value, err)),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "position" => game = parse_position(args, &engine),
+            "go" => {
+                if let Some(running) = search.take() {
+                    running.stop();
+                }
+                let limits = parse_go(args, game.get_game_turn());
+                search = Some(start_search(&ai, &game, &engine, limits, &output));
+            }
+            "stop" => {
+                if let Some(running) = search.take() {
+                    running.stop();
+                }
+            }
+            "ponderhit" => {
+                // the ponder move was played, so search on with the time of a normal move
+                if let Some(running) = &search {
+                    running.hold.store(false, Ordering::Relaxed);
+                    if let Some(time) = running.time {
+                        let stop = running.stop.clone();
+                        thread::spawn(move || {
+                            thread::sleep(time);
+                            stop.store(true, Ordering::Relaxed);
+                        });
+                    }
+                }
+            }
+            "quit" => {
+                if let Some(running) = search.take() {
+                    running.stop();
+                }
+                break;
+            }
+            _ => {}
+        }
+
+        if search.as_ref().is_some_and(RunningSearch::is_done) {
+            search = None;
+        }
+    }
+
+    // at the end of the input a normal search is finished, an infinite one is stopped
+    if let Some(running) = search.take() {
+        if running.hold.load(Ordering::Relaxed) {
+            running.stop();
+        } else {
+            let _ = running.handle.join();
+        }
+    }
+}
+
+/* A writer the tests can read from after a protocol loop has taken it */
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for Buffer {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(bytes)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/** Feeds the commands to a protocol loop, UCI or XBoard, and returns everything it wrote */
+#[cfg(test)]
+pub(crate) fn run_commands(run: fn(Ai, &'static [u8], Buffer), commands: &'static str) -> String {
+    let buffer = Buffer::default();
+    run(Ai::default(), commands.as_bytes(), buffer.clone());
+    let bytes = buffer.0.lock().unwrap().clone();
+    String::from_utf8(bytes).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_go, parse_position, run, run_commands, EngineOptions, MAX_SKILL};
+    use crate::variant::Standard;
+    use crate::ColorTurn;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn answers_handshake_and_searches() {
+        let output = run_commands(run, "uci\nisready\nposition startpos moves e2e4\ngo depth 2\n");
+        assert!(output.contains("uciok"));
+        assert!(output.contains("readyok"));
+        assert!(output.contains("info depth 2 multipv 1 score cp"));
+        assert!(output.lines().last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn finds_mate_from_fen_and_stops_infinite_search() {
+        let output = run_commands(run, "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 2\n");
+        assert!(output.contains("score mate 1"));
+        assert!(output.contains("bestmove a1a8"));
+
+        let output = run_commands(run, "position startpos\ngo infinite\nstop\n");
+        assert_eq!(output.matches("bestmove").count(), 1);
+    }
+
+    #[test]
+    fn castles_as_the_king_taking_the_rook_in_chess960() {
+        let position = "position fen 4k3/8/8/8/8/8/PPPPPPPP/RK5R w AH - 0 1 moves b1h1 e8d8\n";
+        let engine = EngineOptions { hash: 16, threads: 1, multipv: 1, skill: MAX_SKILL, chess960: true, variant: Arc::new(Standard) };
+        let game = parse_position(&position.split_whitespace().skip(1).collect::<Vec<&str>>(), &engine);
+        assert_eq!(game.to_fen(), "3k4/8/8/8/8/8/PPPPPPPP/R4RK1 w - - 2 2");
+
+        let output = run_commands(run, "setoption name UCI_Chess960 value true\nsetoption name MultiPV value 64\nposition startpos moves e2e4 e7e5 g1f3 b8c6 f1c4 g8f6\ngo depth 1\n");
+        assert!(output.contains("e1h1"));
+    }
+
+    #[test]
+    fn plays_variants() {
+        let output = run_commands(run, "setoption name UCI_Variant value kingofthehill\nposition fen 4k3/8/8/8/8/4K3/8/q7 w - - 0 1\ngo depth 2\n");
+        assert!(output.contains("bestmove e3e4"));
+    }
+
+    #[test]
+    fn splits_the_clock_over_the_moves_left() {
+        let limits = parse_go(&["wtime", "60000", "btime", "1000", "winc", "1000"], ColorTurn::White);
+        assert_eq!(limits.time, Some(Duration::from_millis(2750)));
+        assert!(!limits.infinite);
+
+        let limits = parse_go(&["wtime", "60000", "btime", "1000", "movestogo", "1"], ColorTurn::Black);
+        assert_eq!(limits.time, Some(Duration::from_millis(950)));
+    }
+}
@@ -0,0 +1,383 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::antichess::Antichess;
+use crate::atomic::Atomic;
+use crate::crazyhouse::Crazyhouse;
+use crate::horde::Horde;
+use crate::racing_kings::RacingKings;
+use crate::eval;
+use crate::result::GameResult;
+use crate::fairy::{Fairy, Geometry, PieceDefinition};
+use crate::{Game, Move, ColorTurn, _KING, _WHITE};
+
+/* The standard starting position */
+pub(crate) const STANDARD_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+/* The hill of King of the Hill, d4, e4, d5 and e5 */
+const HILL: [u8; 4] = [27, 28, 35, 36];
+/* The bonus for a king the given number of king moves from the hill */
+const HILL_BONUS: [i32; 8] = [0, 250, 120, 50, 20, 0, 0, 0];
+/* The checks that win Three-check */
+const CHECKS_TO_WIN: u32 = 3;
+/* The bonus for having given no, one or two checks in Three-check */
+const CHECK_BONUS: [i32; 3] = [0, 150, 450];
+
+/**
+ * The rules of a chess variant. Every method has the standard chess rules as its default,
+ * so a variant only overrides the rules it changes. The exceptions are the draw by
+ * insufficient material and the Syzygy tables, which only standard chess has.
+ *
+ * Moves are found in two steps: generate_moves gives the moves the pieces can make,
+ * and every move is then made with make_move and kept if is_legal says the position
+ * after it is allowed. The game is over when outcome gives a result, or when the player
+ * to move has no legal moves and no_moves_result gives it
+ */
+pub trait Variant: fmt::Debug + Send + Sync {
+    /// The name of the variant, the way the PGN Variant tag writes it.
+    fn name(&self) -> &str;
+
+    /// The starting position as a full fen string.
+    fn start_fen(&self) -> &str {
+        STANDARD_FEN
+    }
+
+    /// The files and ranks of the board.
+    fn geometry(&self) -> Geometry {
+        Geometry::STANDARD
+    }
+
+    /**
+     * How the piece moves when the variant moves it by a piece definition, None for the
+     * pieces Game moves itself. The fen letters of pieces without one are not read
+     */
+    fn piece_definition(&self, _piece: u8) -> Option<&PieceDefinition> {
+        None
+    }
+
+    /// The moves the player to move can make, they may break a rule checked by is_legal.
+    fn generate_moves(&self, game: &Game) -> Vec<Move> {
+        game.standard_moves()
+    }
+
+    /**
+     * Makes the move on the board. do_move saves everything needed to take it back and
+     * changes the turn afterwards, so this only moves the pieces
+     */
+    fn make_move(&self, game: &mut Game, mv: Move) {
+        game.make_standard_move(mv);
+    }
+
+    /// Checks if the last move was allowed, the other player is to move.
+    fn is_legal(&self, game: &Game) -> bool {
+        !game.is_own_king_left_in_check()
+    }
+
+    /// Checks if the player to move is in check.
+    fn is_check(&self, game: &Game) -> bool {
+        game.is_king_attacked()
+    }
+
+    /// Checks if the checks each player gives are counted, they are written in the fen string.
+    fn counts_checks(&self) -> bool {
+        false
+    }
+
+    /// Checks if the players have pieces in hand, they are written in the fen string.
+    fn has_pockets(&self) -> bool {
+        false
+    }
+
+    /// The result if a rule of the variant has ended the game, None if it goes on.
+    fn outcome(&self, _game: &Game) -> Option<GameResult> {
+        None
+    }
+
+    /// The result when the player to move has no legal moves, checkmate or stalemate.
+    fn no_moves_result(&self, game: &Game) -> GameResult {
+        if game.is_king_check() {
+            let turn = game.get_game_turn();
+            GameResult::win_for(if turn == ColorTurn::White { ColorTurn::Black } else { ColorTurn::White })
+        } else {
+            GameResult::Draw
+        }
+    }
+
+    /// Evaluates the position in centipawns for the player to move, see eval::evaluate.
+    fn evaluate(&self, game: &Game) -> i32 {
+        eval::standard_evaluation(game)
+    }
+
+    /// Checks if neither player has the material to win, which draws the game. The variants win in other ways.
+    fn insufficient_material(&self, _game: &Game) -> bool {
+        false
+    }
+
+    /// Checks if the Syzygy tables, which hold the results of standard chess, give the results of the variant.
+    fn uses_standard_endgames(&self) -> bool {
+        false
+    }
+}
+
+/// Standard chess.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Standard;
+
+impl Variant for Standard {
+    fn name(&self) -> &str {
+        "Standard"
+    }
+
+    fn insufficient_material(&self, game: &Game) -> bool {
+        game.has_no_mating_material()
+    }
+
+    fn uses_standard_endgames(&self) -> bool {
+        true
+    }
+}
+
+/// King of the Hill, a player also wins by moving the king to the centre.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KingOfTheHill;
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &str {
+        "King of the Hill"
+    }
+
+    fn outcome(&self, game: &Game) -> Option<GameResult> {
+        // only the player who just moved can have reached the hill
+        HILL.iter().find(|_pos| game.board[**_pos as usize][0] == _KING)
+            .map(|_pos| GameResult::win_for(if game.board[*_pos as usize][1] == _WHITE { ColorTurn::White } else { ColorTurn::Black }))
+    }
+
+    fn evaluate(&self, game: &Game) -> i32 {
+        let mut score = eval::standard_evaluation(game);
+        for (_pos, square) in game.board.iter().enumerate().filter(|(_, square)| square[0] == _KING) {
+            let distance = HILL.iter().map(|hill| king_distance(_pos as u8, *hill)).min().unwrap_or(7);
+            let bonus = HILL_BONUS[distance as usize];
+            score += if square[1] == game.color_to_move() { bonus } else { -bonus };
+        }
+        score
+    }
+}
+
+/// The number of king moves between the squares.
+fn king_distance(from: u8, to: u8) -> u8 {
+    let files = (from % 8).abs_diff(to % 8);
+    let ranks = (from / 8).abs_diff(to / 8);
+    files.max(ranks)
+}
+
+/// Three-check, a player also wins by giving check three times.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ThreeCheck;
+
+impl Variant for ThreeCheck {
+    fn name(&self) -> &str {
+        "Three-check"
+    }
+
+    fn start_fen(&self) -> &str {
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 +0+0"
+    }
+
+    fn counts_checks(&self) -> bool {
+        true
+    }
+
+    fn outcome(&self, game: &Game) -> Option<GameResult> {
+        if game.checks[0] >= CHECKS_TO_WIN {
+            Some(GameResult::WhiteWins)
+        } else if game.checks[1] >= CHECKS_TO_WIN {
+            Some(GameResult::BlackWins)
+        } else {
+            None
+        }
+    }
+
+    fn evaluate(&self, game: &Game) -> i32 {
+        let bonus = |checks: u32| CHECK_BONUS[checks.min(2) as usize];
+        let (own, other) = if game.color_to_move() == _WHITE { (0, 1) } else { (1, 0) };
+        eval::standard_evaluation(game) + bonus(game.checks[own]) - bonus(game.checks[other])
+    }
+}
+
+/**
+ * Finds the variant with the name, in any case and with or without spaces and dashes,
+ * as the PGN Variant tag and the command line write it
+ */
+pub fn variant_from_name(name: &str) -> Option<Arc<dyn Variant>> {
+    let name: String = name.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase();
+    match name.as_str() {
+        "standard" | "chess" => Some(Arc::new(Standard)),
+        "kingofthehill" | "koth" => Some(Arc::new(KingOfTheHill)),
+        "threecheck" | "3check" => Some(Arc::new(ThreeCheck)),
+        "crazyhouse" | "zh" => Some(Arc::new(Crazyhouse)),
+        "atomic" => Some(Arc::new(Atomic)),
+        "antichess" | "losingchess" | "giveaway" => Some(Arc::new(Antichess)),
+        "horde" => Some(Arc::new(Horde)),
+        "racingkings" => Some(Arc::new(RacingKings)),
+        "capablanca" | "capa" => Some(Arc::new(Fairy::capablanca())),
+        "losalamos" => Some(Arc::new(Fairy::los_alamos())),
+        _ => None,
+    }
+}
+
+impl Game {
+
+    /**
+     * Plays the game by the rules of the variant. The board is left as it is,
+     * init_board sets up the starting position of the variant. A variant played on
+     * a board of another size empties the board, since the pieces no longer fit
+     */
+    pub fn set_variant(&mut self, variant: Arc<dyn Variant>) {
+        let geometry = variant.geometry();
+        self.variant = variant;
+        if geometry != self.geometry {
+            self.geometry = geometry;
+            self.board.clear();
+            self.history.clear();
+        }
+        if !self.board.is_empty() {
+            self.update_state();
+        }
+    }
+
+    pub fn variant(&self) -> &dyn Variant {
+        self.variant.as_ref()
+    }
+
+    /// The checks the player has given, which are only counted in variants such as Three-check.
+    pub fn checks(&self, color: ColorTurn) -> u32 {
+        self.checks[if color == ColorTurn::White { 0 } else { 1 }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{variant_from_name, Variant, STANDARD_FEN};
+    use crate::player::Termination;
+    use crate::search::AnalysisOptions;
+    use crate::result::GameResult;
+    use crate::{Ai, ColorTurn, Game, GameState, Move};
+    use std::sync::Arc;
+
+    /* Standard chess where rooks may not move and the game is drawn once a piece stands on e4 */
+    #[derive(Debug)]
+    struct Stiff;
+
+    impl Variant for Stiff {
+        fn name(&self) -> &str {
+            "Stiff"
+        }
+
+        fn generate_moves(&self, game: &Game) -> Vec<Move> {
+            let mut moves = game.standard_moves();
+            moves.retain(|mv| game.get_board_vec()[mv.from as usize][0] != 4);
+            moves
+        }
+
+        fn outcome(&self, game: &Game) -> Option<GameResult> {
+            if game.get_board_vec()[28][0] != 0 { Some(GameResult::Draw) } else { None }
+        }
+    }
+
+    fn start(variant: &str) -> Game {
+        let mut game = Game::new();
+        game.set_variant(variant_from_name(variant).unwrap());
+        game.init_board();
+        game
+    }
+
+    fn ai(depth: u32) -> Ai {
+        Ai { options: AnalysisOptions { depth, ..AnalysisOptions::default() }, ..Ai::default() }
+    }
+
+    fn play(game: &mut Game, moves: &str) {
+        for text in moves.split_whitespace() {
+            let mv = game.parse_move(text).or_else(|| game.parse_san(text)).unwrap();
+            assert!(game.play(mv), "{} is not legal", text);
+        }
+    }
+
+    #[test]
+    fn kings_win_on_the_hill() {
+        let mut game = start("King of the Hill");
+        play(&mut game, "e4 d5 Ke2 dxe4 Ke3 Kd7");
+        assert_eq!(game.result(), GameResult::Ongoing);
+        play(&mut game, "Kxe4");
+        assert_eq!(game.result(), GameResult::WhiteWins);
+        assert_eq!(game.termination(), Some(Termination::Variant));
+
+        // the ai walks into the hill instead of taking material
+        let mut game = start("koth");
+        game.load_fen_board("4k3/8/8/8/8/4K3/8/q7 w - - 0 1".to_string());
+        assert_eq!(ai(2).choose_move(&game).unwrap().to_uci(), "e3e4");
+    }
+
+    #[test]
+    fn three_checks_win() {
+        let mut game = start("Three-check");
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 +0+0");
+        play(&mut game, "e4 e5 Bb5 Nc6 Bxc6 dxc6 Qh5 Nf6");
+        assert_eq!(game.checks(ColorTurn::White), 0);
+        play(&mut game, "Qxf7+ Kxf7");
+        assert_eq!(game.checks(ColorTurn::White), 1);
+        assert!(game.to_fen().ends_with(" +1+0"));
+
+        game.load_fen_board("4k3/8/8/8/8/8/8/4K2R w - - 0 30 +2+0".to_string());
+        assert_eq!(game.fullmove_number, 30);
+        play(&mut game, "Rh8+");
+        assert_eq!(game.result(), GameResult::WhiteWins);
+        game.undo();
+        assert_eq!(game.checks(ColorTurn::White), 2);
+
+        // the lichess way writes the checks that are left before the move counters
+        game.load_fen_board("4k3/8/8/8/8/8/8/4K2R w - - 1+3 0 30".to_string());
+        assert_eq!(game.checks(ColorTurn::White), 2);
+        assert_eq!(ai(1).choose_move(&game).unwrap().to_uci(), "h1h8");
+    }
+
+    #[test]
+    fn standard_is_the_default() {
+        let mut game = Game::new();
+        game.init_board();
+        assert_eq!(game.variant().name(), "Standard");
+        assert_eq!(game.to_fen(), STANDARD_FEN);
+        assert_eq!(variant_from_name("Standard").unwrap().name(), "Standard");
+        assert!(variant_from_name("shogi").is_none());
+    }
+
+    #[test]
+    fn variants_change_moves_and_results() {
+        let mut game = Game::new();
+        game.set_variant(Arc::new(Stiff));
+        game.load_fen_board("rnbqkbnr/pppppppp/8/8/8/8/1PPPPPPP/RNBQKBNR w KQkq - 0 1".to_string());
+        assert!(game.parse_move("a1a7").is_none());
+
+        game.init_board();
+        game.play(game.parse_move("e2e4").unwrap());
+        assert_eq!(game.get_game_state(), GameState::GameOver);
+        assert_eq!(game.result(), GameResult::Draw);
+        game.undo();
+        assert_eq!(game.result(), GameResult::Ongoing);
+    }
+
+    #[test]
+    fn variants_state_their_own_draw_rules() {
+        let bare_kings = "4k3/8/8/8/8/8/8/4K3 w - - 0 1".to_string();
+        let mut game = Game::new();
+        game.load_fen_board(bare_kings.clone());
+        assert!(game.is_insufficient_material());
+        assert!(game.variant().uses_standard_endgames());
+
+        // only the variant decides, whatever its name
+        for variant in [Arc::new(Stiff) as Arc<dyn Variant>, variant_from_name("koth").unwrap()] {
+            game.set_variant(variant);
+            game.load_fen_board(bare_kings.clone());
+            assert!(!game.is_insufficient_material());
+            assert!(!game.variant().uses_standard_endgames());
+        }
+    }
+}
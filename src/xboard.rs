@@ -0,0 +1,348 @@
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::ai::Ai;
+use crate::player::Termination;
+use crate::result::GameResult;
+use crate::search::{Analysis, AnalysisOptions, Score};
+use crate::uci::{send, time_for_move, Output, MAX_DEPTH};
+use crate::{Game, Move, ColorTurn};
+
+/* Mate scores are sent as 100000 plus the number of moves to the mate */
+const MATE_OFFSET: i32 = 100000;
+
+/**
+ * Everything the main loop waits for, a line from the GUI or the move of a finished
+ * search. The searches are numbered so the move of a cancelled search can be ignored
+ */
+enum Event {
+    Command(String),
+    Done(u32, Option<Move>),
+    EndOfInput,
+}
+
+/**
+ * The time control set with level or st, and the clock set with time
+ */
+#[derive(Clone, Debug, Default)]
+struct TimeControl {
+    moves_per_session: u32,
+    increment: Duration,
+    fixed_time: Option<Duration>,
+    depth: Option<u32>,
+    clock: Option<Duration>,
+}
+
+impl TimeControl {
+
+    /**
+     * Reads "level MPS BASE INC", where BASE is minutes or minutes:seconds
+     * and MPS is 0 when the whole game has to be played in the base time
+     */
+    fn set_level(&mut self, args: &[&str]) {
+        if args.len() < 3 {
+            return;
+        }
+        self.moves_per_session = args[0].parse().unwrap_or(0);
+        // the clock starts at the base time until the GUI sends the real one with time
+        let (minutes, seconds) = args[1].split_once(':').unwrap_or((args[1], "0"));
+        let base = minutes.parse::<u64>().unwrap_or(0) * 60 + seconds.parse::<u64>().unwrap_or(0);
+        self.clock = Some(Duration::from_secs(base));
+        self.increment = Duration::from_secs_f64(args[2].parse::<f64>().unwrap_or(0.0).max(0.0));
+        self.fixed_time = None;
+    }
+
+    /// The time the engine may think about its move, None if there is no limit.
+    fn time_for_move(&self, game: &Game) -> Option<Duration> {
+        if let Some(time) = self.fixed_time {
+            return Some(time);
+        }
+        let clock = self.clock?;
+        let moves_to_go = if self.moves_per_session > 0 {
+            let moves_made = game.fullmove_number.saturating_sub(1);
+            Some(self.moves_per_session - moves_made % self.moves_per_session)
+        } else {
+            None
+        };
+        Some(time_for_move(clock, self.increment, moves_to_go))
+    }
+}
+
+/**
+ * Returns the result line to send if the board has decided the game, like "1-0 {White mates}"
+ */
+fn game_result(game: &Game) -> Option<String> {
+    let (result, termination) = game.board_result()?;
+    let reason = match termination {
+        Termination::Checkmate if result == GameResult::WhiteWins => "White mates",
+        Termination::Checkmate => "Black mates",
+        Termination::Stalemate => "Stalemate",
+        Termination::FiftyMoves => "50 move rule",
+        Termination::Repetition => "Draw by repetition",
+        Termination::InsufficientMaterial => "Insufficient material",
+        _ => "Variant rule",
+    };
+    Some(format!("{} {{{}}}", result, reason))
+}
+
+/**
+ * Sends the thinking output of a completed depth, "ply score time nodes pv" with the
+ * score in centipawns, the time in centiseconds and the moves in SAN
+ */
+fn send_thinking<W: Write>(output: &Output<W>, game: &Game, analysis: &Analysis) {
+    let line = match analysis.lines.first() {
+        Some(line) => line,
+        None => return,
+    };
+    let score = match line.score {
+        Score::Centipawns(cp) => cp,
+        Score::Mate(moves) if moves > 0 => MATE_OFFSET + moves,
+        Score::Mate(moves) => -MATE_OFFSET + moves,
+    };
+    let mut position = game.clone();
+    let mut pv: Vec<String> = Vec::new();
+    for mv in &line.moves {
+        pv.push(position.move_to_san(*mv));
+        position.play(*mv);
+    }
+    send(output, &format!(
+        "{} {} {} {} {}",
+        analysis.depth, score, analysis.time.as_millis() / 10, analysis.nodes, pv.join(" ")
+    ));
+}
+
+/**
+ * Runs the engine with the Chess Engine Communication Protocol used by xboard and WinBoard,
+ * reading commands from input and writing replies to output until "quit" or the end of the input.
+ *
+ * After "new" the engine plays black, "go" makes it play the side to move and "force" makes it
+ * only keep track of the moves. The engine thinks on its own thread, so "?" can make it move at once
+ */
+pub fn run<R: BufRead + Send + 'static, W: Write + Send + 'static>(ai: Ai, input: R, output: W) {
+    let output: Output<W> = Arc::new(Mutex::new(output));
+    let (events, inbox) = mpsc::channel();
+    let reader = events.clone();
+    thread::spawn(move || {
+        for line in input.lines() {
+            match line {
+                Ok(line) => {
+                    if reader.send(Event::Command(line)).is_err() {
+                        return;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        let _ = reader.send(Event::EndOfInput);
+    });
+
+    let mut game = Game::new();
+    game.init_board();
+    let mut engine_color: Option<ColorTurn> = Some(ColorTurn::Black);
+    let mut time_control = TimeControl::default();
+    let mut post = false;
+    let mut search_id = 0;
+    let mut searching: Option<Arc<AtomicBool>> = None;
+    let mut quitting = false;
+    // moves come after "usermove" unless the GUI rejects that feature
+    let mut bare_moves = false;
+
+    for event in inbox.iter() {
+        let line = match event {
+            Event::Command(line) => line,
+            Event::Done(id, mv) => {
+                if id != search_id || searching.is_none() {
+                    continue;
+                }
+                searching = None;
+                if let Some(mv) = mv {
+                    game.play(mv);
+                    send(&output, &format!("move {}", mv.to_uci()));
+                    if let Some(result) = game_result(&game) {
+                        send(&output, &result);
+                    }
+                }
+                if quitting {
+                    break;
+                }
+                continue;
+            }
+            Event::EndOfInput => {
+                // a move that is being thought about is still sent
+                if searching.is_none() {
+                    break;
+                }
+                quitting = true;
+                continue;
+            }
+        };
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => continue,
+        };
+
+        // every command but these makes the engine stop thinking without moving
+        if !matches!(command, "?" | "ping" | "time" | "otim" | "post" | "nopost" | "xboard" | "protover" | "accepted" | "rejected" | "hard" | "easy" | "computer" | "name" | "draw" | "hint" | "bk") {
+            if let Some(stop) = searching.take() {
+                stop.store(true, Ordering::Relaxed);
+            }
+        }
+
+        let mut user_move: Option<&str> = None;
+        match command {
+            "xboard" | "accepted" | "hard" | "easy" | "computer" | "name" | "otim" => {}
+            // draw offers are declined by playing on, and there are no hints, book lists or random play
+            "draw" | "hint" | "bk" | "random" => {}
+            // only sent by GUIs that do not understand colors=0, the side to move comes from the moves
+            "white" | "black" => {}
+            "rejected" => {
+                if args.first() == Some(&"usermove") {
+                    bare_moves = true;
+                }
+            }
+            "protover" => {
+                send(&output, "feature done=0");
+                send(&output, "feature myname=\"ogronman-chess\" setboard=1 usermove=1 ping=1 playother=1 colors=0 analyze=0 sigint=0 sigterm=0 reuse=1");
+                send(&output, "feature done=1");
+            }
+            "ping" => send(&output, &format!("pong {}", args.first().unwrap_or(&""))),
+            "new" => {
+                game = Game::new();
+                game.init_board();
+                engine_color = Some(ColorTurn::Black);
+                time_control.depth = None;
+            }
+            "force" | "result" => engine_color = None,
+            "go" => engine_color = Some(game.get_game_turn()),
+            "playother" => {
+                engine_color = Some(if game.get_game_turn() == ColorTurn::White { ColorTurn::Black } else { ColorTurn::White });
+            }
+            "setboard" => game.load_fen_board(args.join(" ")),
+            "undo" => {
+                game.undo();
+            }
+            "remove" => {
+                game.undo();
+                game.undo();
+            }
+            "level" => time_control.set_level(args),
+            "st" => {
+                let seconds = args.first().and_then(|s| s.parse::<f64>().ok()).unwrap_or(0.0).max(0.0);
+                time_control.fixed_time = Some(Duration::from_secs_f64(seconds));
+            }
+            "sd" => time_control.depth = args.first().and_then(|s| s.parse().ok()),
+            "time" => {
+                let centiseconds = args.first().and_then(|s| s.parse::<i64>().ok()).unwrap_or(0).max(0) as u64;
+                time_control.clock = Some(Duration::from_millis(centiseconds * 10));
+            }
+            "post" => post = true,
+            "nopost" => post = false,
+            "?" => {
+                if let Some(stop) = &searching {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            }
+            "quit" => {
+                if let Some(stop) = searching.take() {
+                    stop.store(true, Ordering::Relaxed);
+                }
+                break;
+            }
+            "usermove" => user_move = args.first().copied(),
+            // a GUI that rejects usermove=1 sends the moves on their own
+            _ if bare_moves => user_move = Some(command),
+            _ => send(&output, &format!("Error (unknown command): {}", command)),
+        }
+
+        if let Some(text) = user_move {
+            match game.parse_move(text).or_else(|| game.parse_san(text)) {
+                Some(mv) if game_result(&game).is_none() => {
+                    game.play(mv);
+                    if let Some(result) = game_result(&game) {
+                        send(&output, &result);
+                    }
+                }
+                _ if command == "usermove" || text.len() >= 4 => send(&output, &format!("Illegal move: {}", text)),
+                _ => send(&output, &format!("Error (unknown command): {}", text)),
+            }
+        }
+
+        // start thinking if it is the engine's turn and it is not thinking already
+        if searching.is_none() && engine_color == Some(game.get_game_turn()) && game_result(&game).is_none() {
+            search_id += 1;
+            let stop = Arc::new(AtomicBool::new(false));
+            searching = Some(stop.clone());
+
+            let options = AnalysisOptions {
+                multipv: 1,
+                depth: time_control.depth.unwrap_or(MAX_DEPTH),
+                nodes: None,
+                movetime: time_control.time_for_move(&game),
+                stop: Some(stop),
+            };
+            let book_move = ai.book.as_ref().and_then(|book| book.choose_move(&game, ai.book_selection));
+            let position = game.clone();
+            let thread_output = output.clone();
+            let events = events.clone();
+            let id = search_id;
+            thread::spawn(move || {
+                let mv = book_move.or_else(|| {
+                    position.analyse_with(&options, |analysis| {
+                        if post {
+                            send_thinking(&thread_output, &position, analysis);
+                        }
+                    }).best_move()
+                });
+                let _ = events.send(Event::Done(id, mv));
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run;
+    use crate::uci::run_commands;
+
+    #[test]
+    fn negotiates_features_and_answers_moves() {
+        let output = run_commands(run, "xboard\nprotover 2\nnew\nsd 2\npost\nusermove e2e4\n");
+        assert!(output.contains("feature done=1"));
+        assert!(output.contains("setboard=1"));
+        assert!(output.lines().any(|line| line.starts_with("2 ")));
+        assert!(output.lines().last().unwrap().starts_with("move "));
+    }
+
+    #[test]
+    fn reports_mate_and_rejects_illegal_moves() {
+        let output = run_commands(run, "new\nforce\nsetboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\nusermove e2e4\nsd 2\ngo\n");
+        assert!(output.contains("Illegal move: e2e4"));
+        assert!(output.contains("move a1a8"));
+        assert!(output.contains("1-0 {White mates}"));
+    }
+
+    #[test]
+    fn unknown_commands_are_not_taken_as_moves() {
+        let output = run_commands(run, "new\nforce\nusermove e2e4\ndraw\nhint\nbk\nfoo\nping 1\n");
+        assert!(!output.contains("Illegal move"));
+        assert!(output.contains("Error (unknown command): foo"));
+        assert!(output.contains("pong 1"));
+
+        // a GUI without usermove sends the moves on their own
+        let output = run_commands(run, "new\nforce\nrejected usermove\ne2e4\ne2e4\n");
+        assert_eq!(output.matches("Illegal move: e2e4").count(), 1);
+    }
+
+    #[test]
+    fn undo_and_force_keep_the_engine_quiet() {
+        let output = run_commands(run, "new\nforce\nusermove e2e4\nusermove e7e5\nremove\nundo\nping 7\n");
+        assert!(!output.contains("move "));
+        assert!(output.contains("pong 7"));
+    }
+}